@@ -1,6 +1,9 @@
 use eyre::{bail, Context, ContextCompat, Result};
-use hound::{SampleFormat, WavReader};
-use std::io::Read;
+use hound::{SampleFormat, WavReader, WavSpec};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
 use std::process::Stdio;
 use std::{path::PathBuf, process::Command};
 use which::which;
@@ -94,28 +97,140 @@ pub fn normalize(input: PathBuf, output: PathBuf, additional_ffmpeg_args: Option
     Ok(())
 }
 
-pub fn parse_wav_file(path: &PathBuf) -> Result<Vec<i16>> {
-    tracing::debug!("wav reader read from {:?}", path);
-    let reader = WavReader::open(path).context("failed to read file")?;
-    tracing::debug!("parsing {}", path.display());
-
-    let channels = reader.spec().channels;
-    if reader.spec().channels != 1 {
-        bail!("expected mono audio file and found {} channels!", channels);
+fn validate_wav_spec(spec: &WavSpec) -> Result<()> {
+    if spec.channels != 1 {
+        bail!("expected mono audio file and found {} channels!", spec.channels);
     }
-    if reader.spec().sample_format != SampleFormat::Int {
+    if spec.sample_format != SampleFormat::Int {
         bail!("expected integer sample format");
     }
-    if reader.spec().sample_rate != 16000 {
+    if spec.sample_rate != 16000 {
         bail!("expected 16KHz sample rate");
     }
-    if reader.spec().bits_per_sample != 16 {
+    if spec.bits_per_sample != 16 {
         bail!("expected 16 bits per sample");
     }
+    Ok(())
+}
+
+/// Hex encoded sha256 of the file content
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).context("failed to hash file")?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn parse_wav_file(path: &PathBuf) -> Result<Vec<i16>> {
+    tracing::debug!("wav reader read from {:?}", path);
+    let reader = WavReader::open(path).context("failed to read file")?;
+    tracing::debug!("parsing {}", path.display());
+
+    validate_wav_spec(&reader.spec())?;
 
     reader.into_samples::<i16>().map(|x| x.context("sample")).collect()
 }
 
+/// Window of samples read by [`WavChunks`]
+#[derive(Debug)]
+pub struct WavChunk {
+    /// Index of the first sample of the window in the file
+    pub offset: usize,
+    pub samples: Vec<i16>,
+    pub is_last: bool,
+}
+
+/// Reads a 16KHz mono wav file in overlapping windows.
+/// Only the current window is kept in memory, regardless of the file length.
+pub struct WavChunks {
+    reader: WavReader<BufReader<File>>,
+    window: usize,
+    overlap: usize,
+    offset: usize,
+    total: usize,
+    carry: Vec<i16>,
+    done: bool,
+}
+
+impl WavChunks {
+    /// `window` and `overlap` are in samples
+    pub fn open(path: &Path, window: usize, overlap: usize) -> Result<Self> {
+        tracing::debug!("wav chunks reader read from {:?}", path);
+        let reader = WavReader::open(path).context("failed to read file")?;
+        validate_wav_spec(&reader.spec())?;
+        if window == 0 || overlap >= window {
+            bail!("chunk overlap must be shorter than the chunk");
+        }
+        let total = reader.duration() as usize;
+        Ok(Self {
+            reader,
+            window,
+            overlap,
+            offset: 0,
+            total,
+            carry: Vec::new(),
+            done: false,
+        })
+    }
+
+    /// Total number of samples in the file
+    pub fn total_samples(&self) -> usize {
+        self.total
+    }
+
+    /// Continue reading windows from `offset` (in samples)
+    pub fn seek(&mut self, offset: usize) -> Result<()> {
+        if offset >= self.total {
+            bail!("seek offset {} is past the end of the file ({} samples)", offset, self.total);
+        }
+        self.reader.seek(offset as u32).context("failed to seek wav")?;
+        self.offset = offset;
+        self.carry.clear();
+        self.done = false;
+        Ok(())
+    }
+}
+
+impl Iterator for WavChunks {
+    type Item = Result<WavChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut samples = std::mem::take(&mut self.carry);
+        let needed = self.window - samples.len();
+        samples.reserve(needed);
+        for sample in self.reader.samples::<i16>().take(needed) {
+            match sample {
+                Ok(sample) => samples.push(sample),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error).context("sample"));
+                }
+            }
+        }
+
+        let offset = self.offset;
+        let is_last = samples.len() < self.window || offset + samples.len() >= self.total;
+        if is_last {
+            self.done = true;
+            if samples.len() <= self.overlap && offset > 0 {
+                // Everything left was already covered by the previous window
+                return None;
+            }
+        } else {
+            self.carry = samples[samples.len() - self.overlap..].to_vec();
+            self.offset += samples.len() - self.overlap;
+        }
+        Some(Ok(WavChunk {
+            offset,
+            samples,
+            is_last,
+        }))
+    }
+}
+
 /// Merge audio files, taking to shortest one and merge the others
 /// ffmpeg -i short.wav -i single.wav -filter_complex amix=inputs=2:duration=shortest -ac 2 merged.wav
 pub fn merge_wav_files(a: PathBuf, b: PathBuf, dst: PathBuf) -> Result<()> {
@@ -149,3 +264,65 @@ pub fn merge_wav_files(a: PathBuf, b: PathBuf, dst: PathBuf) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavWriter;
+    use tempfile::tempdir;
+
+    fn write_wav(path: &Path, num_samples: usize) {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 16000,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for i in 0..num_samples {
+            writer.write_sample(i as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_wav_chunks_overlap() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audio.wav");
+        write_wav(&path, 25);
+
+        let chunks: Vec<WavChunk> = WavChunks::open(&path, 10, 2).unwrap().map(|c| c.unwrap()).collect();
+        let offsets: Vec<usize> = chunks.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![0, 8, 16]);
+        assert_eq!(chunks[1].samples[0], 8);
+        assert_eq!(chunks[2].samples.len(), 9);
+        assert!(chunks[2].is_last);
+        assert!(!chunks[1].is_last);
+    }
+
+    #[test]
+    fn test_wav_chunks_seek() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audio.wav");
+        write_wav(&path, 25);
+
+        let mut chunks = WavChunks::open(&path, 10, 2).unwrap();
+        chunks.seek(8).unwrap();
+        let chunks: Vec<WavChunk> = chunks.map(|c| c.unwrap()).collect();
+        let offsets: Vec<usize> = chunks.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![8, 16]);
+        assert_eq!(chunks[0].samples[0], 8);
+    }
+
+    #[test]
+    fn test_wav_chunks_single_window() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audio.wav");
+        write_wav(&path, 5);
+
+        let chunks: Vec<WavChunk> = WavChunks::open(&path, 10, 2).unwrap().map(|c| c.unwrap()).collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].samples.len(), 5);
+        assert!(chunks[0].is_last);
+    }
+}
@@ -0,0 +1,240 @@
+use eyre::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Normalized audio is ~115MB per hour, keep a few hours around
+pub const DEFAULT_MAX_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Bytes hashed from the start and the end of the source file
+const PARTIAL_HASH_SIZE: u64 = 1024 * 1024;
+
+/// Cache of normalized wav files keyed by the source content and ffmpeg args.
+/// Least recently used files are evicted once the total size is above `max_size`.
+pub struct AudioCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Default for AudioCache {
+    fn default() -> Self {
+        // Outside of vibe_temp_* so it survives the startup cleanup
+        Self::new(std::env::temp_dir().join("vibe_cache"), DEFAULT_MAX_SIZE)
+    }
+}
+
+impl AudioCache {
+    pub fn new(dir: PathBuf, max_size: u64) -> Self {
+        Self { dir, max_size }
+    }
+
+    /// Key of a source file. Uses size, modified time and a partial content hash
+    /// so that keying a multi GB video doesn't read the whole file.
+    pub fn key(source: &Path, additional_ffmpeg_args: &Option<Vec<String>>) -> Result<String> {
+        let mut file = File::open(source).with_context(|| format!("failed to open {}", source.display()))?;
+        let metadata = file.metadata().context("failed to read metadata")?;
+        let size = metadata.len();
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_nanos())
+            .unwrap_or_default();
+
+        let mut hasher = Sha256::new();
+        hasher.update(size.to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+
+        let mut buf = Vec::new();
+        (&mut file).take(PARTIAL_HASH_SIZE).read_to_end(&mut buf)?;
+        hasher.update(&buf);
+        if size > PARTIAL_HASH_SIZE * 2 {
+            file.seek(SeekFrom::End(-(PARTIAL_HASH_SIZE as i64)))?;
+            buf.clear();
+            file.take(PARTIAL_HASH_SIZE).read_to_end(&mut buf)?;
+            hasher.update(&buf);
+        }
+
+        for arg in additional_ffmpeg_args.iter().flatten() {
+            hasher.update(arg.as_bytes());
+            hasher.update([0]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn path_of(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.wav", key))
+    }
+
+    /// Cached file of `key`, marked as recently used
+    pub fn get(&self, key: &str) -> Option<PathBuf> {
+        let path = self.path_of(key);
+        if !path.exists() {
+            return None;
+        }
+        if let Err(error) = File::options()
+            .append(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(SystemTime::now()))
+        {
+            // Evicted by another job since the check
+            if error.kind() == std::io::ErrorKind::NotFound {
+                return None;
+            }
+            tracing::warn!("failed to touch cached audio {}: {:?}", path.display(), error);
+        }
+        path.exists().then_some(path)
+    }
+
+    /// Create the cached file of `key` with `create`, which receives the path to write to.
+    /// The file only becomes visible to `get` once `create` succeeded.
+    /// Every call writes its own temp file, so jobs can insert the same key at the same time
+    pub fn insert<F>(&self, key: &str, create: F) -> Result<PathBuf>
+    where
+        F: FnOnce(&Path) -> Result<()>,
+    {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let path = self.path_of(key);
+        // Removed when dropped, including when `create` fails
+        let tmp_file = tempfile::Builder::new()
+            .prefix(key)
+            .suffix(".tmp.wav")
+            .tempfile_in(&self.dir)
+            .with_context(|| format!("failed to create temp file in {}", self.dir.display()))?;
+        create(tmp_file.path())?;
+        if let Err(error) = tmp_file.persist(&path) {
+            // Replacing a file that is open fails on Windows. Another job inserted the same audio
+            if !path.exists() {
+                return Err(error).with_context(|| format!("failed to move {}", path.display()));
+            }
+            tracing::debug!("keep cached audio {} of another job: {:?}", path.display(), error.error);
+        }
+        if let Err(error) = self.evict() {
+            tracing::warn!("failed to evict audio cache: {:?}", error);
+        }
+        // Evicted by another job in the meantime
+        if !path.exists() {
+            bail!("cached audio {} was evicted", path.display())
+        }
+        Ok(path)
+    }
+
+    /// Remove least recently used files until the cache fits in `max_size`.
+    /// The most recent file is always kept, even if it's bigger than the cap.
+    /// Files removed by another job meanwhile, or that can't be removed because they're in use, are skipped
+    pub fn evict(&self) -> Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.ends_with(".wav") || name.ends_with(".tmp.wav") {
+                continue;
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            entries.push((metadata.modified().unwrap_or(UNIX_EPOCH), metadata.len(), path));
+        }
+        entries.sort_by_key(|(modified, _, _)| *modified);
+
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        for (_, size, path) in entries.iter().take(entries.len().saturating_sub(1)) {
+            if total <= self.max_size {
+                break;
+            }
+            tracing::debug!("evict cached audio {}", path.display());
+            match std::fs::remove_file(path) {
+                Ok(()) => total -= size,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => total -= size,
+                Err(error) => tracing::warn!("failed to remove cached audio {}: {:?}", path.display(), error),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn write(path: &Path, size: usize) -> Result<()> {
+        std::fs::write(path, vec![0u8; size])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_depends_on_content_and_args() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mp3");
+        std::fs::write(&a, "content").unwrap();
+
+        let key = AudioCache::key(&a, &None).unwrap();
+        assert_eq!(key, AudioCache::key(&a, &None).unwrap());
+        assert_ne!(key, AudioCache::key(&a, &Some(vec!["-af".into(), "volume=2".into()])).unwrap());
+
+        std::fs::write(&a, "changed").unwrap();
+        assert_ne!(key, AudioCache::key(&a, &None).unwrap());
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let dir = tempdir().unwrap();
+        let cache = AudioCache::new(dir.path().join("cache"), 1000);
+        assert!(cache.get("key").is_none());
+
+        let path = cache.insert("key", |path| write(path, 10)).unwrap();
+        assert_eq!(cache.get("key"), Some(path));
+
+        assert!(cache.insert("failed", |_| eyre::bail!("ffmpeg failed")).is_err());
+        assert!(cache.get("failed").is_none());
+        // Temp files are removed
+        assert_eq!(std::fs::read_dir(dir.path().join("cache")).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_insert() {
+        let dir = tempdir().unwrap();
+        let cache = AudioCache::new(dir.path().to_path_buf(), 1000);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        cache.insert("key", |path| {
+                            write(path, 10)?;
+                            std::thread::sleep(Duration::from_millis(20));
+                            Ok(())
+                        })
+                    })
+                })
+                .collect();
+            for handle in handles {
+                assert_eq!(handle.join().unwrap().unwrap(), dir.path().join("key.wav"));
+            }
+        });
+        assert_eq!(std::fs::read(cache.get("key").unwrap()).unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let dir = tempdir().unwrap();
+        let cache = AudioCache::new(dir.path().to_path_buf(), 250);
+
+        cache.insert("first", |path| write(path, 100)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        cache.insert("second", |path| write(path, 100)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        // Using the first makes the second the least recently used
+        cache.get("first").unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        cache.insert("third", |path| write(path, 100)).unwrap();
+
+        assert!(cache.get("first").is_some());
+        assert!(cache.get("second").is_none());
+        assert!(cache.get("third").is_some());
+    }
+}
@@ -0,0 +1,283 @@
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rubato::{FftFixedIn, Resampler};
+use std::fs::File;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Sample rate whisper expects
+const TARGET_SAMPLE_RATE: u32 = 16000;
+
+/// Input frames passed to the resampler on every call
+const RESAMPLER_CHUNK_SIZE: usize = 1024;
+
+/// Decode any supported audio file (mp3, flac, ogg/vorbis, m4a/aac, wav) and write it
+/// as 16kHz mono pcm_s16le wav, the same output `audio::normalize` produces with ffmpeg.
+///
+/// Decoding is streamed packet by packet so memory stays flat regardless of file length.
+pub fn decode_to_wav(input: &Path, output: &Path) -> Result<()> {
+    tracing::debug!("native decode {} to {}", input.display(), output.display());
+    let file = File::open(input).with_context(|| format!("failed to open {}", input.display()))?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = input.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, mss, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| eyre!("unsupported format: {:?}", e))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .context("no audio track found")?;
+    let track_id = track.id;
+    let sample_rate = track.codec_params.sample_rate.context("unknown sample rate")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| eyre!("unsupported codec: {:?}", e))?;
+    tracing::debug!("decoding track {} with sample rate {}", track_id, sample_rate);
+
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate: TARGET_SAMPLE_RATE,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer = WavWriter::create(output, spec).context("failed to create wav writer")?;
+    let mut resampler = MonoResampler::new(sample_rate)?;
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+    let mut mono = Vec::new();
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(error) => bail!("failed to read packet: {:?}", error),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(error)) => {
+                tracing::warn!("skip corrupted packet: {}", error);
+                continue;
+            }
+            Err(error) => bail!("failed to decode packet: {:?}", error),
+        };
+
+        let channels = decoded.spec().channels.count();
+        let buf = match sample_buf.as_mut() {
+            Some(buf) if buf.capacity() >= decoded.capacity() * channels => buf,
+            _ => sample_buf.insert(SampleBuffer::new(decoded.capacity() as u64, *decoded.spec())),
+        };
+        buf.copy_interleaved_ref(decoded);
+
+        mono.clear();
+        mono.extend(
+            buf.samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+        resampler.push(&mono, |sample| writer.write_sample(sample))?;
+    }
+    resampler.finish(|sample| writer.write_sample(sample))?;
+    writer.finalize().context("failed to finalize wav")?;
+
+    if !output.exists() {
+        bail!("native decoder failed for some reason. output not exists")
+    }
+    Ok(())
+}
+
+/// Streaming mono resampler to `TARGET_SAMPLE_RATE`.
+/// Trims the resampler delay so the output length matches the input duration.
+struct MonoResampler {
+    inner: Option<FftFixedIn<f32>>,
+    pending: Vec<f32>,
+    skip: usize,
+    input_frames: u64,
+    output_frames: u64,
+    sample_rate: u32,
+}
+
+impl MonoResampler {
+    fn new(sample_rate: u32) -> Result<Self> {
+        let inner = if sample_rate == TARGET_SAMPLE_RATE {
+            None
+        } else {
+            let resampler = FftFixedIn::<f32>::new(
+                sample_rate as usize,
+                TARGET_SAMPLE_RATE as usize,
+                RESAMPLER_CHUNK_SIZE,
+                2,
+                1,
+            )
+            .map_err(|e| eyre!("failed to create resampler: {:?}", e))?;
+            Some(resampler)
+        };
+        Ok(Self {
+            skip: inner.as_ref().map(|r| r.output_delay()).unwrap_or_default(),
+            inner,
+            pending: Vec::new(),
+            input_frames: 0,
+            output_frames: 0,
+            sample_rate,
+        })
+    }
+
+    /// Number of output frames the input seen so far should produce
+    fn expected_output_frames(&self) -> u64 {
+        (self.input_frames * TARGET_SAMPLE_RATE as u64).div_ceil(self.sample_rate as u64)
+    }
+
+    fn emit<F, E>(&mut self, samples: &[f32], write: &mut F) -> Result<()>
+    where
+        F: FnMut(i16) -> std::result::Result<(), E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        for &sample in samples {
+            if self.skip > 0 {
+                self.skip -= 1;
+                continue;
+            }
+            write((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).context("failed to write sample")?;
+            self.output_frames += 1;
+        }
+        Ok(())
+    }
+
+    fn push<F, E>(&mut self, samples: &[f32], mut write: F) -> Result<()>
+    where
+        F: FnMut(i16) -> std::result::Result<(), E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.input_frames += samples.len() as u64;
+        let Some(resampler) = self.inner.as_mut() else {
+            return self.emit(samples, &mut write);
+        };
+        self.pending.extend_from_slice(samples);
+
+        let mut consumed = 0;
+        let mut resampled = Vec::new();
+        while self.pending.len() - consumed >= resampler.input_frames_next() {
+            let needed = resampler.input_frames_next();
+            let chunk = &self.pending[consumed..consumed + needed];
+            let out = resampler
+                .process(&[chunk], None)
+                .map_err(|e| eyre!("failed to resample: {:?}", e))?;
+            resampled.extend_from_slice(&out[0]);
+            consumed += needed;
+        }
+        self.pending.drain(..consumed);
+        self.emit(&resampled, &mut write)
+    }
+
+    fn finish<F, E>(&mut self, mut write: F) -> Result<()>
+    where
+        F: FnMut(i16) -> std::result::Result<(), E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let expected = self.expected_output_frames();
+        let Some(resampler) = self.inner.as_mut() else {
+            return Ok(());
+        };
+
+        let mut resampled = Vec::new();
+        let pending = std::mem::take(&mut self.pending);
+        let out = resampler
+            .process_partial(Some(&[pending.as_slice()]), None)
+            .map_err(|e| eyre!("failed to resample: {:?}", e))?;
+        resampled.extend_from_slice(&out[0]);
+
+        // Drain the resampler delay until the expected duration is reached
+        while self.output_frames + (resampled.len() as u64).saturating_sub(self.skip as u64) < expected {
+            let out = resampler
+                .process_partial::<&[f32]>(None, None)
+                .map_err(|e| eyre!("failed to resample: {:?}", e))?;
+            if out[0].is_empty() {
+                break;
+            }
+            resampled.extend_from_slice(&out[0]);
+        }
+
+        let remaining = expected.saturating_sub(self.output_frames) as usize + self.skip;
+        resampled.truncate(remaining);
+        self.emit(&resampled, &mut write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hound::WavReader;
+    use tempfile::tempdir;
+
+    fn write_sine_wav(path: &Path, sample_rate: u32, channels: u16, duration_secs: f32) {
+        let spec = WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        let frames = (sample_rate as f32 * duration_secs) as usize;
+        for i in 0..frames {
+            let t = i as f32 / sample_rate as f32;
+            let value = (0.3 * (2.0 * std::f32::consts::PI * 440.0 * t).sin() * i16::MAX as f32) as i16;
+            for _ in 0..channels {
+                writer.write_sample(value).unwrap();
+            }
+        }
+        writer.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_decode_resamples_to_16khz_mono() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("stereo_44100.wav");
+        let output = dir.path().join("out.wav");
+        write_sine_wav(&input, 44100, 2, 2.0);
+
+        decode_to_wav(&input, &output).unwrap();
+
+        let reader = WavReader::open(&output).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 1);
+        assert_eq!(spec.sample_rate, 16000);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(reader.duration(), 32000);
+    }
+
+    #[test]
+    fn test_decode_passthrough_sample_rate() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("mono_16000.wav");
+        let output = dir.path().join("out.wav");
+        write_sine_wav(&input, 16000, 1, 1.0);
+
+        decode_to_wav(&input, &output).unwrap();
+
+        let reader = WavReader::open(&output).unwrap();
+        assert_eq!(reader.duration(), 16000);
+    }
+
+    #[test]
+    fn test_decode_unsupported_file() {
+        let dir = tempdir().unwrap();
+        let input = dir.path().join("not_audio.txt");
+        std::fs::write(&input, "hello world").unwrap();
+        assert!(decode_to_wav(&input, &dir.path().join("out.wav")).is_err());
+    }
+}
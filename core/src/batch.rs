@@ -0,0 +1,491 @@
+use crate::config::TranscribeOptions;
+use crate::transcribe::{self, DiarizeOptions, Progress, ProgressCallback, WhisperContext};
+use crate::transcript::{Segment, Transcript};
+use eyre::{bail, eyre, Result};
+use serde::Serialize;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+pub type JobId = usize;
+
+/// A file to transcribe in a batch
+#[derive(Debug, Clone)]
+pub struct BatchJob {
+    pub options: TranscribeOptions,
+    pub diarize_options: Option<DiarizeOptions>,
+    pub additional_ffmpeg_args: Option<Vec<String>>,
+}
+
+impl BatchJob {
+    pub fn new(options: TranscribeOptions) -> Self {
+        Self {
+            options,
+            diarize_options: None,
+            additional_ffmpeg_args: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "status", content = "error")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Paused,
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Done | JobStatus::Failed(_) | JobStatus::Cancelled)
+    }
+}
+
+/// Reported by [`BatchQueue::run`] from the worker threads
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum BatchEvent {
+    Status { id: JobId, status: JobStatus },
+    Progress { id: JobId, progress: Progress },
+    Segment { id: JobId, segment: Segment },
+    Finished { id: JobId, transcript: Transcript },
+}
+
+type EventCallback<'a> = &'a (dyn Fn(BatchEvent) + Send + Sync);
+
+struct JobEntry {
+    job: Option<BatchJob>,
+    status: JobStatus,
+    paused: bool,
+    cancelled: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    jobs: Mutex<Vec<JobEntry>>,
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Vec<JobEntry>> {
+        // A panicking job is reported as failed, the list itself stays consistent
+        self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Blocks while the job is paused. Returns true if the job should abort.
+    fn wait_if_paused(&self, id: JobId, on_event: EventCallback) -> bool {
+        let mut jobs = self.lock();
+        if jobs[id].paused && !jobs[id].cancelled {
+            jobs[id].status = JobStatus::Paused;
+            on_event(BatchEvent::Status {
+                id,
+                status: JobStatus::Paused,
+            });
+            while jobs[id].paused && !jobs[id].cancelled {
+                jobs = self.changed.wait(jobs).unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+            if !jobs[id].cancelled {
+                jobs[id].status = JobStatus::Running;
+                on_event(BatchEvent::Status {
+                    id,
+                    status: JobStatus::Running,
+                });
+            }
+        }
+        jobs[id].cancelled
+    }
+}
+
+/// Callbacks handed to a running job
+pub(crate) struct JobCallbacks {
+    pub progress: ProgressCallback,
+    pub new_segment: Box<dyn Fn(Segment)>,
+    pub abort: Box<dyn Fn() -> bool>,
+}
+
+/// Queue of files transcribed by parallel workers sharing one `WhisperContext`.
+/// Each worker creates its own `WhisperState`, so `workers` states are alive at most.
+///
+/// The queue can be controlled from other threads while `run` is blocking:
+/// jobs can be submitted, paused, resumed and cancelled.
+#[derive(Clone, Default)]
+pub struct BatchQueue {
+    shared: Arc<Shared>,
+}
+
+impl BatchQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn submit(&self, job: BatchJob) -> JobId {
+        let mut jobs = self.shared.lock();
+        jobs.push(JobEntry {
+            job: Some(job),
+            status: JobStatus::Queued,
+            paused: false,
+            cancelled: false,
+        });
+        self.shared.changed.notify_all();
+        jobs.len() - 1
+    }
+
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.shared.lock().get(id).map(|entry| entry.status.clone())
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.shared.lock().iter().map(|entry| entry.status.clone()).collect()
+    }
+
+    /// Cancel a queued, paused or running job
+    pub fn cancel(&self, id: JobId) -> Result<()> {
+        self.update(id, |entry| entry.cancelled = true)
+    }
+
+    /// Queued jobs won't start and running jobs are suspended until `resume`
+    pub fn pause(&self, id: JobId) -> Result<()> {
+        self.update(id, |entry| entry.paused = true)
+    }
+
+    pub fn resume(&self, id: JobId) -> Result<()> {
+        self.update(id, |entry| entry.paused = false)
+    }
+
+    fn update<F: FnOnce(&mut JobEntry)>(&self, id: JobId, update: F) -> Result<()> {
+        let mut jobs = self.shared.lock();
+        let Some(entry) = jobs.get_mut(id) else {
+            bail!("job {} not found", id)
+        };
+        update(entry);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Run the queued jobs with `workers` threads and block until every job finished.
+    pub fn run<F>(&self, ctx: &WhisperContext, workers: usize, on_event: F)
+    where
+        F: Fn(BatchEvent) + Send + Sync,
+    {
+        self.run_with(workers, &on_event, |job, callbacks| {
+            transcribe::transcribe(
+                ctx,
+                &job.options,
+                Some(callbacks.progress),
+                Some(callbacks.new_segment),
+                Some(callbacks.abort),
+                job.diarize_options.clone(),
+                job.additional_ffmpeg_args.clone(),
+            )
+        });
+    }
+
+    pub(crate) fn run_with<T>(&self, workers: usize, on_event: EventCallback, transcribe_job: T)
+    where
+        T: Fn(&BatchJob, JobCallbacks) -> Result<Transcript> + Sync,
+    {
+        let workers = workers.max(1);
+        tracing::debug!("run batch with {} workers", workers);
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some((id, job)) = self.next_job(on_event) {
+                        self.run_job(id, job, on_event, &transcribe_job);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Take the next job to run. None once there's nothing left to start.
+    fn next_job(&self, on_event: EventCallback) -> Option<(JobId, BatchJob)> {
+        let mut jobs = self.shared.lock();
+        loop {
+            // Events are reported after unlocking so callers may use the queue from the callback
+            let mut events = Vec::new();
+            let mut next = None;
+            for (id, entry) in jobs.iter_mut().enumerate() {
+                if entry.status != JobStatus::Queued {
+                    continue;
+                }
+                if entry.cancelled {
+                    entry.status = JobStatus::Cancelled;
+                    entry.job = None;
+                    events.push(BatchEvent::Status {
+                        id,
+                        status: JobStatus::Cancelled,
+                    });
+                } else if !entry.paused {
+                    entry.status = JobStatus::Running;
+                    events.push(BatchEvent::Status {
+                        id,
+                        status: JobStatus::Running,
+                    });
+                    next = entry.job.take().map(|job| (id, job));
+                    break;
+                }
+            }
+            // Only paused jobs are left, wait until they're resumed or cancelled
+            let wait = next.is_none() && jobs.iter().any(|entry| entry.status == JobStatus::Queued);
+            if !events.is_empty() || !wait {
+                drop(jobs);
+                events.into_iter().for_each(on_event);
+                if !wait {
+                    return next;
+                }
+                jobs = self.shared.lock();
+                continue;
+            }
+            jobs = self.shared.changed.wait(jobs).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    fn run_job<T>(&self, id: JobId, job: BatchJob, on_event: EventCallback, transcribe_job: &T)
+    where
+        T: Fn(&BatchJob, JobCallbacks) -> Result<Transcript> + Sync,
+    {
+        tracing::debug!("start job {} {}", id, job.options.path);
+        let result = std::thread::scope(|scope| {
+            // The callbacks must be 'static, so events are forwarded through a channel
+            let (sender, receiver) = std::sync::mpsc::channel::<BatchEvent>();
+            let forward = scope.spawn(move || {
+                for event in receiver {
+                    on_event(event);
+                }
+            });
+
+            let progress_sender = Mutex::new(sender.clone());
+            let segment_sender = sender.clone();
+            let shared = self.shared.clone();
+            let abort_sender = sender;
+            let callbacks = JobCallbacks {
+                progress: Box::new(move |progress| {
+                    if let Ok(sender) = progress_sender.lock() {
+                        let _ = sender.send(BatchEvent::Progress { id, progress });
+                    }
+                }),
+                new_segment: Box::new(move |segment| {
+                    let _ = segment_sender.send(BatchEvent::Segment { id, segment });
+                }),
+                abort: Box::new(move || {
+                    shared.wait_if_paused(id, &|event| {
+                        let _ = abort_sender.send(event);
+                    })
+                }),
+            };
+            let result = catch_unwind(AssertUnwindSafe(|| transcribe_job(&job, callbacks)));
+            let _ = forward.join();
+            result
+        });
+
+        let result = match result {
+            Ok(result) => result,
+            Err(error) => Err(eyre!("transcribe crash: {:?}", error)),
+        };
+
+        let mut jobs = self.shared.lock();
+        let (status, transcript) = if jobs[id].cancelled {
+            (JobStatus::Cancelled, None)
+        } else {
+            match result {
+                Ok(transcript) => (JobStatus::Done, Some(transcript)),
+                Err(error) => {
+                    tracing::error!("job {} failed: {:?}", id, error);
+                    (JobStatus::Failed(format!("{:?}", error)), None)
+                }
+            }
+        };
+        jobs[id].status = status.clone();
+        self.shared.changed.notify_all();
+        // Events are reported after unlocking so callers may use the queue from the callback
+        drop(jobs);
+        if let Some(transcript) = transcript {
+            on_event(BatchEvent::Finished { id, transcript });
+        }
+        on_event(BatchEvent::Status { id, status });
+    }
+}
+
+/// Files in `folder` ending with one of `patterns` (extensions such as `.mp3`), sorted by path
+pub fn glob_files(folder: &Path, patterns: &[String], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in std::fs::read_dir(&folder)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    folders.push(path);
+                }
+                continue;
+            }
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if patterns.iter().any(|p| file_name.ends_with(p.as_str())) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcribe::ProgressPhase;
+    use crate::transcript::Timestamp;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn job(path: &str) -> BatchJob {
+        BatchJob::new(TranscribeOptions {
+            path: path.into(),
+            ..Default::default()
+        })
+    }
+
+    fn transcript(text: &str) -> Transcript {
+        Transcript {
+            processing_time_sec: 0,
+            segments: vec![Segment {
+                start: Timestamp::ZERO,
+                stop: Timestamp::from_centis(100),
+                text: text.into(),
+                ..Default::default()
+            }],
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_runs_jobs_in_parallel() {
+        let queue = BatchQueue::new();
+        for path in ["a", "b", "c", "fail"] {
+            queue.submit(job(path));
+        }
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let events = Mutex::new(Vec::new());
+
+        queue.run_with(
+            2,
+            &|event| events.lock().unwrap().push(event),
+            |job, callbacks| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(50));
+                (callbacks.progress)(Progress::new(ProgressPhase::Transcription, 1.0, 1.0));
+                running.fetch_sub(1, Ordering::SeqCst);
+                if job.options.path == "fail" {
+                    bail!("failed")
+                }
+                Ok(transcript(&job.options.path))
+            },
+        );
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        let statuses = queue.statuses();
+        assert_eq!(&statuses[..3], &[JobStatus::Done, JobStatus::Done, JobStatus::Done]);
+        assert!(matches!(statuses[3], JobStatus::Failed(_)));
+        let events = events.into_inner().unwrap();
+        let finished = events.iter().filter(|e| matches!(e, BatchEvent::Finished { .. })).count();
+        let progress = events.iter().filter(|e| matches!(e, BatchEvent::Progress { .. })).count();
+        assert_eq!(finished, 3);
+        assert_eq!(progress, 4);
+    }
+
+    #[test]
+    fn test_cancel_and_pause() {
+        let queue = BatchQueue::new();
+        let paused_id = queue.submit(job("paused"));
+        let cancelled_id = queue.submit(job("cancelled"));
+        queue.cancel(cancelled_id).unwrap();
+        queue.pause(paused_id).unwrap();
+
+        let control = queue.clone();
+        let controller = std::thread::spawn(move || {
+            // Paused before start, the job waits in the queue
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(control.status(paused_id), Some(JobStatus::Queued));
+            control.resume(paused_id).unwrap();
+            // Paused while running, the job waits inside its abort callback
+            while control.status(paused_id) != Some(JobStatus::Paused) {
+                std::thread::sleep(Duration::from_millis(5));
+            }
+            control.resume(paused_id).unwrap();
+        });
+
+        queue.run_with(
+            1,
+            &|_| {},
+            |job, callbacks| {
+                queue.pause(paused_id).unwrap();
+                if (callbacks.abort)() {
+                    bail!("aborted")
+                }
+                Ok(transcript(&job.options.path))
+            },
+        );
+        controller.join().unwrap();
+
+        assert_eq!(queue.status(paused_id), Some(JobStatus::Done));
+        assert_eq!(queue.status(cancelled_id), Some(JobStatus::Cancelled));
+    }
+
+    #[test]
+    fn test_cancel_running_job() {
+        let queue = BatchQueue::new();
+        let id = queue.submit(job("a"));
+        queue.run_with(
+            1,
+            &|_| {},
+            |_, callbacks| {
+                queue.cancel(id).unwrap();
+                assert!((callbacks.abort)());
+                Ok(transcript("partial"))
+            },
+        );
+        assert_eq!(queue.status(id), Some(JobStatus::Cancelled));
+        assert!(queue.cancel(10).is_err());
+    }
+
+    #[test]
+    fn test_events_can_use_queue() {
+        let queue = BatchQueue::new();
+        let id = queue.submit(job("a"));
+        let finished = Mutex::new(None);
+        queue.run_with(
+            1,
+            &|event| {
+                if let BatchEvent::Finished { id, .. } = event {
+                    *finished.lock().unwrap() = queue.status(id);
+                }
+            },
+            |job, _| Ok(transcript(&job.options.path)),
+        );
+        assert_eq!(*finished.lock().unwrap(), Some(JobStatus::Done));
+        assert_eq!(queue.status(id), Some(JobStatus::Done));
+    }
+
+    #[test]
+    fn test_glob_files() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        for name in ["b.mp3", "a.wav", "notes.txt", "nested/c.mp3"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let patterns = vec![".mp3".to_string(), ".wav".to_string()];
+
+        let files = glob_files(dir.path(), &patterns, false).unwrap();
+        assert_eq!(files, vec![dir.path().join("a.wav"), dir.path().join("b.mp3")]);
+
+        let files = glob_files(dir.path(), &patterns, true).unwrap();
+        assert_eq!(files.len(), 3);
+    }
+}
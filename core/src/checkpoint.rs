@@ -0,0 +1,224 @@
+use crate::audio;
+use crate::config::TranscribeOptions;
+use crate::transcript::{Segment, Timestamp};
+use crate::vad::VadOptions;
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Progress of a chunked transcription, saved after every window
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Sample offset of the next window to transcribe
+    pub offset: usize,
+    /// Segments starting before this timestamp were already committed
+    pub keep_from: Timestamp,
+    pub segments: Vec<Segment>,
+}
+
+/// Checkpoint location of a transcription.
+/// Keyed by the source file content, the model and the options that change decoding, so renaming the file
+/// or changing what's applied after transcribing still resumes, while changing the decoding starts over.
+pub struct CheckpointFile {
+    path: PathBuf,
+}
+
+impl CheckpointFile {
+    /// `model` identifies the loaded model
+    pub fn new(options: &TranscribeOptions, model: &str, additional_ffmpeg_args: &Option<Vec<String>>) -> Result<Self> {
+        let key = checkpoint_key(options, model, additional_ffmpeg_args)?;
+        // Outside of the dated vibe_temp_* folders, so a transcription still resumes the next day
+        let dir = std::env::temp_dir().join("vibe_checkpoints");
+        std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        Ok(Self::at(dir.join(format!("checkpoint_{}.json", key))))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the saved checkpoint. A missing or unreadable checkpoint means starting over.
+    pub fn load(&self) -> Option<Checkpoint> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(checkpoint) => Some(checkpoint),
+            Err(error) => {
+                tracing::warn!("ignoring corrupted checkpoint {}: {:?}", self.path.display(), error);
+                None
+            }
+        }
+    }
+
+    /// Write to a temporary file first so a crash while saving never leaves a broken checkpoint
+    pub fn save(&self, checkpoint: &Checkpoint) -> Result<()> {
+        let tmp_path = self.path.with_extension("json.tmp");
+        let content = serde_json::to_string(checkpoint).context("failed to serialize checkpoint")?;
+        std::fs::write(&tmp_path, content).with_context(|| format!("failed to write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &self.path).with_context(|| format!("failed to write {}", self.path.display()))?;
+        tracing::debug!("saved checkpoint at offset {} to {}", checkpoint.offset, self.path.display());
+        Ok(())
+    }
+
+    pub fn remove(&self) -> Result<()> {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path).with_context(|| format!("failed to remove {}", self.path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything that changes the decoded segments. Cleanup, glossary corrections and translation are applied
+/// after transcribing, and diarization doesn't use checkpoints
+#[derive(Serialize)]
+struct DecodeOptions<'a> {
+    model: &'a str,
+    lang: &'a Option<String>,
+    init_prompt: &'a Option<String>,
+    /// The glossary terms are added to the prompt
+    glossary_terms: Vec<&'a str>,
+    temperature: Option<f32>,
+    temperatures: &'a Option<Vec<f32>>,
+    compression_ratio_threshold: Option<f32>,
+    logprob_threshold: Option<f32>,
+    no_speech_threshold: Option<f32>,
+    translate: Option<bool>,
+    max_text_ctx: Option<i32>,
+    word_timestamps: Option<bool>,
+    max_sentence_len: Option<i32>,
+    sampling_strategy: &'a Option<String>,
+    sampling_bestof_or_beam_size: Option<i32>,
+    chunk_duration: Option<u32>,
+    chunk_overlap: Option<u32>,
+    vad: &'a Option<VadOptions>,
+    detect_no_speech: Option<bool>,
+    multilingual: Option<bool>,
+    languages: &'a Option<Vec<String>>,
+    additional_ffmpeg_args: &'a Option<Vec<String>>,
+}
+
+fn checkpoint_key(options: &TranscribeOptions, model: &str, additional_ffmpeg_args: &Option<Vec<String>>) -> Result<String> {
+    // The content, since the path itself doesn't change the result
+    let content_hash = audio::hash_file(Path::new(&options.path))?;
+    let decode_options = DecodeOptions {
+        model,
+        lang: &options.lang,
+        init_prompt: &options.init_prompt,
+        glossary_terms: options
+            .glossary
+            .iter()
+            .flat_map(|glossary| &glossary.terms)
+            .map(|term| term.term.as_str())
+            .collect(),
+        temperature: options.temperature,
+        temperatures: &options.temperatures,
+        compression_ratio_threshold: options.compression_ratio_threshold,
+        logprob_threshold: options.logprob_threshold,
+        no_speech_threshold: options.no_speech_threshold,
+        translate: options.translate,
+        max_text_ctx: options.max_text_ctx,
+        word_timestamps: options.word_timestamps,
+        max_sentence_len: options.max_sentence_len,
+        sampling_strategy: &options.sampling_strategy,
+        sampling_bestof_or_beam_size: options.sampling_bestof_or_beam_size,
+        chunk_duration: options.chunk_duration,
+        chunk_overlap: options.chunk_overlap,
+        vad: &options.vad,
+        detect_no_speech: options.detect_no_speech,
+        multilingual: options.multilingual,
+        languages: &options.languages,
+        additional_ffmpeg_args,
+    };
+
+    let mut hasher = Sha256::new();
+    hasher.update(content_hash.as_bytes());
+    hasher.update(
+        serde_json::to_string(&decode_options)
+            .context("failed to serialize options")?
+            .as_bytes(),
+    );
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::glossary::Glossary;
+    use crate::transcript::cleanup::CleanupOptions;
+    use tempfile::tempdir;
+
+    fn options(path: &Path) -> TranscribeOptions {
+        TranscribeOptions {
+            path: path.to_str().unwrap().into(),
+            lang: Some("en".into()),
+            chunk_duration: Some(60),
+            checkpoint: Some(true),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_key() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.wav");
+        let b = dir.path().join("b.wav");
+        std::fs::write(&a, "same content").unwrap();
+        std::fs::write(&b, "same content").unwrap();
+
+        let key = checkpoint_key(&options(&a), "base", &None).unwrap();
+        assert_eq!(key, checkpoint_key(&options(&b), "base", &None).unwrap());
+
+        let mut other_options = options(&a);
+        other_options.lang = Some("he".into());
+        assert_ne!(key, checkpoint_key(&other_options, "base", &None).unwrap());
+        assert_ne!(key, checkpoint_key(&options(&a), "large", &None).unwrap());
+        assert_ne!(key, checkpoint_key(&options(&a), "base", &Some(vec!["-af".into()])).unwrap());
+
+        // Options applied after transcribing don't start over
+        let mut other_options = options(&a);
+        other_options.verbose = Some(true);
+        other_options.cleanup = Some(CleanupOptions::default());
+        other_options.glossary = Some(Glossary::parse("gonna => going to").unwrap());
+        assert_eq!(key, checkpoint_key(&other_options, "base", &None).unwrap());
+        // Unlike glossary terms, which are in the prompt
+        other_options.glossary = Some(Glossary::parse("Kubernetes").unwrap());
+        assert_ne!(key, checkpoint_key(&other_options, "base", &None).unwrap());
+
+        std::fs::write(&b, "other content").unwrap();
+        assert_ne!(key, checkpoint_key(&options(&b), "base", &None).unwrap());
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let dir = tempdir().unwrap();
+        let file = CheckpointFile::at(dir.path().join("checkpoint.json"));
+        assert!(file.load().is_none());
+
+        let checkpoint = Checkpoint {
+            offset: 16000,
+            keep_from: Timestamp::from_centis(75),
+            segments: vec![Segment {
+                start: Timestamp::ZERO,
+                stop: Timestamp::from_millis(703),
+                text: "hello".into(),
+                ..Default::default()
+            }],
+        };
+        file.save(&checkpoint).unwrap();
+        let loaded = file.load().unwrap();
+        assert_eq!(loaded.offset, 16000);
+        assert_eq!(loaded.keep_from, Timestamp::from_centis(75));
+        assert_eq!(loaded.segments.len(), 1);
+        assert_eq!(loaded.segments[0].stop, Timestamp::from_millis(703));
+
+        file.remove().unwrap();
+        assert!(file.load().is_none());
+
+        std::fs::write(file.path(), "not json").unwrap();
+        assert!(file.load().is_none());
+    }
+}
@@ -1,7 +1,12 @@
 use core::fmt;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
+use crate::glossary::Glossary;
+use crate::transcript::cleanup::CleanupOptions;
+use crate::translate::TranslateOptions;
+use crate::vad::VadOptions;
+
+#[derive(Deserialize, Serialize, Clone, Default)]
 #[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
 
 pub struct TranscribeOptions {
@@ -12,12 +17,47 @@ pub struct TranscribeOptions {
     pub n_threads: Option<i32>,
     pub init_prompt: Option<String>,
     pub temperature: Option<f32>,
+    /// Temperature schedule such as [0.0, 0.2, 0.4, 0.6, 0.8, 1.0]. Decodes with the first temperature
+    /// and decodes segments failing the thresholds again with the next ones. Overrides `temperature`
+    pub temperatures: Option<Vec<f32>>,
+    /// Decode again when the compression ratio of the text is above this (default: 2.4)
+    pub compression_ratio_threshold: Option<f32>,
+    /// Decode again when the average log probability is below this (default: -1.0)
+    pub logprob_threshold: Option<f32>,
+    /// Drop low probability segments when the no-speech probability is above this (default: 0.6).
+    /// Requires `detect_no_speech`
+    pub no_speech_threshold: Option<f32>,
     pub translate: Option<bool>,
     pub max_text_ctx: Option<i32>,
     pub word_timestamps: Option<bool>,
     pub max_sentence_len: Option<i32>,
     pub sampling_strategy: Option<String>,
     pub sampling_bestof_or_beam_size: Option<i32>,
+    /// Transcribe in windows of this many seconds to keep memory flat on long files
+    pub chunk_duration: Option<u32>,
+    /// Overlap in seconds between consecutive windows (default: 5)
+    pub chunk_overlap: Option<u32>,
+    /// Save progress to the temp folder and resume it when the same file is transcribed again with the same model
+    /// and decoding options.
+    /// Implies chunked transcription.
+    pub checkpoint: Option<bool>,
+    /// Detect speech first and transcribe only speech regions. Timestamps stay on the original timeline
+    pub vad: Option<VadOptions>,
+    /// Compute the no-speech probability of segments. Runs the encoder once more for every 30s of audio
+    pub detect_no_speech: Option<bool>,
+    /// Drop or flag repetition loops and other hallucinations after transcribing
+    pub cleanup: Option<CleanupOptions>,
+    /// Domain terms added to the prompt and used to correct the text after transcribing
+    pub glossary: Option<Glossary>,
+    /// Detect the language again for every chunk, or every diarized segment, for audio that switches languages.
+    /// Segments record their language. Implies chunked transcription
+    pub multilingual: Option<bool>,
+    /// Languages to choose from in multilingual mode, such as ["he", "en"]. Short segments are often mistaken
+    /// for similar languages otherwise
+    pub languages: Option<Vec<String>>,
+    /// Translate the transcript to any language with a translation backend. Unlike `translate`, which uses whisper
+    /// and translates only to English
+    pub translation: Option<TranslateOptions>,
 }
 
 impl fmt::Debug for TranscribeOptions {
@@ -0,0 +1,572 @@
+use crate::speaker_profiles::SpeakerProfile;
+use crate::transcript::{Overlap, Segment, Timestamp, Word};
+use serde::{Deserialize, Serialize};
+
+/// How diarized audio is transcribed
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DiarizeMode {
+    /// Transcribe every speaker turn on its own
+    #[default]
+    Segments,
+    /// Transcribe the whole audio once with word timestamps, and split segments where the speaker of the words changes.
+    /// Faster, and keeps the context between turns
+    Words,
+}
+
+/// Another speaker counts as talking in a window when the window is about as similar to them as to the speaker
+/// of its segment, which is the case for mixed voices
+const OVERLAP_MARGIN: f32 = 0.1;
+
+/// Minimum similarity of a speaker to an enrolled profile to be labelled with its name.
+/// Stricter than clustering, since a wrong name is worse than a number
+pub const DEFAULT_PROFILE_THRESHOLD: f32 = 0.6;
+
+/// Time range in which a speaker talks
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeakerTurn {
+    pub start: Timestamp,
+    pub stop: Timestamp,
+    pub speaker: String,
+    pub overlap: Option<Overlap>,
+}
+
+/// Embedding of a short window inside a speech segment
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowEmbedding {
+    pub start: Timestamp,
+    pub stop: Timestamp,
+    pub embedding: Vec<f32>,
+}
+
+/// Cosine similarity of two embeddings, between -1 and 1
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+fn average<'a>(embeddings: impl IntoIterator<Item = &'a Vec<f32>>) -> Vec<f32> {
+    let mut sum: Vec<f32> = Vec::new();
+    let mut count = 0;
+    for embedding in embeddings {
+        if sum.is_empty() {
+            sum.resize(embedding.len(), 0.0);
+        }
+        for (sum, value) in sum.iter_mut().zip(embedding) {
+            *sum += value;
+        }
+        count += 1;
+    }
+    sum.iter_mut().for_each(|value| *value /= count.max(1) as f32);
+    sum
+}
+
+/// Agglomerative clustering with average linkage on cosine similarity.
+/// With `speakers` the closest clusters are merged until there are that many.
+/// Otherwise they are merged while their similarity is at least `threshold`, and then down to `max_speakers`.
+/// Returns the cluster of every embedding, numbered in order of first appearance
+pub fn cluster(embeddings: &[Vec<f32>], speakers: Option<usize>, threshold: f32, max_speakers: usize) -> Vec<usize> {
+    let n = embeddings.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut similarity = vec![vec![0.0f32; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let value = cosine_similarity(&embeddings[i], &embeddings[j]);
+            similarity[i][j] = value;
+            similarity[j][i] = value;
+        }
+    }
+    let mut sizes = vec![1usize; n];
+    let mut active = vec![true; n];
+    // Clusters are identified by their first embedding, and every embedding points to the cluster it's in
+    let mut parent: Vec<usize> = (0..n).collect();
+    let closest = |i: usize, active: &[bool], similarity: &[Vec<f32>]| {
+        (0..n)
+            .filter(|j| *j != i && active[*j])
+            .map(|j| (j, similarity[i][j]))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    };
+    let mut nearest: Vec<Option<(usize, f32)>> = (0..n).map(|i| closest(i, &active, &similarity)).collect();
+
+    let mut clusters = n;
+    let target = speakers.map(|speakers| speakers.max(1)).unwrap_or(max_speakers.max(1));
+    while clusters > 1 {
+        let Some((a, (b, value))) = (0..n)
+            .filter(|i| active[*i])
+            .filter_map(|i| nearest[i].map(|nearest| (i, nearest)))
+            .max_by(|x, y| x.1 .1.total_cmp(&y.1 .1))
+        else {
+            break;
+        };
+        if clusters <= target && (speakers.is_some() || value < threshold) {
+            break;
+        }
+        // Merge b into a
+        let (a, b) = (a.min(b), a.max(b));
+        for k in 0..n {
+            if active[k] && k != a && k != b {
+                let merged =
+                    (similarity[a][k] * sizes[a] as f32 + similarity[b][k] * sizes[b] as f32) / (sizes[a] + sizes[b]) as f32;
+                similarity[a][k] = merged;
+                similarity[k][a] = merged;
+            }
+        }
+        sizes[a] += sizes[b];
+        active[b] = false;
+        for cluster in parent.iter_mut().filter(|cluster| **cluster == b) {
+            *cluster = a;
+        }
+        clusters -= 1;
+        for k in 0..n {
+            if !active[k] {
+                continue;
+            }
+            nearest[k] = match nearest[k] {
+                Some((j, _)) if j == a || j == b || k == a => closest(k, &active, &similarity),
+                Some((j, value)) if similarity[k][a] <= value => Some((j, value)),
+                _ => Some((a, similarity[k][a])),
+            };
+        }
+    }
+
+    // Number clusters by first appearance
+    let mut numbers: Vec<Option<usize>> = vec![None; n];
+    let mut next = 0;
+    parent
+        .iter()
+        .map(|cluster| {
+            *numbers[*cluster].get_or_insert_with(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect()
+}
+
+/// Name of the enrolled profile of every cluster, given the average embedding of each one.
+/// Every profile names at most one cluster, the most similar one, and only when they're at least `threshold` similar
+fn match_profiles(centroids: &[Vec<f32>], profiles: &[SpeakerProfile], threshold: f32) -> Vec<Option<String>> {
+    let mut pairs = Vec::new();
+    for (cluster, centroid) in centroids.iter().enumerate() {
+        for (profile, speaker) in profiles.iter().enumerate() {
+            let similarity = cosine_similarity(centroid, &speaker.embedding);
+            if similarity >= threshold {
+                pairs.push((cluster, profile, similarity));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let mut names = vec![None; centroids.len()];
+    let mut used = vec![false; profiles.len()];
+    for (cluster, profile, similarity) in pairs {
+        if names[cluster].is_none() && !used[profile] {
+            tracing::debug!("speaker {} is {} ({:.2})", cluster, profiles[profile].name, similarity);
+            names[cluster] = Some(profiles[profile].name.clone());
+            used[profile] = true;
+        }
+    }
+    names
+}
+
+/// Speaker of every segment. Speakers at least `profile_threshold` similar to an enrolled profile get its name,
+/// and the others are numbered
+/// from "0". Segments without an embedding, such as ones too short to compute it, get the speaker of the previous
+/// segment, or of the next one at the start
+pub fn assign_speakers(
+    embeddings: &[Option<Vec<f32>>],
+    speakers: Option<usize>,
+    threshold: f32,
+    max_speakers: usize,
+    profiles: &[SpeakerProfile],
+    profile_threshold: f32,
+) -> Vec<String> {
+    let known: Vec<Vec<f32>> = embeddings.iter().flatten().cloned().collect();
+    let clusters = cluster(&known, speakers, threshold, max_speakers);
+
+    let count = clusters.iter().max().map_or(0, |max| max + 1);
+    let centroids: Vec<Vec<f32>> = (0..count)
+        .map(|cluster| {
+            average(
+                known
+                    .iter()
+                    .zip(&clusters)
+                    .filter(|(_, other)| **other == cluster)
+                    .map(|(embedding, _)| embedding),
+            )
+        })
+        .collect();
+    let mut names = match_profiles(&centroids, profiles, profile_threshold);
+    // Clusters are numbered by first appearance, so unnamed speakers keep counting from 0
+    for (next, name) in names.iter_mut().filter(|name| name.is_none()).enumerate() {
+        *name = Some(next.to_string());
+    }
+
+    let mut clusters = clusters.into_iter();
+    let labels: Vec<Option<usize>> = embeddings
+        .iter()
+        .map(|embedding| embedding.as_ref().and_then(|_| clusters.next()))
+        .collect();
+    let mut previous = labels.iter().flatten().next().copied().unwrap_or_default();
+    labels
+        .into_iter()
+        .map(|label| {
+            previous = label.unwrap_or(previous);
+            names.get(previous).cloned().flatten().unwrap_or_else(|| "0".into())
+        })
+        .collect()
+}
+
+/// Average embedding of every speaker, in order of first appearance
+pub fn speaker_centroids(embeddings: &[Option<Vec<f32>>], speakers: &[String]) -> Vec<(String, Vec<f32>)> {
+    let mut names: Vec<&String> = Vec::new();
+    for speaker in speakers {
+        if !names.contains(&speaker) {
+            names.push(speaker);
+        }
+    }
+    names
+        .into_iter()
+        .map(|name| {
+            let embeddings = embeddings
+                .iter()
+                .zip(speakers)
+                .filter(|(_, speaker)| *speaker == name)
+                .filter_map(|(embedding, _)| embedding.as_ref());
+            (name.clone(), average(embeddings))
+        })
+        .filter(|(_, centroid)| !centroid.is_empty())
+        .collect()
+}
+
+/// Overlapping speech in the windows of a segment of `speaker`. A window overlaps when it's similar to another
+/// speaker about as much as to `speaker`, such as two voices mixed together, or more, when they talk over the segment
+pub fn find_overlap(
+    speaker: &str,
+    windows: &[WindowEmbedding],
+    centroids: &[(String, Vec<f32>)],
+    threshold: f32,
+) -> Option<Overlap> {
+    let mut overlap: Option<Overlap> = None;
+    for window in windows {
+        let similarities: Vec<(&str, f32)> = centroids
+            .iter()
+            .map(|(name, centroid)| (name.as_str(), cosine_similarity(&window.embedding, centroid)))
+            .collect();
+        let own = similarities
+            .iter()
+            .find(|(name, _)| *name == speaker)
+            .map_or(-1.0, |(_, similarity)| *similarity);
+        let others: Vec<&str> = similarities
+            .iter()
+            .filter(|(name, similarity)| *name != speaker && *similarity >= threshold && *similarity >= own - OVERLAP_MARGIN)
+            .map(|(name, _)| *name)
+            .collect();
+        if others.is_empty() {
+            continue;
+        }
+        tracing::trace!("overlap {} - {} with {:?}", window.start, window.stop, others);
+        let overlap = overlap.get_or_insert_with(|| Overlap {
+            start: window.start,
+            stop: window.stop,
+            speakers: vec![speaker.to_string()],
+        });
+        overlap.start = overlap.start.min(window.start);
+        overlap.stop = overlap.stop.max(window.stop);
+        for other in others {
+            if !overlap.speakers.iter().any(|speaker| speaker == other) {
+                overlap.speakers.push(other.to_string());
+            }
+        }
+    }
+    overlap
+}
+
+/// Overlapping speech of the turns within `start..stop`
+fn overlap_within(turns: &[SpeakerTurn], start: Timestamp, stop: Timestamp) -> Option<Overlap> {
+    turns.iter().filter_map(|turn| turn.overlap.as_ref()).find_map(|overlap| {
+        let clipped = Overlap {
+            start: overlap.start.max(start),
+            stop: overlap.stop.min(stop),
+            speakers: overlap.speakers.clone(),
+        };
+        (clipped.start < clipped.stop).then_some(clipped)
+    })
+}
+
+/// Speaker of the turn overlapping `start..stop` the most, or of the nearest turn when none overlaps
+fn speaker_at(turns: &[SpeakerTurn], start: Timestamp, stop: Timestamp) -> Option<&str> {
+    // Negative for turns that don't overlap, the distance to them
+    let overlap = |turn: &&SpeakerTurn| turn.stop.min(stop).as_millis() - turn.start.max(start).as_millis();
+    turns.iter().max_by_key(overlap).map(|turn| turn.speaker.as_str())
+}
+
+/// Label segments with the speaker turns their words fall in.
+/// Segments with words of several speakers are split where the speaker changes, and segments without words
+/// get the speaker of their whole time range
+pub fn align_words(segments: Vec<Segment>, turns: &[SpeakerTurn]) -> Vec<Segment> {
+    let mut aligned = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let mut groups: Vec<(Option<&str>, Vec<Word>)> = Vec::new();
+        for word in &segment.words {
+            let speaker = speaker_at(turns, word.start, word.stop);
+            match groups.last_mut() {
+                Some((last, words)) if *last == speaker => words.push(word.clone()),
+                _ => groups.push((speaker, vec![word.clone()])),
+            }
+        }
+        if groups.len() <= 1 {
+            let speaker = match groups.first() {
+                Some((speaker, _)) => *speaker,
+                None => speaker_at(turns, segment.start, segment.stop),
+            };
+            aligned.push(Segment {
+                speaker: speaker.map(Into::into),
+                overlap: overlap_within(turns, segment.start, segment.stop),
+                ..segment
+            });
+            continue;
+        }
+        let count = groups.len();
+        for (i, (speaker, words)) in groups.into_iter().enumerate() {
+            let text: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+            let start = if i == 0 { segment.start } else { words[0].start };
+            let stop = if i + 1 == count {
+                segment.stop
+            } else {
+                words[words.len() - 1].stop
+            };
+            aligned.push(Segment {
+                start,
+                stop,
+                // Whisper segments start with a space
+                text: format!(" {}", text.join(" ")),
+                speaker: speaker.map(Into::into),
+                words,
+                overlap: overlap_within(turns, start, stop),
+                ..segment.clone()
+            });
+        }
+    }
+    aligned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Embeddings near the axis of `speaker`
+    fn embedding(speaker: usize, noise: f32) -> Vec<f32> {
+        let mut embedding = vec![noise; 4];
+        embedding[speaker] = 1.0;
+        embedding
+    }
+
+    #[test]
+    fn test_cluster_automatic() {
+        let embeddings = vec![
+            embedding(2, 0.1),
+            embedding(0, 0.05),
+            embedding(2, 0.2),
+            embedding(1, 0.1),
+            embedding(0, 0.15),
+            embedding(1, 0.0),
+        ];
+        assert_eq!(cluster(&embeddings, None, 0.8, 10), vec![0, 1, 0, 2, 1, 2]);
+        // Limited to max speakers
+        let clusters = cluster(&embeddings, None, 0.8, 2);
+        assert_eq!(clusters.iter().max(), Some(&1));
+        assert_eq!(clusters[0], clusters[2]);
+        // Everything is one speaker with a low threshold
+        assert_eq!(cluster(&embeddings, None, -1.0, 10), vec![0; 6]);
+    }
+
+    #[test]
+    fn test_cluster_speaker_count() {
+        let embeddings = vec![embedding(0, 0.0), embedding(0, 0.1), embedding(1, 0.0), embedding(1, 0.1)];
+        assert_eq!(cluster(&embeddings, Some(2), 0.99, 10), vec![0, 0, 1, 1]);
+        assert_eq!(cluster(&embeddings, Some(4), 0.0, 10), vec![0, 1, 2, 3]);
+        assert_eq!(cluster(&embeddings, Some(1), 0.99, 10), vec![0; 4]);
+        assert!(cluster(&[], Some(2), 0.5, 10).is_empty());
+    }
+
+    #[test]
+    fn test_assign_speakers() {
+        let embeddings = vec![None, Some(embedding(1, 0.0)), None, Some(embedding(0, 0.0)), None];
+        assert_eq!(
+            assign_speakers(&embeddings, None, 0.5, 10, &[], DEFAULT_PROFILE_THRESHOLD),
+            vec!["0", "0", "0", "1", "1"]
+        );
+        assert_eq!(assign_speakers(&[None], None, 0.5, 10, &[], DEFAULT_PROFILE_THRESHOLD), vec!["0"]);
+    }
+
+    #[test]
+    fn test_assign_enrolled_speakers() {
+        let profile = |name: &str, embedding: Vec<f32>| SpeakerProfile {
+            id: 0,
+            name: name.into(),
+            created_at: String::new(),
+            clips: 1,
+            embedding,
+        };
+        let profiles = vec![profile("Alice", embedding(1, 0.1)), profile("Bob", embedding(3, 0.0))];
+        let embeddings = vec![
+            Some(embedding(0, 0.0)),
+            Some(embedding(1, 0.0)),
+            Some(embedding(2, 0.0)),
+            Some(embedding(1, 0.05)),
+        ];
+        assert_eq!(
+            assign_speakers(&embeddings, None, 0.8, 10, &profiles, 0.8),
+            vec!["0", "Alice", "1", "Alice"]
+        );
+        // Profiles are matched with their own threshold
+        assert_eq!(
+            assign_speakers(&embeddings, None, 0.8, 10, &profiles, 0.995),
+            vec!["0", "1", "2", "1"]
+        );
+    }
+
+    fn turn(start: i64, stop: i64, speaker: &str) -> SpeakerTurn {
+        SpeakerTurn {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            speaker: speaker.into(),
+            overlap: None,
+        }
+    }
+
+    fn word(start: i64, stop: i64, text: &str) -> Word {
+        Word {
+            text: text.into(),
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            probability: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_align_words() {
+        let turns = vec![turn(0, 300, "1"), turn(320, 600, "2"), turn(900, 1000, "1")];
+        let segments = vec![
+            Segment {
+                start: Timestamp::from_centis(0),
+                stop: Timestamp::from_centis(560),
+                text: " Hi there. Hello!".into(),
+                words: vec![word(0, 100, "Hi"), word(100, 290, "there."), word(330, 450, "Hello!")],
+                ..Default::default()
+            },
+            // Between turns, closer to the last one
+            Segment {
+                start: Timestamp::from_centis(800),
+                stop: Timestamp::from_centis(880),
+                text: " Bye.".into(),
+                ..Default::default()
+            },
+        ];
+        let aligned = align_words(segments, &turns);
+        let summary: Vec<(i64, i64, &str, Option<&str>, usize)> = aligned
+            .iter()
+            .map(|segment| {
+                (
+                    segment.start.as_centis(),
+                    segment.stop.as_centis(),
+                    segment.text.as_str(),
+                    segment.speaker.as_deref(),
+                    segment.words.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, 290, " Hi there.", Some("1"), 2),
+                (330, 560, " Hello!", Some("2"), 1),
+                (800, 880, " Bye.", Some("1"), 0),
+            ]
+        );
+        // Segments of a single speaker keep their text
+        let segment = Segment {
+            text: " Hi,there".into(),
+            words: vec![word(0, 100, "Hi,"), word(100, 200, "there")],
+            ..Default::default()
+        };
+        assert_eq!(align_words(vec![segment], &turns)[0].text, " Hi,there");
+    }
+
+    #[test]
+    fn test_find_overlap() {
+        let window = |start: i64, embedding: Vec<f32>| WindowEmbedding {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(start + 150),
+            embedding,
+        };
+        let centroids = vec![
+            ("1".to_string(), embedding(0, 0.0)),
+            ("2".to_string(), embedding(1, 0.0)),
+            ("3".to_string(), embedding(2, 0.0)),
+        ];
+        let mixed = vec![1.0, 0.9, 0.0, 0.0];
+        let windows = vec![
+            window(0, embedding(0, 0.05)),
+            window(75, mixed.clone()),
+            window(150, mixed),
+            window(225, embedding(0, 0.1)),
+        ];
+        assert_eq!(
+            find_overlap("1", &windows, &centroids, 0.5),
+            Some(Overlap {
+                start: Timestamp::from_centis(75),
+                stop: Timestamp::from_centis(300),
+                speakers: vec!["1".into(), "2".into()],
+            })
+        );
+        assert_eq!(find_overlap("1", &windows[..1], &centroids, 0.5), None);
+
+        // Turns pass their overlap to the segments within it
+        let turns = vec![SpeakerTurn {
+            overlap: find_overlap("1", &windows, &centroids, 0.5),
+            ..turn(0, 400, "1")
+        }];
+        let segments = vec![
+            Segment {
+                start: Timestamp::from_centis(0),
+                stop: Timestamp::from_centis(100),
+                ..Default::default()
+            },
+            Segment {
+                start: Timestamp::from_centis(200),
+                stop: Timestamp::from_centis(400),
+                ..Default::default()
+            },
+        ];
+        let overlaps: Vec<Option<(i64, i64)>> = align_words(segments, &turns)
+            .iter()
+            .map(|segment| {
+                segment
+                    .overlap
+                    .as_ref()
+                    .map(|overlap| (overlap.start.as_centis(), overlap.stop.as_centis()))
+            })
+            .collect();
+        assert_eq!(overlaps, vec![Some((75, 100)), Some((200, 300))]);
+    }
+
+    #[test]
+    fn test_speaker_centroids() {
+        let embeddings = vec![Some(vec![1.0, 0.0]), None, Some(vec![0.0, 1.0]), Some(vec![0.0, 3.0])];
+        let speakers: Vec<String> = vec!["2".into(), "2".into(), "1".into(), "1".into()];
+        assert_eq!(
+            speaker_centroids(&embeddings, &speakers),
+            vec![("2".to_string(), vec![1.0, 0.0]), ("1".to_string(), vec![0.0, 2.0])]
+        );
+    }
+}
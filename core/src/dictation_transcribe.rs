@@ -1,4 +1,5 @@
-use crate::transcribe::WhisperContext;
+use crate::glossary::Glossary;
+use crate::transcribe::{glossary_prompt, WhisperContext};
 use eyre::{Context, Result};
 use hound::{SampleFormat, WavSpec, WavWriter};
 use std::io::Cursor;
@@ -15,6 +16,7 @@ const DICTATION_SAMPLE_RATE: u32 = 16000;
 /// * `audio_samples` - Raw i16 PCM samples at 16kHz mono
 /// * `language` - Optional language code (e.g., "en", "es"). If None, auto-detect
 /// * `init_prompt` - Optional initial prompt to guide transcription style
+/// * `glossary` - Optional domain terms added to the prompt and used to correct the text
 ///
 /// # Returns
 /// * Transcribed text with automatic punctuation, or empty string if no speech detected
@@ -23,6 +25,7 @@ pub fn transcribe_dictation(
     audio_samples: &[i16],
     language: Option<String>,
     init_prompt: Option<String>,
+    glossary: Option<&Glossary>,
 ) -> Result<String> {
     tracing::debug!("Starting dictation transcription with {} samples", audio_samples.len());
 
@@ -36,6 +39,10 @@ pub fn transcribe_dictation(
     let audio_data = convert_integer_to_float_audio(audio_samples);
 
     // Create transcription parameters with automatic punctuation
+    let init_prompt = match glossary {
+        Some(glossary) => glossary_prompt(ctx, glossary, init_prompt.as_deref(), None),
+        None => init_prompt,
+    };
     let params = create_dictation_params(language.as_deref(), init_prompt.as_deref());
 
     // Create state for transcription
@@ -60,7 +67,10 @@ pub fn transcribe_dictation(
         transcription_text.push(' ');
     }
 
-    let transcription_text = transcription_text.trim().to_string();
+    let mut transcription_text = transcription_text.trim().to_string();
+    if let Some(glossary) = glossary {
+        transcription_text = glossary.correct_text(&transcription_text);
+    }
 
     if transcription_text.is_empty() {
         tracing::debug!("Empty transcription result (no speech detected)");
@@ -268,7 +278,7 @@ mod tests {
         let ctx = create_context(&model_path, None, None).unwrap();
         let samples: Vec<i16> = vec![];
 
-        let result = transcribe_dictation(&ctx, &samples, Some("en".to_string()), None).unwrap();
+        let result = transcribe_dictation(&ctx, &samples, Some("en".to_string()), None, None).unwrap();
         assert_eq!(result, "");
     }
 
@@ -286,7 +296,7 @@ mod tests {
         let ctx = create_context(&model_path, None, None).unwrap();
         let samples = vec![0i16; 16000]; // 1 second of silence
 
-        let result = transcribe_dictation(&ctx, &samples, Some("en".to_string()), None).unwrap();
+        let result = transcribe_dictation(&ctx, &samples, Some("en".to_string()), None, None).unwrap();
         // Silence should return empty or minimal output
         assert!(result.len() < 20); // Allow for some noise detection
     }
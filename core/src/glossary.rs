@@ -0,0 +1,354 @@
+use crate::transcript::{Segment, Timestamp, Word};
+use eyre::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Words of a transcript this similar to a term (0 to 1) are corrected to it
+const DEFAULT_THRESHOLD: f32 = 0.85;
+/// Shorter terms are only corrected when they match exactly, "cat" is too close to "CAD"
+const MIN_FUZZY_CHARS: usize = 5;
+
+/// A domain term, such as a name or jargon whisper tends to misspell
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct GlossaryTerm {
+    pub term: String,
+    /// How the term sounds or is commonly misheard, e.g. "cube ernetes" for "Kubernetes"
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// Replaced whenever it's in the text, without fuzzy matching
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+/// Custom vocabulary. The terms bias whisper through the initial prompt,
+/// and the text of segments is corrected towards them after transcribing
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Glossary {
+    #[serde(default)]
+    pub terms: Vec<GlossaryTerm>,
+    #[serde(default)]
+    pub replacements: Vec<Replacement>,
+    /// Similarity between 0 and 1 needed to correct words to a term (default: 0.85)
+    pub threshold: Option<f32>,
+}
+
+/// Lowercase alphanumeric characters, so "Kubernetes," and "kubernetes" compare equal
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// 1 minus the edit distance relative to the longer text
+fn similarity(a: &str, b: &str) -> f32 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f32 / a.len().max(b.len()) as f32
+}
+
+/// Punctuation around a word, kept when the word is corrected
+fn affixes(first: &str, last: &str) -> (String, String) {
+    let prefix = first.chars().take_while(|c| !c.is_alphanumeric()).collect();
+    let suffix: String = last.chars().rev().take_while(|c| !c.is_alphanumeric()).collect();
+    (prefix, suffix.chars().rev().collect())
+}
+
+impl Glossary {
+    /// Load a json glossary, or a text file with an entry per line:
+    ///
+    /// ```text
+    /// # Terms, optionally followed by aliases
+    /// PostgreSQL
+    /// Kubernetes: cube ernetes, kuber netties
+    /// # Forced replacements
+    /// gonna => going to
+    /// ```
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        if path.extension().is_some_and(|extension| extension == "json") {
+            return serde_json::from_str(&content).with_context(|| format!("invalid glossary {}", path.display()));
+        }
+        Self::parse(&content)
+    }
+
+    /// Parse the text format of `load`
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut glossary = Self::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((from, to)) = line.split_once("=>") {
+                if from.trim().is_empty() {
+                    bail!("missing text to replace in glossary line {:?}", line)
+                }
+                glossary.replacements.push(Replacement {
+                    from: from.trim().to_string(),
+                    to: to.trim().to_string(),
+                });
+                continue;
+            }
+            let (term, aliases) = line.split_once(':').unwrap_or((line, ""));
+            glossary.terms.push(GlossaryTerm {
+                term: term.trim().to_string(),
+                aliases: aliases
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|alias| !alias.is_empty())
+                    .map(String::from)
+                    .collect(),
+            });
+        }
+        Ok(glossary)
+    }
+
+    /// Initial prompt with the terms before `init_prompt`, as many as fit in `max_tokens`.
+    /// Whisper keeps the end of a prompt that's too long, so `init_prompt` itself is never cut by the terms
+    pub fn prompt<F>(&self, init_prompt: Option<&str>, max_tokens: usize, count_tokens: F) -> Option<String>
+    where
+        F: Fn(&str) -> usize,
+    {
+        let init_prompt = init_prompt.map(str::trim).filter(|prompt| !prompt.is_empty());
+        let mut terms: Vec<&str> = Vec::new();
+        for term in &self.terms {
+            terms.push(&term.term);
+            let candidate = Self::join_prompt(&terms, init_prompt);
+            if count_tokens(&candidate) > max_tokens {
+                let skipped = self.terms.len() - terms.len() + 1;
+                tracing::debug!("glossary prompt is full, skip {} of {} terms", skipped, self.terms.len());
+                terms.pop();
+                break;
+            }
+        }
+        if terms.is_empty() {
+            return init_prompt.map(String::from);
+        }
+        Some(Self::join_prompt(&terms, init_prompt))
+    }
+
+    fn join_prompt(terms: &[&str], init_prompt: Option<&str>) -> String {
+        let terms = format!("{}.", terms.join(", "));
+        match init_prompt {
+            Some(init_prompt) => format!("{} {}", terms, init_prompt),
+            None => terms,
+        }
+    }
+
+    /// The term or replacement for the normalized text of `words` words, how well it matches, and whether it
+    /// matches an entry with fewer words. Entries only match windows of about as many words: as many, or one
+    /// more or less for an entry whisper split or joined, such as "Postgre SQL"
+    fn lookup(&self, normalized: &str, words: usize) -> Option<(&str, f32, bool)> {
+        if normalized.is_empty() {
+            return None;
+        }
+        let fits = |entry: &str| words.abs_diff(entry.split_whitespace().count()) <= 1;
+        let split = |entry: &str| entry.split_whitespace().count() < words;
+        if let Some(replacement) = self
+            .replacements
+            .iter()
+            .find(|replacement| fits(&replacement.from) && normalize(&replacement.from) == normalized)
+        {
+            return Some((&replacement.to, 1.0, split(&replacement.from)));
+        }
+        let threshold = self.threshold.unwrap_or(DEFAULT_THRESHOLD);
+        let mut best: Option<(&str, f32, bool)> = None;
+        for term in &self.terms {
+            for entry in std::iter::once(&term.term).chain(&term.aliases).filter(|entry| fits(entry)) {
+                let candidate = normalize(entry);
+                let score = if candidate.chars().count() < MIN_FUZZY_CHARS {
+                    if candidate == normalized {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                } else {
+                    similarity(&candidate, normalized)
+                };
+                if score >= threshold && best.is_none_or(|(_, best, _)| score > best) {
+                    best = Some((&term.term, score, split(entry)));
+                }
+            }
+        }
+        best
+    }
+
+    /// Most words an entry can span. One more than the longest entry, for words whisper splits
+    fn max_window(&self) -> usize {
+        let terms = self.terms.iter().flat_map(|term| std::iter::once(&term.term).chain(&term.aliases));
+        let replacements = self.replacements.iter().map(|replacement| &replacement.from);
+        terms.chain(replacements).map(|text| text.split_whitespace().count()).max().unwrap_or(0) + 1
+    }
+
+    /// Correct a sequence of words. Words matching an entry together are merged into one
+    fn correct_words(&self, words: &[Word]) -> Option<Vec<Word>> {
+        let max_window = self.max_window();
+        let mut corrected = Vec::with_capacity(words.len());
+        let mut changed = false;
+        let mut i = 0;
+        while i < words.len() {
+            // The best matching window starting at this word, shorter ones on ties. A window with more words than
+            // its entry only matches when no shorter window does, so "Kubernete is" doesn't swallow "is"
+            let mut best: Option<(usize, &str, f32)> = None;
+            for n in 1..=max_window.min(words.len() - i) {
+                let window: String = words[i..i + n].iter().map(|word| normalize(&word.text)).collect();
+                if let Some((target, score, split)) = self.lookup(&window, n) {
+                    if best.is_none_or(|(_, _, best)| score > best && !split) {
+                        best = Some((n, target, score));
+                    }
+                }
+            }
+            let Some((n, target, _)) = best else {
+                corrected.push(words[i].clone());
+                i += 1;
+                continue;
+            };
+            let window = &words[i..i + n];
+            let (prefix, suffix) = affixes(&window[0].text, &window[n - 1].text);
+            let text = format!("{prefix}{target}{suffix}");
+            let original: Vec<&str> = window.iter().map(|word| word.text.as_str()).collect();
+            if text != original.join(" ") {
+                tracing::debug!("glossary corrected {:?} to {:?}", original.join(" "), text);
+                changed = true;
+            }
+            corrected.push(Word {
+                text,
+                start: window[0].start,
+                stop: window[n - 1].stop,
+                probability: window.iter().map(|word| word.probability).sum::<f32>() / n as f32,
+            });
+            i += n;
+        }
+        changed.then_some(corrected)
+    }
+
+    /// Correct words of a text towards the terms and apply the replacements. The text is unchanged without matches
+    pub fn correct_text(&self, text: &str) -> String {
+        let words: Vec<Word> = text
+            .split_whitespace()
+            .map(|word| Word {
+                text: word.to_string(),
+                start: Timestamp::ZERO,
+                stop: Timestamp::ZERO,
+                probability: 1.0,
+            })
+            .collect();
+        match self.correct_words(&words) {
+            Some(corrected) => {
+                let corrected: Vec<String> = corrected.into_iter().map(|word| word.text).collect();
+                // Keep the leading space of whisper segments
+                let leading: String = text.chars().take_while(|c| c.is_whitespace()).collect();
+                format!("{}{}", leading, corrected.join(" "))
+            }
+            None => text.to_string(),
+        }
+    }
+
+    /// Correct the text of segments. Corrected words keep the time of the words they replace
+    pub fn correct_segments(&self, segments: Vec<Segment>) -> Vec<Segment> {
+        segments
+            .into_iter()
+            .map(|segment| {
+                if segment.words.is_empty() {
+                    return Segment {
+                        text: self.correct_text(&segment.text),
+                        ..segment
+                    };
+                }
+                match self.correct_words(&segment.words) {
+                    Some(words) => Segment {
+                        text: words.iter().fold(String::new(), |text, word| text + " " + &word.text),
+                        words,
+                        ..segment
+                    },
+                    None => segment,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary() -> Glossary {
+        Glossary::parse("# devops\nKubernetes: cube ernetes\nPostgreSQL\nAPI\n\ngonna => going to\n").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let glossary = glossary();
+        assert_eq!(glossary.terms.len(), 3);
+        assert_eq!(glossary.terms[0].aliases, vec!["cube ernetes"]);
+        assert_eq!(
+            glossary.replacements,
+            vec![Replacement {
+                from: "gonna".into(),
+                to: "going to".into()
+            }]
+        );
+        assert!(Glossary::parse(" => nothing").is_err());
+    }
+
+    #[test]
+    fn test_correct_text() {
+        let glossary = glossary();
+        assert_eq!(
+            glossary.correct_text(" We're gonna deploy cube ernetes, with Postgre SQL and an api."),
+            " We're going to deploy Kubernetes, with PostgreSQL and an API."
+        );
+        assert_eq!(glossary.correct_text(" Kubernetis is great"), " Kubernetes is great");
+        // A longer window matching a bit better doesn't drop the next word
+        assert_eq!(glossary.correct_text(" Kubernete is great"), " Kubernetes is great");
+        // Short terms only match exactly, and unrelated text is left alone
+        assert_eq!(glossary.correct_text(" The app is  fine."), " The app is  fine.");
+    }
+
+    #[test]
+    fn test_correct_segments_keeps_timing() {
+        let word = |text: &str, start, stop| Word {
+            text: text.into(),
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            probability: 1.0,
+        };
+        let segment = Segment {
+            text: " Run cube ernetes".into(),
+            words: vec![word("Run", 0, 50), word("cube", 50, 80), word("ernetes", 80, 120)],
+            ..Default::default()
+        };
+        let corrected = glossary().correct_segments(vec![segment]);
+        assert_eq!(corrected[0].text, " Run Kubernetes");
+        assert_eq!(corrected[0].words[1], word("Kubernetes", 50, 120));
+    }
+
+    #[test]
+    fn test_prompt_budget() {
+        let glossary = glossary();
+        let count_words = |text: &str| text.split_whitespace().count();
+        assert_eq!(
+            glossary.prompt(Some("Meeting notes."), 100, count_words).unwrap(),
+            "Kubernetes, PostgreSQL, API. Meeting notes."
+        );
+        assert_eq!(glossary.prompt(Some("Meeting notes."), 4, count_words).unwrap(), "Kubernetes, PostgreSQL. Meeting notes.");
+        assert_eq!(glossary.prompt(Some("Meeting notes."), 1, count_words).unwrap(), "Meeting notes.");
+        assert_eq!(Glossary::default().prompt(None, 100, count_words), None);
+    }
+}
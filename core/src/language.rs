@@ -0,0 +1,122 @@
+use crate::audio::WavChunks;
+use eyre::{bail, Context, ContextCompat, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use whisper_rs::{WhisperContext, WhisperState};
+
+/// Whisper identifies the language from a single 30 seconds window
+pub const DEFAULT_DETECT_SECONDS: u32 = 30;
+
+const SAMPLE_RATE: usize = 16000;
+
+/// `TranscribeOptions.lang` value that detects the language before transcribing
+pub const AUTO: &str = "auto";
+
+/// A language and the probability that it's the one spoken
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct LanguageProbability {
+    /// Whisper language code, such as "en"
+    pub code: String,
+    /// Whisper language name, such as "english"
+    pub name: String,
+    pub probability: f32,
+}
+
+/// Sort by probability, most likely first, and drop languages that are impossible
+fn rank(mut languages: Vec<LanguageProbability>) -> Vec<LanguageProbability> {
+    languages.retain(|language| language.probability > 0.0);
+    languages.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+    languages
+}
+
+/// Identify the language of 16KHz mono `samples` with whisper.
+/// Only the first 30 seconds are used. Returns every language the model knows, most likely first
+pub fn detect_language(ctx: &WhisperContext, samples: &[f32], n_threads: usize) -> Result<Vec<LanguageProbability>> {
+    let mut state = ctx.create_state().context("failed to create state")?;
+    detect_language_with_state(ctx, &mut state, samples, n_threads)
+}
+
+/// `detect_language` with an existing state. Transcribing computes the mel again, so the state can be reused for it
+pub fn detect_language_with_state(
+    ctx: &WhisperContext,
+    state: &mut WhisperState,
+    samples: &[f32],
+    n_threads: usize,
+) -> Result<Vec<LanguageProbability>> {
+    if samples.is_empty() {
+        bail!("no audio to detect the language of")
+    }
+    if !ctx.is_multilingual() {
+        tracing::debug!("english only model");
+        return Ok(vec![LanguageProbability {
+            code: "en".into(),
+            name: "english".into(),
+            probability: 1.0,
+        }]);
+    }
+    let n_threads = n_threads.max(1);
+    state.pcm_to_mel(samples, n_threads).context("failed to compute mel")?;
+    let (_, probabilities) = state.lang_detect(0, n_threads).context("failed to detect language")?;
+    let languages = probabilities
+        .into_iter()
+        .enumerate()
+        .filter_map(|(id, probability)| {
+            let code = whisper_rs::get_lang_str(id as i32)?;
+            let name = whisper_rs::get_lang_str_full(id as i32).unwrap_or(code);
+            Some(LanguageProbability {
+                code: code.into(),
+                name: name.into(),
+                probability,
+            })
+        })
+        .collect();
+    let languages = rank(languages);
+    tracing::debug!("detected languages {:?}", languages.iter().take(3).collect::<Vec<_>>());
+    Ok(languages)
+}
+
+/// The most likely of `candidates`, or of all languages without candidates
+pub fn most_likely<'a>(languages: &'a [LanguageProbability], candidates: Option<&[String]>) -> Option<&'a LanguageProbability> {
+    languages
+        .iter()
+        .find(|language| candidates.is_none_or(|candidates| candidates.contains(&language.code)))
+}
+
+/// Identify the language of the first `seconds` of a 16KHz mono wav file
+pub fn detect_language_from_file(
+    ctx: &WhisperContext,
+    path: &Path,
+    seconds: u32,
+    n_threads: usize,
+) -> Result<Vec<LanguageProbability>> {
+    let window = seconds.max(1) as usize * SAMPLE_RATE;
+    let chunk = WavChunks::open(path, window, 0)?
+        .next()
+        .context("empty audio file")??;
+    let mut samples = vec![0.0f32; chunk.samples.len()];
+    whisper_rs::convert_integer_to_float_audio(&chunk.samples, &mut samples)?;
+    detect_language(ctx, &samples, n_threads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_and_most_likely() {
+        let language = |code: &str, probability| LanguageProbability {
+            code: code.into(),
+            name: code.into(),
+            probability,
+        };
+        let ranked = rank(vec![language("en", 0.2), language("de", 0.0), language("he", 0.7), language("fr", 0.1)]);
+        let codes: Vec<&str> = ranked.iter().map(|language| language.code.as_str()).collect();
+        assert_eq!(codes, vec!["he", "en", "fr"]);
+
+        assert_eq!(most_likely(&ranked, None).unwrap().code, "he");
+        let candidates = vec!["fr".to_string(), "en".to_string()];
+        assert_eq!(most_likely(&ranked, Some(&candidates)).unwrap().code, "en");
+        assert!(most_likely(&ranked, Some(&["ar".to_string()])).is_none());
+    }
+}
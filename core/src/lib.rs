@@ -1,12 +1,23 @@
 pub mod audio;
+pub mod audio_cache;
 pub mod audio_capture;
+#[cfg(feature = "decoder")]
+pub mod audio_decode;
+pub mod batch;
+pub mod checkpoint;
 pub mod config;
+pub mod diarize;
 pub mod dictation;
 pub mod dictation_history;
 pub mod dictation_transcribe;
 pub mod downloader;
+pub mod glossary;
+pub mod language;
+pub mod speaker_profiles;
 pub mod transcribe;
 pub mod transcript;
+pub mod translate;
+pub mod vad;
 
 #[cfg(test)]
 mod test;
@@ -0,0 +1,239 @@
+use eyre::{bail, eyre, Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// A named voice enrolled from sample clips
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct SpeakerProfile {
+    pub id: i64,
+    pub name: String,
+    pub created_at: String, // ISO 8601
+    /// Number of clips averaged into the embedding
+    pub clips: i64,
+    /// Voice embedding of pyannote's speaker model
+    #[serde(skip)]
+    pub embedding: Vec<f32>,
+}
+
+// Embeddings have hundreds of values, too many for logs
+impl fmt::Debug for SpeakerProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpeakerProfile")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("created_at", &self.created_at)
+            .field("clips", &self.clips)
+            .finish_non_exhaustive()
+    }
+}
+
+fn to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Voice embedding of a clip of a single speaker, 16KHz mono.
+/// The embeddings of its speech segments are averaged, so that silence and noise between them are left out
+pub fn clip_embedding(samples: &[i16], segment_model_path: &str, embedding_model_path: &str) -> Result<Vec<f32>> {
+    let segments = pyannote_rs::get_segments(samples, 16000, segment_model_path)
+        .map_err(|e| eyre!("{:?}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| eyre!("Failed to collect segments: {:?}", e))?;
+    let mut extractor = pyannote_rs::EmbeddingExtractor::new(embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let mut embeddings = Vec::new();
+    for segment in &segments {
+        match extractor.compute(&segment.samples) {
+            Ok(embedding) => embeddings.push(embedding.collect::<Vec<f32>>()),
+            Err(error) => tracing::debug!("no embedding for {:.2} - {:.2}: {:?}", segment.start, segment.end, error),
+        }
+    }
+    if embeddings.is_empty() {
+        bail!("no speech found in the clip")
+    }
+    tracing::debug!("voice embedding of {} segments", embeddings.len());
+    let mut average = vec![0.0f32; embeddings[0].len()];
+    for embedding in &embeddings {
+        for (sum, value) in average.iter_mut().zip(embedding) {
+            *sum += value / embeddings.len() as f32;
+        }
+    }
+    Ok(average)
+}
+
+pub struct SpeakerProfiles {
+    conn: Connection,
+}
+
+/// Trimmed profile name. Numbers are the ids of unnamed speakers in transcripts, so they can't be names
+fn profile_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("speaker name is empty")
+    }
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        bail!("speaker name {} is a number, which is used for unnamed speakers", name)
+    }
+    Ok(name)
+}
+
+impl SpeakerProfiles {
+    /// Create or open the speaker profiles database
+    pub fn new(db_path: &Path) -> Result<Self> {
+        let conn = Connection::open(db_path).context("Failed to open speaker profiles database")?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS speaker_profiles (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                created_at TEXT NOT NULL,
+                clips INTEGER NOT NULL,
+                embedding BLOB NOT NULL
+            )",
+            [],
+        )
+        .context("Failed to create speaker_profiles table")?;
+
+        Ok(Self { conn })
+    }
+
+    /// Enroll a voice embedding under `name`. Enrolling an existing name again averages the clips into its profile
+    pub fn enroll(&self, name: &str, embedding: &[f32]) -> Result<i64> {
+        let name = profile_name(name)?;
+        let existing = self
+            .conn
+            .query_row(
+                "SELECT id, clips, embedding FROM speaker_profiles WHERE name = ?1",
+                params![name],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, Vec<u8>>(2)?)),
+            )
+            .optional()
+            .context("Failed to query speaker profile")?;
+
+        match existing {
+            Some((id, clips, blob)) => {
+                let previous = from_blob(&blob);
+                if previous.len() != embedding.len() {
+                    bail!("{} was enrolled with another speaker model", name)
+                }
+                let weight = clips as f32;
+                let average: Vec<f32> = previous
+                    .iter()
+                    .zip(embedding)
+                    .map(|(previous, value)| (previous * weight + value) / (weight + 1.0))
+                    .collect();
+                self.conn
+                    .execute(
+                        "UPDATE speaker_profiles SET clips = ?1, embedding = ?2 WHERE id = ?3",
+                        params![clips + 1, to_blob(&average), id],
+                    )
+                    .context("Failed to update speaker profile")?;
+                Ok(id)
+            }
+            None => {
+                let created_at = chrono::Utc::now().to_rfc3339();
+                self.conn
+                    .execute(
+                        "INSERT INTO speaker_profiles (name, created_at, clips, embedding) VALUES (?1, ?2, 1, ?3)",
+                        params![name, created_at, to_blob(embedding)],
+                    )
+                    .context("Failed to insert speaker profile")?;
+                Ok(self.conn.last_insert_rowid())
+            }
+        }
+    }
+
+    /// Get all profiles ordered by name
+    pub fn get_all(&self) -> Result<Vec<SpeakerProfile>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, created_at, clips, embedding FROM speaker_profiles ORDER BY name")
+            .context("Failed to prepare get_all query")?;
+
+        let profiles = stmt
+            .query_map([], |row| {
+                Ok(SpeakerProfile {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                    clips: row.get(3)?,
+                    embedding: from_blob(&row.get::<_, Vec<u8>>(4)?),
+                })
+            })
+            .context("Failed to query profiles")?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("Failed to collect profiles")?;
+
+        Ok(profiles)
+    }
+
+    /// Rename a profile
+    pub fn rename(&self, id: i64, name: &str) -> Result<()> {
+        let name = profile_name(name)?;
+        self.conn
+            .execute("UPDATE speaker_profiles SET name = ?1 WHERE id = ?2", params![name, id])
+            .context("Failed to rename speaker profile")?;
+
+        Ok(())
+    }
+
+    /// Delete a profile by ID
+    pub fn delete(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM speaker_profiles WHERE id = ?1", params![id])
+            .context("Failed to delete speaker profile")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_enroll_and_average() {
+        let dir = tempdir().unwrap();
+        let profiles = SpeakerProfiles::new(&dir.path().join("speakers.db")).unwrap();
+
+        let alice = profiles.enroll("Alice", &[1.0, 0.0, 0.5]).unwrap();
+        profiles.enroll("Bob", &[0.0, 1.0, 0.0]).unwrap();
+        // Enrolling again averages the clips
+        assert_eq!(profiles.enroll(" Alice ", &[0.0, 0.0, 0.5]).unwrap(), alice);
+        assert!(profiles.enroll("Alice", &[1.0]).is_err());
+        assert!(profiles.enroll("  ", &[1.0, 0.0, 0.0]).is_err());
+        // Numbers are speaker ids
+        assert!(profiles.enroll(" 2", &[1.0, 0.0, 0.0]).is_err());
+
+        let all = profiles.get_all().unwrap();
+        let names: Vec<&str> = all.iter().map(|profile| profile.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert_eq!(all[0].clips, 2);
+        assert_eq!(all[0].embedding, vec![0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_rename_and_delete() {
+        let dir = tempdir().unwrap();
+        let profiles = SpeakerProfiles::new(&dir.path().join("speakers.db")).unwrap();
+
+        let id = profiles.enroll("Alice", &[1.0, 0.0]).unwrap();
+        profiles.rename(id, "Alicia").unwrap();
+        assert_eq!(profiles.get_all().unwrap()[0].name, "Alicia");
+        assert!(profiles.rename(id, "2").is_err());
+        assert_eq!(profiles.get_all().unwrap()[0].name, "Alicia");
+
+        profiles.delete(id).unwrap();
+        assert!(profiles.get_all().unwrap().is_empty());
+        // Deleting again is a no-op
+        profiles.delete(id).unwrap();
+    }
+}
@@ -23,10 +23,24 @@ fn test_transcribe() {
         max_text_ctx: None,
         n_threads: None,
         temperature: None,
+        temperatures: None,
+        compression_ratio_threshold: None,
+        logprob_threshold: None,
+        no_speech_threshold: None,
         translate: None,
         word_timestamps: None,
         sampling_bestof_or_beam_size: None,
         sampling_strategy: None,
+        chunk_duration: None,
+        chunk_overlap: None,
+        checkpoint: None,
+        vad: None,
+        detect_no_speech: None,
+        cleanup: None,
+        glossary: None,
+        multilingual: None,
+        languages: None,
+        translation: None,
     };
     let start = Instant::now();
     let result = crate::transcribe::transcribe(&ctx, options, None, None, None, None, None);
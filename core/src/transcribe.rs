@@ -1,20 +1,83 @@
+use crate::audio;
+use crate::audio_cache::AudioCache;
+use crate::checkpoint::CheckpointFile;
 use crate::config::TranscribeOptions;
-use crate::transcript::{Segment, Transcript};
-use crate::{audio, get_vibe_temp_folder};
+use crate::diarize::{self, DiarizeMode, SpeakerTurn, WindowEmbedding};
+use crate::glossary::Glossary;
+use crate::language;
+use crate::speaker_profiles::SpeakerProfile;
+use crate::transcript::{cleanup, Confidence, Overlap, Segment, Timestamp, Transcript, Word};
+use crate::vad;
 use eyre::{bail, eyre, Context, OptionExt, Result};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
 use hound::WavReader;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::c_int;
+use std::io::Write;
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::rc::Rc;
 use std::time::Instant;
 pub use whisper_rs::SegmentCallbackData;
 pub use whisper_rs::WhisperContext;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContextParameters};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContextParameters, WhisperState};
 
-type ProgressCallbackType = once_cell::sync::Lazy<Mutex<Option<Box<dyn Fn(i32) + Send + Sync>>>>;
-static PROGRESS_CALLBACK: ProgressCallbackType = once_cell::sync::Lazy::new(|| Mutex::new(None));
+/// Sample rate of normalized audio
+const SAMPLE_RATE: usize = 16000;
+/// Default overlap between windows in chunked mode
+const DEFAULT_CHUNK_OVERLAP_SEC: u32 = 5;
+/// Window length when checkpoints are enabled without a chunk duration
+const DEFAULT_CHECKPOINT_CHUNK_SEC: u32 = 300;
+/// Window length in multilingual mode without a chunk duration. Whisper detects the language of 30 seconds
+const DEFAULT_MULTILINGUAL_CHUNK_SEC: u32 = 30;
+/// Fallback thresholds of the reference whisper implementation
+const DEFAULT_COMPRESSION_RATIO_THRESHOLD: f32 = 2.4;
+const DEFAULT_LOGPROB_THRESHOLD: f32 = -1.0;
+const DEFAULT_NO_SPEECH_THRESHOLD: f32 = 0.6;
+/// Upper bound when counting the tokens of a prompt
+const MAX_PROMPT_TOKENS: usize = 1024;
+
+/// Phases of a transcription. With diarize, the speakers of the whole audio are found before transcribing it
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgressPhase {
+    /// Finding speech segments
+    Segmentation,
+    /// Computing the voice embedding of every segment
+    Embedding,
+    Transcription,
+}
+
+/// Reported to the progress callback. The percent starts over in every phase
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    pub percent: i32,
+}
+
+impl Progress {
+    /// Percent of `done` out of `total`, at most 100
+    pub fn new(phase: ProgressPhase, done: f64, total: f64) -> Self {
+        let percent = if total > 0.0 { done / total * 100.0 } else { 100.0 };
+        Self {
+            phase,
+            percent: percent.clamp(0.0, 100.0) as i32,
+        }
+    }
+}
+
+pub type ProgressCallback = Box<dyn Fn(Progress) + Send + Sync>;
+
+type ProgressCallbackType = RefCell<Option<Box<dyn Fn(i32) + Send + Sync>>>;
+thread_local! {
+    // whisper.cpp reports progress on the thread that called full(),
+    // so transcriptions running in parallel threads keep their own callback
+    static PROGRESS_CALLBACK: ProgressCallbackType = const { RefCell::new(None) };
+}
 
 pub fn create_context(model_path: &Path, gpu_device: Option<i32>, use_gpu: Option<bool>) -> Result<WhisperContext> {
     whisper_rs::install_whisper_tracing_trampoline();
@@ -63,31 +126,58 @@ pub fn should_normalize(source: PathBuf) -> bool {
     true
 }
 
-fn generate_cache_key(source: &Path, additional_ffmpeg_args: &Option<Vec<String>>) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    source.hash(&mut hasher);
+pub fn create_normalized_audio(source: PathBuf, additional_ffmpeg_args: Option<Vec<String>>) -> Result<PathBuf> {
+    tracing::debug!("normalize {:?}", source.display());
+
+    let cache = AudioCache::default();
+    let cache_key = AudioCache::key(&source, &additional_ffmpeg_args)?;
+    if let Some(cached) = cache.get(&cache_key) {
+        tracing::info!("Using cached normalized audio: {}", cached.display());
+        return Ok(cached);
+    }
+    cache.insert(&cache_key, |out_path| normalize_to(&source, out_path, additional_ffmpeg_args))
+}
 
-    if let Some(args) = additional_ffmpeg_args {
-        for arg in args {
-            arg.hash(&mut hasher);
+fn normalize_to(source: &Path, out_path: &Path, additional_ffmpeg_args: Option<Vec<String>>) -> Result<()> {
+    // Prefer the native decoder. additional ffmpeg args are filters only ffmpeg understands,
+    // so keep using ffmpeg for them when it's available.
+    #[cfg(feature = "decoder")]
+    {
+        let has_ffmpeg_args = additional_ffmpeg_args.as_ref().is_some_and(|args| !args.is_empty());
+        let ffmpeg_available = audio::find_ffmpeg_path().is_some();
+        if !has_ffmpeg_args || !ffmpeg_available {
+            if has_ffmpeg_args {
+                tracing::warn!("ffmpeg not found. ignoring additional ffmpeg args {:?}", additional_ffmpeg_args);
+            }
+            match crate::audio_decode::decode_to_wav(source, out_path) {
+                Ok(()) => return Ok(()),
+                Err(error) if !ffmpeg_available => {
+                    return Err(error.wrap_err("native decoder failed and ffmpeg not found"));
+                }
+                Err(error) => {
+                    tracing::debug!("native decoder failed: {:?}. falling back to ffmpeg", error);
+                }
+            }
         }
     }
 
-    hasher.finish()
+    audio::normalize(source.to_path_buf(), out_path.to_path_buf(), additional_ffmpeg_args)
 }
 
-pub fn create_normalized_audio(source: PathBuf, additional_ffmpeg_args: Option<Vec<String>>) -> Result<PathBuf> {
-    tracing::debug!("normalize {:?}", source.display());
-
-    let cache_key = generate_cache_key(&source, &additional_ffmpeg_args);
-    let out_path = get_vibe_temp_folder().join(format!("{:x}.wav", cache_key));
-    //if out_path.exists() {
-    //    tracing::info!("Using cached normalized audio: {}", out_path.display());
-    //   return Ok(out_path);
-    //}
-	// ^ TODO: should we use caching? what if we have two files with the same name?
-    audio::normalize(source, out_path.clone(), additional_ffmpeg_args)?;
-    Ok(out_path)
+/// Initial prompt with the glossary terms that fit in the prompt budget of whisper,
+/// half of the text context (224 tokens) or `max_text_ctx` when smaller
+pub fn glossary_prompt(
+    ctx: &WhisperContext,
+    glossary: &Glossary,
+    init_prompt: Option<&str>,
+    max_text_ctx: Option<i32>,
+) -> Option<String> {
+    let max_tokens = (ctx.n_text_ctx() / 2).min(max_text_ctx.unwrap_or(i32::MAX)).max(0) as usize;
+    glossary.prompt(init_prompt, max_tokens, |text| match ctx.tokenize(text, MAX_PROMPT_TOKENS) {
+        Ok(tokens) => tokens.len(),
+        // More tokens than we'd ever use
+        Err(_) => MAX_PROMPT_TOKENS + 1,
+    })
 }
 
 pub fn setup_params(options: &TranscribeOptions) -> FullParams<'_, '_> {
@@ -115,7 +205,10 @@ pub fn setup_params(options: &TranscribeOptions) -> FullParams<'_, '_> {
     if let Some(true) = options.word_timestamps {
         params.set_token_timestamps(true);
         params.set_split_on_word(true);
-        params.set_max_len(options.max_sentence_len.unwrap_or(1));
+        // Word timings are kept in Segment.words, split segments only when asked to
+        if let Some(max_sentence_len) = options.max_sentence_len {
+            params.set_max_len(max_sentence_len);
+        }
     }
 
     if let Some(true) = options.translate {
@@ -136,6 +229,12 @@ pub fn setup_params(options: &TranscribeOptions) -> FullParams<'_, '_> {
         tracing::debug!("setting temperature to {temperature}");
         params.set_temperature(temperature);
     }
+    if let Some(temperature) = options.temperatures.as_ref().and_then(|temperatures| temperatures.first()) {
+        tracing::debug!("setting temperature schedule {:?}", options.temperatures);
+        params.set_temperature(*temperature);
+        // Failed segments are decoded again by us with the rest of the schedule
+        params.set_temperature_inc(0.0);
+    }
 
     if let Some(max_text_ctx) = options.max_text_ctx {
         tracing::debug!("setting n_max_text_ctx to {}", max_text_ctx);
@@ -155,18 +254,724 @@ pub fn setup_params(options: &TranscribeOptions) -> FullParams<'_, '_> {
     params
 }
 
+/// Special tokens such as [_BEG_], [_TT_150] or <|endoftext|>
+fn is_special_token(text: &str) -> bool {
+    (text.starts_with("[_") && text.ends_with(']')) || (text.starts_with("<|") && text.ends_with("|>"))
+}
+
+/// Merge tokens (text, start, stop, probability) into words.
+/// A token starting with a space starts a new word, others such as punctuation or
+/// sub-words continue the previous one.
+fn merge_tokens_to_words<I>(tokens: I) -> Vec<Word>
+where
+    I: IntoIterator<Item = (String, Timestamp, Timestamp, f32)>,
+{
+    let mut words: Vec<Word> = Vec::new();
+    let mut token_counts: Vec<usize> = Vec::new();
+    for (text, start, stop, probability) in tokens {
+        if text.is_empty() || is_special_token(&text) {
+            continue;
+        }
+        match words.last_mut() {
+            Some(word) if !text.starts_with(' ') => {
+                word.text.push_str(&text);
+                word.stop = word.stop.max(stop);
+                word.probability += probability;
+                if let Some(count) = token_counts.last_mut() {
+                    *count += 1;
+                }
+            }
+            _ => {
+                words.push(Word {
+                    text: text.trim_start().to_string(),
+                    start,
+                    stop,
+                    probability,
+                });
+                token_counts.push(1);
+            }
+        }
+    }
+    for (word, count) in words.iter_mut().zip(token_counts) {
+        word.probability /= count as f32;
+    }
+    words.retain(|word| !word.text.trim().is_empty());
+    words
+}
+
+/// Ratio between the text size and its zlib compressed size.
+/// Repetitive text such as looped hallucinations compresses well and gets a high ratio.
+pub fn compression_ratio(text: &str) -> f32 {
+    if text.is_empty() {
+        return 0.0;
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|compressed| compressed.len())
+        .unwrap_or(text.len());
+    text.len() as f32 / compressed.max(1) as f32
+}
+
+/// No-speech probability the way whisper computes it before decoding a window:
+/// the probability of the no-speech token right after the prompt.
+/// Windows are 30s aligned, so it costs one extra encoder run per 30s of audio.
+struct NoSpeechDetector<'a> {
+    ctx: &'a WhisperContext,
+    translate: bool,
+    n_threads: usize,
+    cache: HashMap<i64, Option<f32>>,
+}
+
+impl<'a> NoSpeechDetector<'a> {
+    /// Length of a whisper window in centiseconds
+    const WINDOW: i64 = 3000;
+
+    fn new(ctx: &'a WhisperContext, options: &TranscribeOptions) -> Self {
+        Self {
+            ctx,
+            translate: options.translate == Some(true),
+            n_threads: options.n_threads.unwrap_or(4).max(1) as usize,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Forget the cached windows, the state was used with other samples
+    fn reset(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Probability of no speech in the window of `timestamp` (in the samples of the last `full` run)
+    fn prob(&mut self, state: &mut WhisperState, timestamp: Timestamp) -> Option<f32> {
+        let window = timestamp.as_centis().max(0) / Self::WINDOW * Self::WINDOW;
+        if let Some(prob) = self.cache.get(&window) {
+            return *prob;
+        }
+        let prob = self
+            .compute(state, window)
+            .map_err(|error| tracing::debug!("failed to compute no speech prob at {}: {:?}", window, error))
+            .ok();
+        self.cache.insert(window, prob);
+        prob
+    }
+
+    fn compute(&self, state: &mut WhisperState, window: i64) -> Result<f32> {
+        let mut prompt = vec![self.ctx.token_sot()];
+        if self.ctx.is_multilingual() {
+            let lang_id = state.full_lang_id_from_state().context("failed to get language")?;
+            prompt.push(self.ctx.token_lang(lang_id));
+            prompt.push(if self.translate {
+                self.ctx.token_translate()
+            } else {
+                self.ctx.token_transcribe()
+            });
+        }
+        // Mel frames are 10ms, the same unit as whisper timestamps
+        state.encode(window as usize, self.n_threads).context("failed to encode")?;
+        state.decode(&prompt, 0, self.n_threads).context("failed to decode")?;
+        let logits = state.get_logits().context("failed to get logits")?;
+        let nosp = self.ctx.token_nosp() as usize;
+        if nosp >= logits.len() {
+            bail!("no speech token out of range")
+        }
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let sum: f32 = logits.iter().map(|logit| (logit - max).exp()).sum();
+        Ok((logits[nosp] - max).exp() / sum)
+    }
+}
+
+/// Words and confidence of a whisper segment. `offset` is added to token timestamps
+fn get_segment_details(
+    state: &WhisperState,
+    segment: c_int,
+    text: &str,
+    with_words: bool,
+    offset: Timestamp,
+) -> Result<(Vec<Word>, Confidence)> {
+    let num_tokens = state.full_n_tokens(segment).context("failed to get number of tokens")?;
+    let mut tokens = Vec::new();
+    let mut logprob_sum = 0.0;
+    let mut logprob_count = 0;
+    for t in 0..num_tokens {
+        let token_text = state.full_get_token_text_lossy(segment, t).context("failed to get token text")?;
+        if is_special_token(&token_text) {
+            continue;
+        }
+        let data = state.full_get_token_data(segment, t).context("failed to get token data")?;
+        logprob_sum += data.plog;
+        logprob_count += 1;
+        if with_words {
+            let start = offset + Timestamp::from_centis(data.t0);
+            let stop = offset + Timestamp::from_centis(data.t1);
+            tokens.push((token_text, start, stop, data.p));
+        }
+    }
+    let confidence = Confidence {
+        avg_logprob: logprob_sum / logprob_count.max(1) as f32,
+        no_speech_prob: None,
+        compression_ratio: compression_ratio(text.trim()),
+    };
+    Ok((merge_tokens_to_words(tokens), confidence))
+}
+
+/// Segments of the last `full` run on the whisper timeline shifted by `offset`
+fn read_raw_segments(
+    state: &mut WhisperState,
+    options: &TranscribeOptions,
+    mut no_speech: Option<&mut NoSpeechDetector>,
+    offset: Timestamp,
+) -> Result<Vec<Segment>> {
+    let with_words = options.word_timestamps == Some(true);
+    let num_segments = state.full_n_segments().context("failed to get number of segments")?;
+    let mut segments = Vec::new();
+    for s in 0..num_segments {
+        let text = state.full_get_segment_text_lossy(s).context("failed to get segment")?;
+        let start = Timestamp::from_centis(state.full_get_segment_t0(s).context("failed to get start timestamp")?);
+        let stop = Timestamp::from_centis(state.full_get_segment_t1(s).context("failed to get end timestamp")?);
+        let (words, mut confidence) = get_segment_details(state, s, &text, with_words, offset)?;
+        if let Some(no_speech) = no_speech.as_mut() {
+            confidence.no_speech_prob = no_speech.prob(state, start);
+        }
+        segments.push(Segment {
+            text,
+            start: offset + start,
+            stop: offset + stop,
+            speaker: None,
+            words,
+            confidence: Some(confidence),
+            hallucination: None,
+            language: None,
+            translation: None,
+            overlap: None,
+        });
+    }
+    Ok(segments)
+}
+
+/// Params of the caller that every decode of the same audio keeps, including the ones of the temperature fallback
+#[derive(Clone, Default)]
+struct DecodeParams {
+    single_segment: bool,
+    abort_callback: Option<Rc<dyn Fn() -> bool>>,
+}
+
+impl DecodeParams {
+    fn setup<'a>(&self, options: &'a TranscribeOptions) -> FullParams<'a, 'a> {
+        let mut params = setup_params(options);
+        params.set_single_segment(self.single_segment);
+        if let Some(abort_callback) = self.abort_callback.clone() {
+            params.set_abort_callback_safe(move || abort_callback());
+        }
+        params
+    }
+
+    fn aborted(&self) -> bool {
+        self.abort_callback.as_ref().is_some_and(|abort_callback| abort_callback())
+    }
+}
+
+/// Segments of the last `full` run on `samples`. Segments failing the fallback thresholds are decoded again
+/// with `decode`. `map_timestamp` converts whisper timestamps to the transcript timeline
+fn read_segments<F>(
+    state: &mut WhisperState,
+    options: &TranscribeOptions,
+    decode: &DecodeParams,
+    samples: &[f32],
+    no_speech: Option<&mut NoSpeechDetector>,
+    map_timestamp: F,
+) -> Result<Vec<Segment>>
+where
+    F: Fn(Timestamp) -> Timestamp,
+{
+    let mut segments = read_raw_segments(state, options, no_speech, Timestamp::ZERO)?;
+    if let Some(fallback) = Fallback::new(options, decode) {
+        segments = fallback.redecode_failed(state, samples, segments)?;
+    }
+    let language = options.lang.clone().filter(|_| options.multilingual == Some(true));
+    for segment in &mut segments {
+        segment.language = language.clone();
+        segment.start = map_timestamp(segment.start);
+        segment.stop = map_timestamp(segment.stop);
+        for word in &mut segment.words {
+            word.start = map_timestamp(word.start);
+            word.stop = map_timestamp(word.stop);
+        }
+    }
+    Ok(segments)
+}
+
+/// Outcome of checking a decoded segment against the fallback thresholds
+#[derive(Debug, PartialEq)]
+enum FallbackCheck {
+    Pass,
+    /// Likely a repetition loop or garbage, decode again at a higher temperature
+    Retry,
+    /// Low probability text over silence, dropped like the reference implementation does
+    Silence,
+}
+
+/// Temperature fallback of the reference whisper implementation.
+/// A segment whose compression ratio is too high or average log probability too low
+/// is decoded again with the next temperature of the schedule.
+struct Fallback<'a> {
+    options: &'a TranscribeOptions,
+    decode: &'a DecodeParams,
+    /// Temperatures after the first one, which is used by `setup_params`
+    temperatures: &'a [f32],
+    compression_ratio_threshold: f32,
+    logprob_threshold: f32,
+    no_speech_threshold: f32,
+}
+
+impl<'a> Fallback<'a> {
+    /// Shorter segments don't carry enough audio to decode again
+    const MIN_SAMPLES: usize = SAMPLE_RATE;
+
+    /// Enabled when the options have a temperature schedule
+    fn new(options: &'a TranscribeOptions, decode: &'a DecodeParams) -> Option<Self> {
+        let temperatures = options.temperatures.as_deref().filter(|temperatures| !temperatures.is_empty())?;
+        Some(Self {
+            options,
+            decode,
+            temperatures: &temperatures[1..],
+            compression_ratio_threshold: options.compression_ratio_threshold.unwrap_or(DEFAULT_COMPRESSION_RATIO_THRESHOLD),
+            logprob_threshold: options.logprob_threshold.unwrap_or(DEFAULT_LOGPROB_THRESHOLD),
+            no_speech_threshold: options.no_speech_threshold.unwrap_or(DEFAULT_NO_SPEECH_THRESHOLD),
+        })
+    }
+
+    fn check(&self, segment: &Segment) -> FallbackCheck {
+        let Some(ref confidence) = segment.confidence else {
+            return FallbackCheck::Pass;
+        };
+        let low_logprob = confidence.avg_logprob < self.logprob_threshold;
+        if low_logprob && confidence.no_speech_prob.is_some_and(|prob| prob > self.no_speech_threshold) {
+            FallbackCheck::Silence
+        } else if low_logprob || confidence.compression_ratio > self.compression_ratio_threshold {
+            FallbackCheck::Retry
+        } else {
+            FallbackCheck::Pass
+        }
+    }
+
+    fn redecode_failed(&self, state: &mut WhisperState, samples: &[f32], segments: Vec<Segment>) -> Result<Vec<Segment>> {
+        let mut result = Vec::new();
+        for segment in segments {
+            match self.check(&segment) {
+                FallbackCheck::Pass => result.push(segment),
+                FallbackCheck::Silence => tracing::debug!("drop segment over silence: {}", segment.text),
+                FallbackCheck::Retry => result.extend(self.redecode(state, samples, segment)?),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Decode the audio of `segment` with increasing temperatures until the result passes the thresholds.
+    /// Keeps the attempt with the best average log probability when none passes.
+    fn redecode(&self, state: &mut WhisperState, samples: &[f32], segment: Segment) -> Result<Vec<Segment>> {
+        let start = segment.start.as_samples(SAMPLE_RATE).min(samples.len());
+        let stop = segment.stop.as_samples(SAMPLE_RATE).clamp(start, samples.len());
+        if stop - start < Self::MIN_SAMPLES {
+            return Ok(vec![segment]);
+        }
+        let no_speech_prob = segment.confidence.as_ref().and_then(|confidence| confidence.no_speech_prob);
+        let mut best_logprob = avg_logprob(std::slice::from_ref(&segment));
+        let mut best = vec![segment];
+        for &temperature in self.temperatures {
+            if self.decode.aborted() {
+                break;
+            }
+            tracing::debug!("decode segment again with temperature {}: {}", temperature, best[0].text);
+            let mut params = self.decode.setup(self.options);
+            params.set_temperature(temperature);
+            state.full(params, &samples[start..stop]).context("failed to transcribe")?;
+            let mut attempt = read_raw_segments(state, self.options, None, Timestamp::from_samples(start, SAMPLE_RATE))?;
+            if attempt.is_empty() {
+                continue;
+            }
+            // Same audio, so the same chance of no speech
+            for attempt_segment in &mut attempt {
+                if let Some(ref mut confidence) = attempt_segment.confidence {
+                    confidence.no_speech_prob = no_speech_prob;
+                }
+            }
+            if attempt.iter().all(|segment| self.check(segment) == FallbackCheck::Pass) {
+                return Ok(attempt);
+            }
+            let logprob = avg_logprob(&attempt);
+            if logprob > best_logprob {
+                best_logprob = logprob;
+                best = attempt;
+            }
+        }
+        Ok(best)
+    }
+}
+
+/// Segments decoded from the audio of one speaker turn as a single segment, with the confidence of the first
+fn join_segments(segments: Vec<Segment>) -> Option<Segment> {
+    let mut segments = segments.into_iter();
+    let first = segments.next()?;
+    Some(segments.fold(first, |mut joined, segment| {
+        joined.text.push_str(&segment.text);
+        joined.stop = joined.stop.max(segment.stop);
+        joined.words.extend(segment.words);
+        joined
+    }))
+}
+
+fn avg_logprob(segments: &[Segment]) -> f32 {
+    let logprobs: Vec<f32> = segments
+        .iter()
+        .filter_map(|segment| segment.confidence.as_ref().map(|confidence| confidence.avg_logprob))
+        .collect();
+    if logprobs.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    logprobs.iter().sum::<f32>() / logprobs.len() as f32
+}
+
+/// Stitches segments of overlapping windows into a single timeline.
+/// The middle of each overlap is the boundary between two windows.
+struct WindowStitcher {
+    /// Overlap between windows
+    overlap: Timestamp,
+    keep_from: Timestamp,
+    last: Option<(Timestamp, String)>,
+}
+
+impl WindowStitcher {
+    fn new(overlap: Timestamp) -> Self {
+        Self {
+            overlap,
+            keep_from: Timestamp::ZERO,
+            last: None,
+        }
+    }
+
+    /// Continue after segments committed by a previous run
+    fn resume(&mut self, keep_from: Timestamp, last: Option<&Segment>) {
+        self.keep_from = keep_from;
+        self.last = last.map(|segment| (segment.stop, segment.text.clone()));
+    }
+
+    /// Takes the segments of a window with absolute timestamps and returns the ones to commit.
+    /// `keep_until` is the boundary with the next window, `None` for the last window.
+    fn push(&mut self, window_segments: Vec<Segment>, keep_until: Option<Timestamp>) -> Vec<Segment> {
+        let mut committed = Vec::new();
+        for segment in window_segments {
+            if segment.start < self.keep_from || keep_until.is_some_and(|keep_until| segment.start >= keep_until) {
+                continue;
+            }
+            if let Some((last_stop, last_text)) = &self.last {
+                // Same speech transcribed by both windows
+                let midpoint = Timestamp::from_millis((segment.start.as_millis() + segment.stop.as_millis()) / 2);
+                let repeated = segment.text.trim() == last_text.trim() && segment.start - *last_stop < self.overlap;
+                if midpoint < *last_stop || repeated {
+                    tracing::trace!("drop overlapped segment {:?}", segment);
+                    continue;
+                }
+            }
+            self.last = Some((segment.stop, segment.text.clone()));
+            committed.push(segment);
+        }
+        if let Some(keep_until) = keep_until {
+            self.keep_from = keep_until;
+        }
+        committed
+    }
+}
+
+/// Options for transcribing a chunk or a diarized segment.
+/// In multilingual mode they have the language detected from its samples
+fn with_detected_language<'a>(
+    ctx: &WhisperContext,
+    state: &mut WhisperState,
+    options: &'a TranscribeOptions,
+    samples: &[f32],
+) -> Result<Cow<'a, TranscribeOptions>> {
+    if options.multilingual != Some(true) {
+        return Ok(Cow::Borrowed(options));
+    }
+    let n_threads = options.n_threads.unwrap_or(4) as usize;
+    let languages = language::detect_language_with_state(ctx, state, samples, n_threads)?;
+    let Some(detected) = language::most_likely(&languages, options.languages.as_deref()) else {
+        return Ok(Cow::Borrowed(options));
+    };
+    tracing::debug!("detected language {} ({:.2})", detected.code, detected.probability);
+    Ok(Cow::Owned(TranscribeOptions {
+        lang: Some(detected.code.clone()),
+        ..options.clone()
+    }))
+}
+
+/// Transcribe the normalized wav in overlapping windows so memory stays flat regardless of file length.
+/// With a checkpoint file, progress is saved after every window and a previous run is resumed.
+#[allow(clippy::too_many_arguments)]
+fn transcribe_chunked(
+    ctx: &WhisperContext,
+    state: &mut WhisperState,
+    options: &TranscribeOptions,
+    path: &Path,
+    chunk_duration: u32,
+    checkpoint_file: Option<CheckpointFile>,
+    progress_callback: Option<ProgressCallback>,
+    new_segment_callback: Option<Box<dyn Fn(Segment)>>,
+    abort_callback: Option<Rc<dyn Fn() -> bool>>,
+) -> Result<Vec<Segment>> {
+    let chunk_duration = chunk_duration.max(1);
+    let overlap = options
+        .chunk_overlap
+        .unwrap_or(DEFAULT_CHUNK_OVERLAP_SEC)
+        .min(chunk_duration / 2);
+    tracing::debug!("chunked transcribe. duration {}s overlap {}s", chunk_duration, overlap);
+
+    let window = chunk_duration as usize * SAMPLE_RATE;
+    let overlap = overlap as usize * SAMPLE_RATE;
+    let mut chunks = audio::WavChunks::open(path, window, overlap)?;
+    let total = chunks.total_samples().max(1);
+
+    let decode = DecodeParams {
+        single_segment: false,
+        abort_callback: abort_callback.clone(),
+    };
+    let mut stitcher = WindowStitcher::new(Timestamp::from_samples(overlap, SAMPLE_RATE));
+    let mut samples = Vec::new();
+
+    let mut progress = checkpoint_file.as_ref().and_then(|file| file.load()).unwrap_or_default();
+    if progress.offset > 0 {
+        tracing::debug!(
+            "resume from checkpoint at sample {} with {} segments",
+            progress.offset,
+            progress.segments.len()
+        );
+        chunks.seek(progress.offset)?;
+        stitcher.resume(progress.keep_from, progress.segments.last());
+        if let Some(ref new_segment_callback) = new_segment_callback {
+            for segment in &progress.segments {
+                new_segment_callback(segment.clone());
+            }
+        }
+    }
+
+    let mut no_speech = (options.detect_no_speech == Some(true)).then(|| NoSpeechDetector::new(ctx, options));
+    let mut vad_detector = options.vad.as_ref().map(vad::create_detector);
+    let mut aborted = false;
+    for chunk in chunks {
+        if abort_callback.as_ref().is_some_and(|abort| abort()) {
+            tracing::debug!("chunked transcribe aborted");
+            aborted = true;
+            break;
+        }
+        let chunk = chunk?;
+        let chunk_len = chunk.samples.len();
+        tracing::debug!("transcribe window at sample {} ({} samples)", chunk.offset, chunk_len);
+
+        let speech = match vad_detector {
+            Some(ref mut detector) => Some(vad::detect_speech_with(detector.as_mut(), &chunk.samples)?),
+            None => None,
+        };
+        let window_samples = match speech {
+            Some(ref speech) => speech.speech_samples(&chunk.samples),
+            None => chunk.samples,
+        };
+        let to_original = |timestamp: Timestamp| speech.as_ref().map_or(timestamp, |speech| speech.to_original(timestamp));
+
+        let offset = Timestamp::from_samples(chunk.offset, SAMPLE_RATE);
+        let mut window_segments = Vec::new();
+        if window_samples.is_empty() {
+            tracing::debug!("no speech in window");
+        } else {
+            samples.resize(window_samples.len(), 0.0f32);
+            whisper_rs::convert_integer_to_float_audio(&window_samples, &mut samples)?;
+
+            let options = with_detected_language(ctx, state, options, &samples)?;
+            state.full(decode.setup(&options), &samples).context("failed to transcribe")?;
+
+            if let Some(ref mut no_speech) = no_speech {
+                no_speech.reset();
+            }
+            window_segments = read_segments(state, &options, &decode, &samples, no_speech.as_mut(), |timestamp| {
+                offset + to_original(timestamp)
+            })?;
+        }
+
+        let keep_until = if chunk.is_last {
+            None
+        } else {
+            Some(Timestamp::from_samples(chunk.offset + window - overlap / 2, SAMPLE_RATE))
+        };
+        for segment in stitcher.push(window_segments, keep_until) {
+            if let Some(ref new_segment_callback) = new_segment_callback {
+                new_segment_callback(segment.clone());
+            }
+            progress.segments.push(segment);
+        }
+
+        if let Some(ref checkpoint_file) = checkpoint_file {
+            if !chunk.is_last {
+                progress.offset = chunk.offset + window - overlap;
+                progress.keep_from = stitcher.keep_from;
+                checkpoint_file.save(&progress)?;
+            }
+        }
+
+        if let Some(ref progress_callback) = progress_callback {
+            let progress = Progress::new(ProgressPhase::Transcription, (chunk.offset + chunk_len) as f64, total as f64);
+            tracing::trace!("progress chunked: {}", progress.percent);
+            progress_callback(progress);
+        }
+    }
+
+    // Keep the checkpoint of an aborted run to resume it later
+    if let Some(checkpoint_file) = checkpoint_file {
+        if !aborted {
+            checkpoint_file.remove()?;
+        }
+    }
+    Ok(progress.segments)
+}
+
 #[derive(Debug, Clone)]
 pub struct DiarizeOptions {
     pub segment_model_path: String,
     pub embedding_model_path: String,
+    /// Minimum similarity of segments of the same speaker, when the number of speakers isn't known
     pub threshold: f32,
     pub max_speakers: usize,
+    /// Exact number of speakers. Found with `threshold`, up to `max_speakers`, when None
+    pub speakers: Option<usize>,
+    pub mode: DiarizeMode,
+    /// Enrolled voices. Speakers matching one of them are labelled with its name
+    pub profiles: Vec<SpeakerProfile>,
+    /// Minimum similarity of a speaker to an enrolled voice to be labelled with its name
+    pub profile_threshold: f32,
+    /// Mark segments in which several speakers talk at once. Computes an embedding for every second of speech
+    pub detect_overlap: bool,
+}
+
+/// Windows compared to the speakers to find overlapping speech, in segments at least twice as long
+const OVERLAP_WINDOW_SEC: f64 = 1.5;
+const OVERLAP_STEP_SEC: f64 = 0.75;
+
+/// Speech segment of pyannote with its speaker
+struct SpeakerSegment {
+    segment: pyannote_rs::Segment,
+    speaker: String,
+    overlap: Option<Overlap>,
+}
+
+/// Embeddings of overlapping windows of a speech segment
+fn window_embeddings(extractor: &mut pyannote_rs::EmbeddingExtractor, segment: &pyannote_rs::Segment) -> Vec<WindowEmbedding> {
+    let window = (OVERLAP_WINDOW_SEC * 16000.0) as usize;
+    let step = (OVERLAP_STEP_SEC * 16000.0) as usize;
+    if segment.samples.len() < window * 2 {
+        return Vec::new();
+    }
+    let segment_start = Timestamp::from_secs_f64(segment.start);
+    (0..=segment.samples.len() - window)
+        .step_by(step)
+        .filter_map(|offset| {
+            let embedding = extractor.compute(&segment.samples[offset..offset + window]).ok()?;
+            Some(WindowEmbedding {
+                start: segment_start + Timestamp::from_samples(offset, 16000),
+                stop: segment_start + Timestamp::from_samples(offset + window, 16000),
+                embedding: embedding.collect(),
+            })
+        })
+        .collect()
+}
+
+/// Speech segments of pyannote and the speaker of each one. Speakers are clustered from the embeddings of all
+/// the segments, so that the labels are consistent across the whole recording.
+/// Segmentation and embedding report their progress. When aborted no segments are returned, without clustering them
+fn find_speakers(
+    samples: &[i16],
+    diarize_options: &DiarizeOptions,
+    progress_callback: Option<&(dyn Fn(Progress) + Send + Sync)>,
+    abort_callback: Option<&dyn Fn() -> bool>,
+) -> Result<Vec<SpeakerSegment>> {
+    let report = |phase, done: f64, total: f64| {
+        if let Some(progress_callback) = progress_callback {
+            progress_callback(Progress::new(phase, done, total));
+        }
+    };
+    // Callers stop right away too
+    let aborted = || abort_callback.is_some_and(|abort_callback| abort_callback());
+
+    // Segments are found window by window while iterating
+    let duration = samples.len() as f64 / 16000.0;
+    let mut diarize_segments = Vec::new();
+    let segments = pyannote_rs::get_segments(samples, 16000, &diarize_options.segment_model_path).map_err(|e| eyre!("{:?}", e))?;
+    for diarize_segment in segments {
+        if aborted() {
+            tracing::debug!("segmentation aborted");
+            return Ok(Vec::new());
+        }
+        let diarize_segment = diarize_segment.map_err(|e| eyre!("Failed to collect segments: {:?}", e))?;
+        report(ProgressPhase::Segmentation, diarize_segment.end, duration);
+        diarize_segments.push(diarize_segment);
+    }
+    report(ProgressPhase::Segmentation, duration, duration);
+
+    let mut extractor =
+        pyannote_rs::EmbeddingExtractor::new(&diarize_options.embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
+    let mut embeddings = Vec::with_capacity(diarize_segments.len());
+    let mut windows = Vec::new();
+    for (i, diarize_segment) in diarize_segments.iter().enumerate() {
+        if aborted() {
+            tracing::debug!("embedding aborted");
+            return Ok(Vec::new());
+        }
+        let embedding = match extractor.compute(&diarize_segment.samples) {
+            Ok(result) => Some(result.collect::<Vec<f32>>()),
+            Err(error) => {
+                tracing::error!("error: {:?}", error);
+                tracing::trace!("no embedding for start = {:.2}, end = {:.2}", diarize_segment.start, diarize_segment.end);
+                None
+            }
+        };
+        embeddings.push(embedding);
+        if diarize_options.detect_overlap {
+            windows.push(window_embeddings(&mut extractor, diarize_segment));
+        }
+        report(ProgressPhase::Embedding, (i + 1) as f64, diarize_segments.len() as f64);
+    }
+    let speakers = diarize::assign_speakers(
+        &embeddings,
+        diarize_options.speakers,
+        diarize_options.threshold,
+        diarize_options.max_speakers,
+        &diarize_options.profiles,
+        diarize_options.profile_threshold,
+    );
+    tracing::debug!("found {} speakers", speakers.iter().collect::<HashSet<_>>().len());
+    let centroids = diarize::speaker_centroids(&embeddings, &speakers);
+    let overlaps: Vec<Option<Overlap>> = speakers
+        .iter()
+        .enumerate()
+        .map(|(i, speaker)| {
+            let windows = windows.get(i)?;
+            diarize::find_overlap(speaker, windows, &centroids, diarize_options.threshold)
+        })
+        .collect();
+    Ok(diarize_segments
+        .into_iter()
+        .zip(speakers)
+        .zip(overlaps)
+        .map(|((segment, speaker), overlap)| SpeakerSegment {
+            segment,
+            speaker,
+            overlap,
+        })
+        .collect())
 }
 
 pub fn transcribe(
     ctx: &WhisperContext,
     options: &TranscribeOptions,
-    progress_callback: Option<Box<dyn Fn(i32) + Send + Sync>>,
+    progress_callback: Option<ProgressCallback>,
     new_segment_callback: Option<Box<dyn Fn(Segment)>>,
     abort_callback: Option<Box<dyn Fn() -> bool>>,
     diarize_options: Option<DiarizeOptions>,
@@ -178,6 +983,17 @@ pub fn transcribe(
         bail!("audio file doesn't exist")
     }
 
+    let checkpoint_file = if options.checkpoint == Some(true) && diarize_options.is_none() {
+        // The model file isn't known here, so its type, quantization and vocabulary stand for it
+        let model = format!("{} {} {}", ctx.model_type_readable().unwrap_or_default(), ctx.model_ftype(), ctx.model_n_vocab());
+        Some(CheckpointFile::new(options, &model, &additional_ffmpeg_args)?)
+    } else {
+        if options.checkpoint == Some(true) {
+            tracing::warn!("checkpoints are not supported with diarize");
+        }
+        None
+    };
+
     let out_path = if should_normalize(options.path.clone().into()) {
         create_normalized_audio(options.path.clone().into(), additional_ffmpeg_args)?
     } else {
@@ -185,29 +1001,84 @@ pub fn transcribe(
         options.path.clone().into()
     };
     tracing::debug!("out path is {}", out_path.display());
-    let original_samples = audio::parse_wav_file(&out_path)?;
 
-    let mut state = ctx.create_state().context("failed to create key")?;
+    let align_words = diarize_options
+        .as_ref()
+        .is_some_and(|diarize_options| diarize_options.mode == DiarizeMode::Words);
+    let options = &TranscribeOptions {
+        // Detected once, so that every window and diarized segment is decoded in the same language
+        lang: match options.lang.as_deref() {
+            Some(language::AUTO) => {
+                let n_threads = options.n_threads.unwrap_or(4) as usize;
+                let languages = language::detect_language_from_file(ctx, &out_path, language::DEFAULT_DETECT_SECONDS, n_threads)?;
+                Some(languages.first().map_or(language::AUTO.into(), |language| language.code.clone()))
+            }
+            _ => options.lang.clone(),
+        },
+        // The glossary terms go into the prompt of every decode, including fallback and chunked windows
+        init_prompt: match options.glossary {
+            Some(ref glossary) => glossary_prompt(ctx, glossary, options.init_prompt.as_deref(), options.max_text_ctx),
+            None => options.init_prompt.clone(),
+        },
+        // Speakers are found for every word
+        word_timestamps: if align_words { Some(true) } else { options.word_timestamps },
+        ..options.clone()
+    };
 
-    let mut params = setup_params(options);
+    let mut state = ctx.create_state().context("failed to create key")?;
+    // Shared with the decodes of the temperature fallback
+    let abort_callback: Option<Rc<dyn Fn() -> bool>> = abort_callback.map(Rc::from);
 
     let mut segments = Vec::new();
 
     let st = std::time::Instant::now();
-    if let Some(diarize_options) = diarize_options {
+    // Speaker turns of the words, found before transcribing the whole audio.
+    // Segments passed to new_segment_callback don't have a speaker yet
+    let speaker_turns = match diarize_options {
+        Some(ref diarize_options) if align_words => {
+            tracing::debug!("Diarize words enabled {:?}", diarize_options);
+            let original_samples = audio::parse_wav_file(&out_path)?;
+            let speakers = find_speakers(
+                &original_samples,
+                diarize_options,
+                progress_callback.as_deref(),
+                abort_callback.as_deref(),
+            )?;
+            let turns: Vec<SpeakerTurn> = speakers
+                .into_iter()
+                .map(|speaker_segment| SpeakerTurn {
+                    // pyannote timestamps are seconds
+                    start: Timestamp::from_secs_f64(speaker_segment.segment.start),
+                    stop: Timestamp::from_secs_f64(speaker_segment.segment.end),
+                    speaker: speaker_segment.speaker,
+                    overlap: speaker_segment.overlap,
+                })
+                .collect();
+            Some(turns)
+        }
+        _ => None,
+    };
+    if let Some(diarize_options) = diarize_options.filter(|_| !align_words) {
         tracing::debug!("Diarize enabled {:?}", diarize_options);
-        params.set_single_segment(true);
-
-        let diarize_segments_iter =
-            pyannote_rs::get_segments(&original_samples, 16000, diarize_options.segment_model_path).map_err(|e| eyre!("{:?}", e))?;
-        
-        // Collect segments to enable progress tracking and error handling
-        let diarize_segments: Vec<_> = diarize_segments_iter.collect::<Result<Vec<_>, _>>().map_err(|e| eyre!("Failed to collect segments: {:?}", e))?;
-        
-        let mut embedding_manager = pyannote_rs::EmbeddingManager::new(diarize_options.max_speakers);
-        let mut extractor =
-            pyannote_rs::EmbeddingExtractor::new(diarize_options.embedding_model_path).map_err(|e| eyre!("{:?}", e))?;
-        for (i, diarize_segment) in diarize_segments.iter().enumerate() {
+        if options.vad.is_some() {
+            tracing::debug!("skip vad. diarize transcribes only speech segments anyway");
+        }
+        let decode = DecodeParams {
+            single_segment: true,
+            abort_callback: abort_callback.clone(),
+        };
+        let original_samples = audio::parse_wav_file(&out_path)?;
+
+        let diarize_segments = find_speakers(
+            &original_samples,
+            &diarize_options,
+            progress_callback.as_deref(),
+            abort_callback.as_deref(),
+        )?;
+        let mut no_speech = (options.detect_no_speech == Some(true)).then(|| NoSpeechDetector::new(ctx, options));
+        for (i, speaker_segment) in diarize_segments.iter().enumerate() {
+            let diarize_segment = &speaker_segment.segment;
+            let speaker = &speaker_segment.speaker;
             if let Some(ref abort_callback) = abort_callback {
                 if abort_callback() {
                     break;
@@ -215,12 +1086,14 @@ pub fn transcribe(
             }
 
             // whisper compatible. segment indices
-            tracing::trace!("diarize segment: {} - {}", diarize_segment.start, diarize_segment.end);
+            tracing::trace!("diarize segment: {} - {} speaker {}", diarize_segment.start, diarize_segment.end, speaker);
 
             let mut samples = vec![0.0f32; diarize_segment.samples.len()];
 
             whisper_rs::convert_integer_to_float_audio(&diarize_segment.samples, &mut samples)?;
-            state.full(params.clone(), &samples).context("failed to transcribe")?;
+            // In the language detected for the segment with multilingual
+            let segment_options = with_detected_language(ctx, &mut state, options, &samples)?;
+            state.full(decode.setup(&segment_options), &samples).context("failed to transcribe")?;
 
             let num_segments = state.full_n_segments().context("failed to get number of segments")?;
             tracing::debug!("found {} sentence segments", num_segments);
@@ -228,40 +1101,30 @@ pub fn transcribe(
             tracing::debug!("looping segments...");
 
             if num_segments > 0 {
-                let embedding_result: Vec<f32> = match extractor.compute(&diarize_segment.samples) {
-                    Ok(result) => result.collect(),
-                    Err(error) => {
-                        tracing::error!("error: {:?}", error);
-                        tracing::trace!(
-                            "start = {:.2}, end = {:.2}, speaker = ?",
-                            diarize_segment.start,
-                            diarize_segment.end
-                        );
-                        continue; // Skip to the next segment
-                    }
-                };
-                // Find the speaker
-                let speaker = if embedding_manager.get_all_speakers().len() == diarize_options.max_speakers {
-                    embedding_manager
-                        .get_best_speaker_match(embedding_result)
-                        .map(|r| r.to_string())
-                        .unwrap_or("?".into())
-                } else {
-                    embedding_manager
-                        .search_speaker(embedding_result, diarize_options.threshold)
-                        .map(|r| r.to_string())
-                        .unwrap_or("?".into())
+                // pyannote timestamps are seconds
+                let start = Timestamp::from_secs_f64(diarize_segment.start);
+                let stop = Timestamp::from_secs_f64(diarize_segment.end);
+                if let Some(ref mut no_speech) = no_speech {
+                    no_speech.reset();
+                }
+                let whisper_segments = read_segments(
+                    &mut state,
+                    &segment_options,
+                    &decode,
+                    &samples,
+                    no_speech.as_mut(),
+                    |timestamp| start + timestamp,
+                )?;
+                // Dropped by the temperature fallback as silence
+                let Some(whisper_segment) = join_segments(whisper_segments) else {
+                    continue;
                 };
-
-                // convert to whisper compatible timestamps
-                let start = 100 * (diarize_segment.start as i64);
-                let stop = 100 * (diarize_segment.end as i64);
-                let text = state.full_get_segment_text_lossy(0).context("failed to get segment")?;
                 let segment = Segment {
-                    speaker: Some(speaker),
+                    speaker: Some(speaker.clone()),
+                    overlap: speaker_segment.overlap.clone(),
                     start,
                     stop,
-                    text,
+                    ..whisper_segment
                 };
                 segments.push(segment.clone());
 
@@ -270,59 +1133,105 @@ pub fn transcribe(
                 }
                 if let Some(ref progress_callback) = progress_callback {
                     tracing::trace!("progress: {} * {} / 100", i, diarize_segments.len());
-                    let progress = ((i + 1) as f64 / diarize_segments.len() as f64 * 100.0) as i32;
-                    tracing::trace!("progress diarize: {}", progress);
+                    let progress = Progress::new(ProgressPhase::Transcription, (i + 1) as f64, diarize_segments.len() as f64);
+                    tracing::trace!("progress diarize: {}", progress.percent);
                     progress_callback(progress);
                 }
             }
         }
+    } else if speaker_turns.is_some() && abort_callback.as_ref().is_some_and(|abort_callback| abort_callback()) {
+        tracing::debug!("aborted while finding speakers. skip transcribe");
+    } else if let Some(chunk_duration) = options
+        .chunk_duration
+        .or(checkpoint_file.as_ref().map(|_| DEFAULT_CHECKPOINT_CHUNK_SEC))
+        .or((options.multilingual == Some(true)).then_some(DEFAULT_MULTILINGUAL_CHUNK_SEC))
+    {
+        segments = transcribe_chunked(
+            ctx,
+            &mut state,
+            options,
+            &out_path,
+            chunk_duration,
+            checkpoint_file,
+            progress_callback,
+            new_segment_callback,
+            abort_callback,
+        )?;
+        if segments.is_empty() {
+            bail!("no segments found!")
+        }
     } else {
+        let original_samples = audio::parse_wav_file(&out_path)?;
+        // Transcribe only speech and map the timestamps back to the original audio
+        let speech = match options.vad {
+            Some(ref vad) => Some(vad::detect_speech(vad, &original_samples)?),
+            None => None,
+        };
+        let original_samples = match speech {
+            Some(ref speech) if speech.is_empty() => bail!("no speech found!"),
+            Some(ref speech) => speech.speech_samples(&original_samples),
+            None => original_samples,
+        };
+        let has_progress_callback = progress_callback.is_some();
         if let Some(callback) = progress_callback {
-            let mut guard = PROGRESS_CALLBACK.lock().map_err(|e| eyre!("{:?}", e))?;
-            let internal_progress_callback = move |progress: i32| callback(progress);
-            *guard = Some(Box::new(internal_progress_callback));
+            let internal_progress_callback = move |percent: i32| {
+                callback(Progress {
+                    phase: ProgressPhase::Transcription,
+                    percent,
+                })
+            };
+            PROGRESS_CALLBACK.with(|guard| *guard.borrow_mut() = Some(Box::new(internal_progress_callback)));
         }
         let mut samples = vec![0.0f32; original_samples.len()];
 
         whisper_rs::convert_integer_to_float_audio(&original_samples, &mut samples)?;
 
+        let decode = DecodeParams {
+            single_segment: false,
+            abort_callback,
+        };
+        let mut params = decode.setup(options);
         if let Some(new_segment_callback) = new_segment_callback {
+            let speech = speech.clone();
+            let to_original = move |timestamp: i64| {
+                let timestamp = Timestamp::from_centis(timestamp);
+                speech.as_ref().map_or(timestamp, |speech| speech.to_original(timestamp))
+            };
             let internal_new_segment_callback = move |segment: SegmentCallbackData| {
                 new_segment_callback(Segment {
-                    start: segment.start_timestamp,
-                    stop: segment.end_timestamp,
+                    start: to_original(segment.start_timestamp),
+                    stop: to_original(segment.end_timestamp),
                     speaker: None,
                     text: segment.text,
+                    words: Vec::new(),
+                    confidence: None,
+                    hallucination: None,
+                    language: None,
+                    translation: None,
+                    overlap: None,
                 })
             };
             params.set_segment_callback_safe_lossy(internal_new_segment_callback);
         }
 
-        if let Some(abort_callback) = abort_callback {
-            params.set_abort_callback_safe(abort_callback);
-        }
-
-        if PROGRESS_CALLBACK.lock().map_err(|e| eyre!("{:?}", e))?.as_ref().is_some() {
+        if has_progress_callback {
             params.set_progress_callback_safe(|progress| {
                 // using move here lead to crash
                 tracing::trace!("progress callback {}", progress);
-                match PROGRESS_CALLBACK.lock() {
-                    Ok(callback_guard) => {
-                        if let Some(progress_callback) = callback_guard.as_ref() {
-                            progress_callback(progress);
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to lock PROGRESS_CALLBACK: {:?}", e);
+                PROGRESS_CALLBACK.with(|callback_guard| {
+                    if let Some(progress_callback) = callback_guard.borrow().as_ref() {
+                        progress_callback(progress);
                     }
-                }
+                });
             });
         }
 
         tracing::debug!("set start time...");
 
         tracing::debug!("setting state full...");
-        state.full(params, &samples).context("failed to transcribe")?;
+        let full_result = state.full(params, &samples);
+        PROGRESS_CALLBACK.with(|guard| guard.borrow_mut().take());
+        full_result.context("failed to transcribe")?;
         let _et = std::time::Instant::now();
 
         tracing::debug!("getting segments count...");
@@ -333,24 +1242,153 @@ pub fn transcribe(
         tracing::debug!("found {} sentence segments", num_segments);
 
         tracing::debug!("looping segments...");
-        for s in 0..num_segments {
-            let text = state.full_get_segment_text_lossy(s).context("failed to get segment")?;
-            let start = state.full_get_segment_t0(s).context("failed to get start timestamp")?;
-            let stop = state.full_get_segment_t1(s).context("failed to get end timestamp")?;
-            segments.push(Segment {
-                text,
-                start,
-                stop,
-                speaker: None,
-            });
-        }
+        let mut no_speech = (options.detect_no_speech == Some(true)).then(|| NoSpeechDetector::new(ctx, options));
+        segments = read_segments(&mut state, options, &decode, &samples, no_speech.as_mut(), |timestamp| {
+            speech.as_ref().map_or(timestamp, |speech| speech.to_original(timestamp))
+        })?;
+    }
+
+    if let Some(ref turns) = speaker_turns {
+        segments = diarize::align_words(segments, turns);
+    }
+    if let Some(ref cleanup) = options.cleanup {
+        segments = cleanup::clean(segments, cleanup);
+    }
+    if let Some(ref glossary) = options.glossary {
+        segments = glossary.correct_segments(segments);
     }
 
     #[allow(unused_mut)]
     let mut transcript = Transcript {
         segments,
         processing_time_sec: Instant::now().duration_since(st).as_secs(),
+        language: options.lang.clone().filter(|lang| lang != language::AUTO),
     };
+    if let Some(ref translation) = options.translation {
+        transcript = transcript.translate(translation)?;
+    }
 
     Ok(transcript)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: i64, stop: i64, text: &str) -> Segment {
+        Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fallback_check() {
+        let options = TranscribeOptions {
+            temperatures: Some(vec![0.0, 0.2, 0.4]),
+            ..Default::default()
+        };
+        let decode = DecodeParams::default();
+        let fallback = Fallback::new(&options, &decode).unwrap();
+        assert_eq!(fallback.temperatures, &[0.2, 0.4]);
+        let check = |avg_logprob, compression_ratio, no_speech_prob| {
+            fallback.check(&Segment {
+                confidence: Some(Confidence {
+                    avg_logprob,
+                    no_speech_prob,
+                    compression_ratio,
+                }),
+                ..Default::default()
+            })
+        };
+        assert_eq!(check(-0.3, 1.5, None), FallbackCheck::Pass);
+        assert_eq!(check(-0.3, 3.0, None), FallbackCheck::Retry);
+        assert_eq!(check(-1.5, 1.5, None), FallbackCheck::Retry);
+        assert_eq!(check(-1.5, 1.5, Some(0.9)), FallbackCheck::Silence);
+        // Confident text is kept even when whisper thinks there's no speech
+        assert_eq!(check(-0.3, 1.5, Some(0.9)), FallbackCheck::Pass);
+        assert_eq!(fallback.check(&segment(0, 100, "no confidence")), FallbackCheck::Pass);
+
+        assert!(Fallback::new(&TranscribeOptions::default(), &decode).is_none());
+    }
+
+    #[test]
+    fn test_join_segments() {
+        assert!(join_segments(Vec::new()).is_none());
+        let joined = join_segments(vec![segment(100, 200, " Hello"), segment(200, 350, " there.")]).unwrap();
+        assert_eq!(joined.text, " Hello there.");
+        assert_eq!(joined.start, Timestamp::from_centis(100));
+        assert_eq!(joined.stop, Timestamp::from_centis(350));
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        assert_eq!(compression_ratio(""), 0.0);
+        let normal = compression_ratio("The quick brown fox jumps over the lazy dog.");
+        let looped = compression_ratio(&"Thank you. ".repeat(20));
+        assert!(normal < 2.0, "{}", normal);
+        assert!(looped > 2.4, "{}", looped);
+    }
+
+    #[test]
+    fn test_merge_tokens_to_words() {
+        let tokens = [
+            ("[_BEG_]", 0, 0, 1.0),
+            (" Hello", 0, 40, 0.9),
+            (",", 40, 45, 0.7),
+            (" wor", 50, 70, 0.6),
+            ("ld", 70, 90, 1.0),
+            ("[_TT_90]", 90, 90, 1.0),
+        ]
+        .map(|(text, start, stop, p)| (text.to_string(), Timestamp::from_centis(start), Timestamp::from_centis(stop), p));
+        let words = merge_tokens_to_words(tokens);
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].text, "Hello,");
+        assert_eq!((words[0].start, words[0].stop), (Timestamp::ZERO, Timestamp::from_centis(45)));
+        assert!((words[0].probability - 0.8).abs() < 1e-6);
+        assert_eq!(words[1].text, "world");
+        assert_eq!((words[1].start, words[1].stop), (Timestamp::from_centis(50), Timestamp::from_centis(90)));
+    }
+
+    #[test]
+    fn test_stitcher_drops_segments_past_boundary() {
+        let mut stitcher = WindowStitcher::new(Timestamp::from_centis(500));
+        let committed = stitcher.push(
+            vec![segment(0, 1000, "first"), segment(2800, 3000, "in overlap")],
+            Some(Timestamp::from_centis(2750)),
+        );
+        assert_eq!(committed.len(), 1);
+        assert_eq!(committed[0].text, "first");
+
+        // Next window starts at 2500, repeats the overlap and continues
+        let committed = stitcher.push(
+            vec![segment(2500, 2700, "tail of first"), segment(2800, 3000, "in overlap"), segment(3000, 3500, "next")],
+            None,
+        );
+        let texts: Vec<_> = committed.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["in overlap", "next"]);
+    }
+
+    #[test]
+    fn test_stitcher_dedups_repeated_text() {
+        let mut stitcher = WindowStitcher::new(Timestamp::from_centis(500));
+        stitcher.push(vec![segment(2000, 2740, " Hello there.")], Some(Timestamp::from_centis(2750)));
+        let committed = stitcher.push(vec![segment(2760, 2900, "Hello there."), segment(2900, 3100, "Bye.")], None);
+        assert_eq!(committed.len(), 1);
+        assert_eq!(committed[0].text, "Bye.");
+    }
+
+    #[test]
+    fn test_progress_percent() {
+        assert_eq!(Progress::new(ProgressPhase::Embedding, 1.0, 3.0).percent, 33);
+        // pyannote segments can end after the last sample
+        assert_eq!(Progress::new(ProgressPhase::Segmentation, 10.5, 10.0).percent, 100);
+        assert_eq!(Progress::new(ProgressPhase::Embedding, 0.0, 0.0).percent, 100);
+        assert_eq!(
+            serde_json::to_string(&Progress::new(ProgressPhase::Segmentation, 1.0, 2.0)).unwrap(),
+            r#"{"phase":"segmentation","percent":50}"#
+        );
+    }
+}
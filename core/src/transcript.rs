@@ -1,44 +1,94 @@
 use eyre::Result;
-use num::integer::div_floor;
 use serde::{Deserialize, Serialize};
 
-pub fn format_timestamp(seconds: i64, always_include_hours: bool, decimal_marker: &str) -> String {
-    assert!(seconds >= 0, "non-negative timestamp expected");
-    let mut milliseconds = seconds * 10;
+pub mod cleanup;
+pub mod edit;
+pub mod format;
+pub mod layout;
+pub mod parse;
+pub mod speakers;
+pub mod timestamp;
 
-    let hours = div_floor(milliseconds, 3_600_000);
-    milliseconds -= hours * 3_600_000;
+pub use timestamp::Timestamp;
 
-    let minutes = div_floor(milliseconds, 60_000);
-    milliseconds -= minutes * 60_000;
-
-    let seconds = div_floor(milliseconds, 1_000);
-    milliseconds -= seconds * 1_000;
-
-    let hours_marker = if always_include_hours || hours != 0 {
-        format!("{:02}:", hours)
-    } else {
-        String::new()
-    };
-
-    format!("{hours_marker}{minutes:02}:{seconds:02}{decimal_marker}{milliseconds:03}")
-}
+use cleanup::Hallucination;
+use speakers::SpeakerLabels;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
 pub struct Transcript {
     pub processing_time_sec: u64,
     pub segments: Vec<Segment>,
+    /// Language code of the speech, when it was given or detected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 #[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
 pub struct Segment {
-    pub start: i64,
-    pub stop: i64,
+    pub start: Timestamp,
+    pub stop: Timestamp,
     pub text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub speaker: Option<String>,
+    /// Available when transcribed with word timestamps
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// Set by the cleanup pass when flagging instead of dropping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hallucination: Option<Hallucination>,
+    /// Language code detected for the segment when transcribing with `multilingual`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Translated text, shown under the text in bilingual subtitles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translation: Option<String>,
+    /// Set by diarization when several speakers talk at once in the segment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<Overlap>,
+}
+
+/// Time range of a segment with overlapping speech
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Overlap {
+    pub start: Timestamp,
+    pub stop: Timestamp,
+    /// Speakers heard in the range, the speaker of the segment first
+    pub speakers: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Word {
+    pub text: String,
+    pub start: Timestamp,
+    pub stop: Timestamp,
+    /// Average probability of the word tokens
+    pub probability: f32,
+}
+
+/// Decoder statistics of a segment, the same ones whisper uses to decide whether to re-decode
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Confidence {
+    /// Average log probability of the text tokens
+    pub avg_logprob: f32,
+    /// Probability that the window has no speech. Only set with `detect_no_speech`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
+    /// Text size divided by its compressed size. High values mean repetitive text
+    pub compression_ratio: f32,
+}
+
+impl Confidence {
+    /// Geometric mean of the token probabilities, between 0 and 1
+    pub fn score(&self) -> f32 {
+        self.avg_logprob.exp()
+    }
 }
 
 impl Segment {
@@ -47,29 +97,113 @@ impl Segment {
     }
 
     pub fn as_vtt(&self) -> String {
+        self.vtt_cue(None)
+    }
+
+    /// WebVTT cue, with a voice tag for the speaker label
+    fn vtt_cue(&self, label: Option<&str>) -> String {
+        let voice = label.map(|label| format!("<v {}>", escape_vtt(label))).unwrap_or_default();
         format!(
-            "{} --> {}\n{}\n",
-            format_timestamp(self.start, false, "."),
-            format_timestamp(self.stop, false, "."),
-            self.text.trim().replace("-->", "->")
+            "{} --> {}\n{voice}{}\n",
+            self.start.format(false, "."),
+            self.stop.format(false, "."),
+            self.with_translation(self.vtt_text()).replace("-->", "->")
         )
     }
 
+    /// Text followed by the translation on its own lines, for bilingual subtitles
+    fn with_translation(&self, text: String) -> String {
+        match self.translation {
+            Some(ref translation) => format!("{text}\n{}", translation.trim()),
+            None => text,
+        }
+    }
+
+    /// Text with WebVTT timestamp tags before every word, so players can highlight words as they're spoken
+    fn vtt_text(&self) -> String {
+        if self.words.is_empty() {
+            return self.text.trim().to_string();
+        }
+        // Keep the line breaks of laid out subtitles
+        let line_lengths: Vec<usize> = self.text.trim().lines().map(|line| line.split_whitespace().count()).collect();
+        let line_starts: Vec<usize> = if line_lengths.iter().sum::<usize>() == self.words.len() {
+            line_lengths
+                .iter()
+                .scan(0, |start, len| {
+                    *start += len;
+                    Some(*start)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut text = String::new();
+        for (i, word) in self.words.iter().enumerate() {
+            if i > 0 {
+                text.push(if line_starts.contains(&i) { '\n' } else { ' ' });
+            }
+            if i == 0 || word.start <= self.start {
+                text.push_str(&word.text);
+            } else {
+                text.push_str(&format!("<{}>{}", word.start.format(false, "."), word.text));
+            }
+        }
+        text
+    }
+
     pub fn as_srt(&self, index: i32) -> String {
+        self.srt_cue(index, None)
+    }
+
+    /// SubRip cue, with the speaker label before the text
+    fn srt_cue(&self, index: i32, label: Option<&str>) -> String {
+        let label = label.map(|label| format!("{label}: ")).unwrap_or_default();
         format!(
-            "\n{index}\n{} --> {}\n{}\n",
-            format_timestamp(self.start, true, ","),
-            format_timestamp(self.stop, true, ","),
-            self.text.trim().replace("-->", "->")
+            "\n{index}\n{} --> {}\n{label}{}\n",
+            self.start.format(true, ","),
+            self.stop.format(true, ","),
+            self.with_translation(self.text.trim().to_string()).replace("-->", "->")
         )
     }
 }
 
+/// Voice annotations end at `>`, so it's escaped like in the cue text
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
 impl Transcript {
-    pub fn as_text(&self) -> String {
+    /// Segments with a confidence score below `threshold` (0 to 1) for review.
+    /// Segments without confidence, such as ones loaded from older transcripts, are skipped.
+    pub fn low_confidence_segments(&self, threshold: f32) -> Vec<&Segment> {
         self.segments
             .iter()
-            .fold(String::new(), |transcript, fragment| transcript + fragment.text.as_str())
+            .filter(|segment| segment.confidence.as_ref().is_some_and(|confidence| confidence.score() < threshold))
+            .collect()
+    }
+
+    pub fn as_text(&self) -> String {
+        self.as_text_with_speakers(&SpeakerLabels::default())
+    }
+
+    /// Plain text with every speaker label starting a new line
+    pub fn as_text_with_speakers(&self, speakers: &SpeakerLabels) -> String {
+        let labels = speakers.labels(&self.segments);
+        let mut text = String::new();
+        for (segment, label) in self.segments.iter().zip(labels) {
+            match label {
+                Some(label) => {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text += &format!("{label}: {}", segment.text.trim());
+                }
+                // Continues the turn of the previous speaker
+                None if segment.speaker.is_some() => text += &format!(" {}", segment.text.trim()),
+                None => text += &segment.text,
+            }
+        }
+        text
     }
 
     pub fn as_json(&self) -> Result<String> {
@@ -77,17 +211,132 @@ impl Transcript {
     }
 
     pub fn as_vtt(&self) -> String {
+        self.as_vtt_with_speakers(&SpeakerLabels::default())
+    }
+
+    /// WebVTT with `<v Speaker>` voice tags
+    pub fn as_vtt_with_speakers(&self, speakers: &SpeakerLabels) -> String {
+        let labels = speakers.labels(&self.segments);
         self.segments
             .iter()
-            .fold(String::new(), |transcript, fragment| transcript + fragment.as_vtt().as_str())
+            .zip(labels)
+            .fold(String::new(), |transcript, (fragment, label)| {
+                transcript + fragment.vtt_cue(label.as_deref()).as_str()
+            })
     }
 
     pub fn as_srt(&self) -> String {
+        self.as_srt_with_speakers(&SpeakerLabels::default())
+    }
+
+    /// SubRip with `Speaker: ` before the text
+    pub fn as_srt_with_speakers(&self, speakers: &SpeakerLabels) -> String {
+        let labels = speakers.labels(&self.segments);
         self.segments
             .iter()
-            .fold((1, String::new()), |(i, transcript), fragment| {
-                (i + 1, transcript + fragment.as_srt(i).as_str())
+            .zip(labels)
+            .fold((1, String::new()), |(i, transcript), (fragment, label)| {
+                (i + 1, transcript + fragment.srt_cue(i, label.as_deref()).as_str())
             })
             .1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_vtt_word_timestamps() {
+        let word = |text: &str, start, stop| Word {
+            text: text.into(),
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            probability: 1.0,
+        };
+        let segment = Segment {
+            start: Timestamp::from_centis(100),
+            stop: Timestamp::from_centis(250),
+            text: " Hello world".into(),
+            words: vec![word("Hello", 100, 150), word("world", 160, 250)],
+            ..Default::default()
+        };
+        assert_eq!(
+            segment.as_vtt(),
+            "00:01.000 --> 00:02.500\nHello <00:01.600>world\n"
+        );
+
+        let json = serde_json::to_string(&segment).unwrap();
+        let parsed: Segment = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.words, segment.words);
+
+        // Older transcripts have no words
+        let parsed: Segment = serde_json::from_str(r#"{"start": 0, "stop": 10, "text": "hi"}"#).unwrap();
+        assert!(parsed.words.is_empty());
+        assert!(!serde_json::to_string(&parsed).unwrap().contains("words"));
+    }
+
+    #[test]
+    fn test_low_confidence_segments() {
+        let segment = |text: &str, confidence: Option<f32>| Segment {
+            text: text.into(),
+            confidence: confidence.map(|avg_logprob| Confidence {
+                avg_logprob,
+                no_speech_prob: None,
+                compression_ratio: 1.0,
+            }),
+            ..Default::default()
+        };
+        let transcript = Transcript {
+            processing_time_sec: 0,
+            segments: vec![segment("sure", Some(-0.1)), segment("unsure", Some(-1.5)), segment("unknown", None)],
+            language: None,
+        };
+        let low: Vec<&str> = transcript
+            .low_confidence_segments(0.5)
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect();
+        assert_eq!(low, vec!["unsure"]);
+        assert!(transcript.low_confidence_segments(0.0).is_empty());
+    }
+
+    #[test]
+    fn test_speaker_labels() {
+        let segment = |start, stop, text: &str, speaker: &str| Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            speaker: Some(speaker.into()),
+            ..Default::default()
+        };
+        let transcript = Transcript {
+            processing_time_sec: 0,
+            segments: vec![
+                segment(0, 100, " Hi.", "0"),
+                segment(100, 200, " How are you?", "0"),
+                segment(200, 300, " Fine <thanks>", "1"),
+            ],
+            language: None,
+        };
+        assert_eq!(
+            transcript.as_srt(),
+            "\n1\n00:00:00,000 --> 00:00:01,000\nSpeaker 1: Hi.\n\n2\n00:00:01,000 --> 00:00:02,000\nSpeaker 1: How are you?\n\n\
+             3\n00:00:02,000 --> 00:00:03,000\nSpeaker 2: Fine <thanks>\n"
+        );
+        assert_eq!(transcript.as_text(), "Speaker 1: Hi.\nSpeaker 1: How are you?\nSpeaker 2: Fine <thanks>");
+
+        let speakers = SpeakerLabels {
+            names: HashMap::from([("1".into(), "Bob <guest>".into())]),
+            on_change_only: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            transcript.as_vtt_with_speakers(&speakers),
+            "00:00.000 --> 00:01.000\n<v Speaker 1>Hi.\n00:01.000 --> 00:02.000\nHow are you?\n\
+             00:02.000 --> 00:03.000\n<v Bob &lt;guest&gt;>Fine <thanks>\n"
+        );
+        assert_eq!(transcript.as_text_with_speakers(&speakers), "Speaker 1: Hi. How are you?\nBob <guest>: Fine <thanks>");
+    }
+}
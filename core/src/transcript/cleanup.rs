@@ -0,0 +1,228 @@
+use super::Segment;
+use serde::{Deserialize, Serialize};
+
+/// Whisper outputs these on music, noise and silence, mostly learned from subtitle credits
+const KNOWN_PHRASES: &[&str] = &[
+    "thank you for watching",
+    "thanks for watching",
+    "thank you so much for watching",
+    "please subscribe",
+    "please like and subscribe",
+    "subtitles by the amaraorg community",
+    "subtitles by",
+    "you",
+    "ご視聴ありがとうございました",
+    "продолжение следует",
+    "sous-titres réalisés par la communauté damaraorg",
+    "untertitel der amaraorg-community",
+    "untertitel im auftrag des zdf 2021",
+    "字幕由amaraorg社区提供",
+];
+
+const DEFAULT_MAX_REPEATS: usize = 3;
+const DEFAULT_MAX_CHARS_PER_SECOND: f32 = 25.0;
+/// Longest word n-gram searched for loops
+const MAX_NGRAM: usize = 8;
+
+/// What to do with detected hallucinations
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupAction {
+    #[default]
+    Drop,
+    /// Keep the segment and set `Segment::hallucination`
+    Flag,
+}
+
+/// Why a segment looks like a hallucination
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Hallucination {
+    /// A looped n-gram, or the same text as the previous segments
+    Repetition,
+    /// A phrase whisper is known to make up
+    KnownPhrase,
+    /// More characters per second than anyone can speak
+    CharsPerSecond,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct CleanupOptions {
+    #[serde(default)]
+    pub action: CleanupAction,
+    /// An n-gram or segment text repeated this many times in a row is a loop (default: 3)
+    pub max_repeats: Option<usize>,
+    /// Faster segments are implausible (default: 25)
+    pub max_chars_per_second: Option<f32>,
+    /// Phrases to treat as hallucinations in addition to the built in ones. Matched against the whole segment
+    #[serde(default)]
+    pub phrases: Vec<String>,
+}
+
+/// Lowercase words without punctuation, so "Thank you." and "thank you" compare equal
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || *c == '-')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether an n-gram repeats `max_repeats` times in a row and the loop covers most of the words
+fn has_loop(words: &[&str], max_repeats: usize) -> bool {
+    let max_repeats = max_repeats.max(2);
+    for n in 1..=MAX_NGRAM.min(words.len() / max_repeats) {
+        let mut start = 0;
+        while start + n * max_repeats <= words.len() {
+            let ngram = &words[start..start + n];
+            let repeats = words[start..].chunks_exact(n).take_while(|chunk| *chunk == ngram).count();
+            if repeats >= max_repeats && repeats * n * 2 >= words.len() {
+                return true;
+            }
+            start += 1;
+        }
+    }
+    false
+}
+
+fn chars_per_second(segment: &Segment) -> f32 {
+    let chars = segment.text.chars().filter(|c| !c.is_whitespace()).count() as f32;
+    let seconds = (segment.stop - segment.start).as_secs_f64() as f32;
+    if seconds <= 0.0 {
+        return if chars > 0.0 { f32::INFINITY } else { 0.0 };
+    }
+    chars / seconds
+}
+
+/// Detect hallucinations of every segment
+pub fn detect(segments: &[Segment], options: &CleanupOptions) -> Vec<Option<Hallucination>> {
+    let max_repeats = options.max_repeats.unwrap_or(DEFAULT_MAX_REPEATS);
+    let max_chars_per_second = options.max_chars_per_second.unwrap_or(DEFAULT_MAX_CHARS_PER_SECOND);
+    let phrases: Vec<String> = options.phrases.iter().map(|phrase| normalize(phrase)).collect();
+
+    let normalized: Vec<String> = segments.iter().map(|segment| normalize(&segment.text)).collect();
+    let mut result = Vec::with_capacity(segments.len());
+    let mut run = 0;
+    for (i, segment) in segments.iter().enumerate() {
+        let text = &normalized[i];
+        // Length of the run of identical segments ending here
+        run = if i > 0 && !text.is_empty() && normalized[i - 1] == *text {
+            run + 1
+        } else {
+            1
+        };
+        let run_is_loop = run > 1 && {
+            let total = run + normalized[i + 1..].iter().take_while(|next| *next == text).count();
+            total >= max_repeats
+        };
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        let hallucination = if run_is_loop || has_loop(&words, max_repeats) {
+            Some(Hallucination::Repetition)
+        } else if KNOWN_PHRASES.contains(&text.as_str()) || phrases.contains(text) {
+            Some(Hallucination::KnownPhrase)
+        } else if chars_per_second(segment) > max_chars_per_second {
+            Some(Hallucination::CharsPerSecond)
+        } else {
+            None
+        };
+        if let Some(hallucination) = hallucination {
+            tracing::debug!("{:?} hallucination: {}", hallucination, segment.text);
+        }
+        result.push(hallucination);
+    }
+    result
+}
+
+/// Drop or flag hallucinated segments
+pub fn clean(segments: Vec<Segment>, options: &CleanupOptions) -> Vec<Segment> {
+    let hallucinations = detect(&segments, options);
+    segments
+        .into_iter()
+        .zip(hallucinations)
+        .filter_map(|(mut segment, hallucination)| match (hallucination, options.action) {
+            (Some(_), CleanupAction::Drop) => None,
+            (hallucination, _) => {
+                segment.hallucination = hallucination;
+                Some(segment)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Timestamp;
+
+    fn segment(start: i64, stop: i64, text: &str) -> Segment {
+        Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_detect_hallucinations() {
+        let segments = vec![
+            segment(0, 300, " Welcome to the show."),
+            segment(300, 600, " Thank you. Thank you. Thank you. Thank you."),
+            segment(600, 900, " We'll be right back."),
+            segment(900, 1000, " Thanks for watching!"),
+            segment(1000, 1050, " This is far too much text for half a second of audio"),
+            segment(1100, 1200, " Again."),
+            segment(1200, 1300, " Again."),
+            segment(1300, 1400, " Again."),
+        ];
+        let detected = detect(&segments, &CleanupOptions::default());
+        assert_eq!(
+            detected,
+            vec![
+                None,
+                Some(Hallucination::Repetition),
+                None,
+                Some(Hallucination::KnownPhrase),
+                Some(Hallucination::CharsPerSecond),
+                None,
+                Some(Hallucination::Repetition),
+                Some(Hallucination::Repetition),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clean_drop_or_flag() {
+        let segments = vec![segment(0, 300, " Hello there."), segment(300, 600, " Custom credit line")];
+        let mut options = CleanupOptions {
+            phrases: vec!["custom credit line.".into()],
+            ..Default::default()
+        };
+        let cleaned = clean(segments.clone(), &options);
+        assert_eq!(cleaned.len(), 1);
+        assert_eq!(cleaned[0].text, " Hello there.");
+
+        options.action = CleanupAction::Flag;
+        let cleaned = clean(segments, &options);
+        assert_eq!(cleaned.len(), 2);
+        assert_eq!(cleaned[0].hallucination, None);
+        assert_eq!(cleaned[1].hallucination, Some(Hallucination::KnownPhrase));
+    }
+
+    #[test]
+    fn test_short_repeats_are_not_loops() {
+        let words = ["no", "no", "i", "said", "no", "to", "the", "offer"];
+        assert!(!has_loop(&words, 3));
+        let words = ["go", "go", "go"];
+        assert!(has_loop(&words, 3));
+    }
+}
@@ -0,0 +1,338 @@
+use super::layout::timed_words;
+use super::{Overlap, Segment, Timestamp, Transcript, Word};
+use eyre::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Where to split a segment
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum SplitAt {
+    /// Before the word at this index
+    Word(usize),
+    /// Before the first word starting at or after this time
+    Time(Timestamp),
+}
+
+/// An editing operation, for applying edits sent by the desktop app or given on the command line
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Edit {
+    Merge { first: usize, last: usize },
+    Split { index: usize, at: SplitAt },
+    Shift { offset: Timestamp },
+    Scale { factor: f64 },
+    RenameSpeaker { from: String, to: String },
+    MergeSpeakers { speakers: Vec<String>, into: String },
+    Replace { pattern: String, replacement: String },
+}
+
+fn scale(timestamp: Timestamp, factor: f64) -> Timestamp {
+    Timestamp::from_millis((timestamp.as_millis() as f64 * factor).round() as i64)
+}
+
+/// Segment text of words, with the leading space whisper puts before segments
+fn words_text(words: &[Word]) -> String {
+    words.iter().fold(String::new(), |text, word| text + " " + &word.text)
+}
+
+impl Transcript {
+    fn with_segments(&self, segments: Vec<Segment>) -> Transcript {
+        Transcript {
+            processing_time_sec: self.processing_time_sec,
+            segments,
+            language: self.language.clone(),
+        }
+    }
+
+    pub fn apply(&self, edit: &Edit) -> Result<Transcript> {
+        match edit {
+            Edit::Merge { first, last } => self.merge_segments(*first, *last),
+            Edit::Split { index, at } => self.split_segment(*index, *at),
+            Edit::Shift { offset } => Ok(self.shift(*offset)),
+            Edit::Scale { factor } => self.scale(*factor),
+            Edit::RenameSpeaker { from, to } => Ok(self.rename_speaker(from, to)),
+            Edit::MergeSpeakers { speakers, into } => Ok(self.merge_speakers(speakers, into)),
+            Edit::Replace { pattern, replacement } => self.replace(pattern, replacement),
+        }
+    }
+
+    /// Merge the segments from `first` to `last` (inclusive) into one with the speaker of the first.
+    /// Confidence describes the decoded text of a single segment, so it's dropped
+    pub fn merge_segments(&self, first: usize, last: usize) -> Result<Transcript> {
+        if first > last || last >= self.segments.len() {
+            bail!("invalid segment range {}..={} of {} segments", first, last, self.segments.len())
+        }
+        let merged = self.segments[first + 1..=last]
+            .iter()
+            .fold(self.segments[first].clone(), |mut merged, segment| {
+                merged.text = format!("{} {}", merged.text.trim_end(), segment.text.trim_start());
+                merged.stop = segment.stop;
+                merged.words.extend(segment.words.iter().cloned());
+                merged
+            });
+        let merged = Segment {
+            confidence: None,
+            hallucination: None,
+            ..merged
+        };
+        let mut segments = self.segments[..first].to_vec();
+        segments.push(merged);
+        segments.extend_from_slice(&self.segments[last + 1..]);
+        Ok(self.with_segments(segments))
+    }
+
+    /// Split a segment in two. Without word timestamps the time of the segment is spread by word length
+    pub fn split_segment(&self, index: usize, at: SplitAt) -> Result<Transcript> {
+        let Some(segment) = self.segments.get(index) else {
+            bail!("segment {} out of range", index)
+        };
+        let words = timed_words(segment);
+        let split = match at {
+            SplitAt::Word(word) => word,
+            SplitAt::Time(time) => words.iter().position(|word| word.start >= time).unwrap_or(words.len()),
+        };
+        if split == 0 || split >= words.len() {
+            bail!("can't split segment {} of {} words at {:?}", index, words.len(), at)
+        }
+        let keep_words = !segment.words.is_empty();
+        let (head, tail) = words.split_at(split);
+        let part = |words: &[Word], start, stop| Segment {
+            start,
+            stop,
+            text: words_text(words),
+            words: if keep_words { words.to_vec() } else { Vec::new() },
+            ..segment.clone()
+        };
+        let mut segments = self.segments[..index].to_vec();
+        segments.push(part(head, segment.start, head[head.len() - 1].stop));
+        segments.push(part(tail, tail[0].start, segment.stop));
+        segments.extend_from_slice(&self.segments[index + 1..]);
+        Ok(self.with_segments(segments))
+    }
+
+    /// Move every timestamp by `offset`, which can be negative.
+    /// Times before zero are clamped, and segments and overlaps ending before zero are removed
+    pub fn shift(&self, offset: Timestamp) -> Transcript {
+        let shift = |timestamp: Timestamp| (timestamp + offset).max(Timestamp::ZERO);
+        let segments = self
+            .segments
+            .iter()
+            .filter(|segment| segment.stop + offset > Timestamp::ZERO)
+            .map(|segment| Segment {
+                start: shift(segment.start),
+                stop: shift(segment.stop),
+                words: segment
+                    .words
+                    .iter()
+                    .map(|word| Word {
+                        start: shift(word.start),
+                        stop: shift(word.stop),
+                        ..word.clone()
+                    })
+                    .collect(),
+                overlap: segment
+                    .overlap
+                    .as_ref()
+                    .filter(|overlap| overlap.stop + offset > Timestamp::ZERO)
+                    .map(|overlap| Overlap {
+                        start: shift(overlap.start),
+                        stop: shift(overlap.stop),
+                        ..overlap.clone()
+                    }),
+                ..segment.clone()
+            })
+            .collect();
+        self.with_segments(segments)
+    }
+
+    /// Multiply every timestamp by `factor`. For a framerate conversion from 25 to 23.976 fps it's 25 / 23.976
+    pub fn scale(&self, factor: f64) -> Result<Transcript> {
+        if !factor.is_finite() || factor <= 0.0 {
+            bail!("invalid scale factor {}", factor)
+        }
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| Segment {
+                start: scale(segment.start, factor),
+                stop: scale(segment.stop, factor),
+                words: segment
+                    .words
+                    .iter()
+                    .map(|word| Word {
+                        start: scale(word.start, factor),
+                        stop: scale(word.stop, factor),
+                        ..word.clone()
+                    })
+                    .collect(),
+                overlap: segment.overlap.as_ref().map(|overlap| Overlap {
+                    start: scale(overlap.start, factor),
+                    stop: scale(overlap.stop, factor),
+                    ..overlap.clone()
+                }),
+                ..segment.clone()
+            })
+            .collect();
+        Ok(self.with_segments(segments))
+    }
+
+    /// Rename a speaker. Renaming to an existing speaker merges the two
+    pub fn rename_speaker(&self, from: &str, to: &str) -> Transcript {
+        self.merge_speakers(&[from.to_string()], to)
+    }
+
+    /// Give the segments of all `speakers` the speaker `into`
+    pub fn merge_speakers(&self, speakers: &[String], into: &str) -> Transcript {
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| match segment.speaker {
+                Some(ref speaker) if speakers.contains(speaker) => Segment {
+                    speaker: Some(into.to_string()),
+                    ..segment.clone()
+                },
+                _ => segment.clone(),
+            })
+            .collect();
+        self.with_segments(segments)
+    }
+
+    /// Replace matches of a regular expression in the text of every segment. `replacement` can refer to groups with `$1`.
+    /// Word timestamps are kept when the number of words doesn't change
+    pub fn replace(&self, pattern: &str, replacement: &str) -> Result<Transcript> {
+        let regex = Regex::new(pattern).with_context(|| format!("invalid pattern {}", pattern))?;
+        let segments = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let text = regex.replace_all(&segment.text, replacement);
+                if text == segment.text {
+                    return segment.clone();
+                }
+                let texts: Vec<&str> = text.split_whitespace().collect();
+                let words = if texts.len() == segment.words.len() {
+                    segment
+                        .words
+                        .iter()
+                        .zip(texts)
+                        .map(|(word, text)| Word {
+                            text: text.to_string(),
+                            ..word.clone()
+                        })
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                Segment {
+                    text: text.into_owned(),
+                    words,
+                    ..segment.clone()
+                }
+            })
+            .collect();
+        Ok(self.with_segments(segments))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: i64, stop: i64, text: &str, speaker: &str) -> Segment {
+        Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            speaker: Some(speaker.into()),
+            ..Default::default()
+        }
+    }
+
+    fn transcript() -> Transcript {
+        Transcript {
+            processing_time_sec: 0,
+            segments: vec![
+                segment(0, 100, " Hello there.", "1"),
+                segment(100, 300, " How are you doing today?", "1"),
+                segment(300, 400, " Fine.", "2"),
+            ],
+            language: None,
+        }
+    }
+
+    fn texts(transcript: &Transcript) -> Vec<&str> {
+        transcript.segments.iter().map(|segment| segment.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_merge_and_split() {
+        let transcript = transcript();
+        let merged = transcript.merge_segments(0, 1).unwrap();
+        assert_eq!(texts(&merged), vec![" Hello there. How are you doing today?", " Fine."]);
+        assert_eq!(merged.segments[0].stop, Timestamp::from_centis(300));
+        assert!(transcript.merge_segments(1, 3).is_err());
+
+        let split = transcript.split_segment(1, SplitAt::Word(3)).unwrap();
+        assert_eq!(texts(&split), vec![" Hello there.", " How are you", " doing today?", " Fine."]);
+        assert_eq!(split.segments[1].start, Timestamp::from_centis(100));
+        assert_eq!(split.segments[2].stop, Timestamp::from_centis(300));
+        assert_eq!(split.segments[1].stop, split.segments[2].start);
+        assert_eq!(split.segments[2].speaker.as_deref(), Some("1"));
+
+        let split = transcript.split_segment(1, SplitAt::Time(Timestamp::from_centis(190))).unwrap();
+        assert_eq!(texts(&split)[2], " doing today?");
+        assert!(transcript.split_segment(2, SplitAt::Word(1)).is_err());
+    }
+
+    #[test]
+    fn test_shift_and_scale() {
+        let mut transcript = transcript();
+        transcript.segments[1].overlap = Some(Overlap {
+            start: Timestamp::from_centis(120),
+            stop: Timestamp::from_centis(240),
+            speakers: vec!["1".into(), "2".into()],
+        });
+        let overlap = |transcript: &Transcript, index: usize| {
+            let overlap = transcript.segments[index].overlap.as_ref().unwrap();
+            (overlap.start, overlap.stop)
+        };
+        let shifted = transcript.shift(Timestamp::from_centis(-150));
+        assert_eq!(shifted.segments.len(), 2);
+        assert_eq!(shifted.segments[0].start, Timestamp::ZERO);
+        assert_eq!(shifted.segments[0].stop, Timestamp::from_centis(150));
+        assert_eq!(overlap(&shifted, 0), (Timestamp::ZERO, Timestamp::from_centis(90)));
+        assert_eq!(transcript.shift(Timestamp::from_centis(-250)).segments[0].overlap, None);
+
+        let scaled = transcript.scale(25.0 / 23.976).unwrap();
+        assert_eq!(scaled.segments[2].start, Timestamp::from_millis(3128));
+        assert_eq!(overlap(&scaled, 1), (Timestamp::from_millis(1251), Timestamp::from_millis(2503)));
+        assert!(transcript.scale(0.0).is_err());
+    }
+
+    #[test]
+    fn test_speakers_and_replace() {
+        let transcript = transcript();
+        let renamed = transcript.apply(&Edit::RenameSpeaker {
+            from: "2".into(),
+            to: "1".into(),
+        });
+        assert!(renamed.unwrap().segments.iter().all(|segment| segment.speaker.as_deref() == Some("1")));
+
+        let mut transcript = transcript;
+        transcript.segments[2].words = vec![Word {
+            text: "Fine.".into(),
+            start: Timestamp::from_centis(300),
+            stop: Timestamp::from_centis(400),
+            probability: 1.0,
+        }];
+        let replaced = transcript.replace(r"(?i)\bfine\b", "Great").unwrap();
+        assert_eq!(replaced.segments[2].text, " Great.");
+        assert_eq!(replaced.segments[2].words[0].text, "Great.");
+        let replaced = transcript.replace(r"Fine", "Very good").unwrap();
+        assert!(replaced.segments[2].words.is_empty());
+        assert!(transcript.replace("(", "").is_err());
+    }
+}
@@ -0,0 +1,333 @@
+use super::speakers::SpeakerLabels;
+use super::{Segment, Timestamp, Transcript};
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Output formats of a transcript
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Txt,
+    Srt,
+    Vtt,
+    Json,
+    /// start, end (milliseconds), speaker, text
+    Tsv,
+    /// start, end (milliseconds), speaker, text
+    Csv,
+    /// Lyrics for music players
+    Lrc,
+    /// Timed Text Markup Language, also known as DFXP
+    Ttml,
+    /// YouTube captions
+    Sbv,
+    /// Markdown with speaker headings and timestamps
+    Md,
+}
+
+impl TranscriptFormat {
+    pub const ALL: &'static [TranscriptFormat] = &[
+        Self::Txt,
+        Self::Srt,
+        Self::Vtt,
+        Self::Json,
+        Self::Tsv,
+        Self::Csv,
+        Self::Lrc,
+        Self::Ttml,
+        Self::Sbv,
+        Self::Md,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Txt => "txt",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+            Self::Tsv => "tsv",
+            Self::Csv => "csv",
+            Self::Lrc => "lrc",
+            Self::Ttml => "ttml",
+            Self::Sbv => "sbv",
+            Self::Md => "md",
+        }
+    }
+
+    /// File extension without the dot. Same as the format name
+    pub fn extension(&self) -> &'static str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for TranscriptFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TranscriptFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.to_lowercase();
+        // dfxp is the older name of ttml
+        let s = if s == "dfxp" { "ttml" } else { s.as_str() };
+        match Self::ALL.iter().find(|format| format.as_str() == s) {
+            Some(format) => Ok(*format),
+            None => bail!("unknown transcript format {}", s),
+        }
+    }
+}
+
+/// Quote a csv field when it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// LRC timestamp [mm:ss.xx]. Minutes keep counting past the hour
+fn lrc_timestamp(timestamp: Timestamp) -> String {
+    let timestamp = timestamp.as_centis().max(0);
+    format!("[{:02}:{:02}.{:02}]", timestamp / 6000, timestamp / 100 % 60, timestamp % 100)
+}
+
+/// SBV timestamp H:MM:SS.mmm
+fn sbv_timestamp(timestamp: Timestamp) -> String {
+    let timestamp = timestamp.as_millis().max(0);
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        timestamp / 3_600_000,
+        timestamp / 60_000 % 60,
+        timestamp / 1000 % 60,
+        timestamp % 1000
+    )
+}
+
+/// Speaker id of a segment, or the ids joined with "+" for overlapping speech
+fn speaker_ids(segment: &Segment) -> String {
+    match segment.overlap {
+        Some(ref overlap) if overlap.speakers.len() > 1 => overlap.speakers.join("+"),
+        _ => segment.speaker.clone().unwrap_or_default(),
+    }
+}
+
+/// Segment text on a single line
+fn single_line(segment: &Segment) -> String {
+    segment.text.trim().replace(['\t', '\n', '\r'], " ")
+}
+
+impl Transcript {
+    pub fn format(&self, format: TranscriptFormat) -> Result<String> {
+        self.format_with_speakers(format, &SpeakerLabels::default())
+    }
+
+    /// Render with custom speaker labels in the text, srt, vtt and markdown formats.
+    /// The tsv and csv speaker columns keep the ids, joined with "+" for overlapping speech
+    pub fn format_with_speakers(&self, format: TranscriptFormat, speakers: &SpeakerLabels) -> Result<String> {
+        Ok(match format {
+            TranscriptFormat::Txt => self.as_text_with_speakers(speakers),
+            TranscriptFormat::Srt => self.as_srt_with_speakers(speakers),
+            TranscriptFormat::Vtt => self.as_vtt_with_speakers(speakers),
+            TranscriptFormat::Json => self.as_json()?,
+            TranscriptFormat::Tsv => self.as_tsv(),
+            TranscriptFormat::Csv => self.as_csv(),
+            TranscriptFormat::Lrc => self.as_lrc(),
+            TranscriptFormat::Ttml => self.as_ttml(),
+            TranscriptFormat::Sbv => self.as_sbv(),
+            TranscriptFormat::Md => self.markdown(speakers),
+        })
+    }
+
+    pub fn as_tsv(&self) -> String {
+        self.segments.iter().fold("start\tend\tspeaker\ttext\n".to_string(), |transcript, segment| {
+            transcript
+                + &format!(
+                    "{}\t{}\t{}\t{}\n",
+                    segment.start.as_millis(),
+                    segment.stop.as_millis(),
+                    speaker_ids(segment).replace('\t', " "),
+                    single_line(segment)
+                )
+        })
+    }
+
+    pub fn as_csv(&self) -> String {
+        self.segments.iter().fold("start,end,speaker,text\n".to_string(), |transcript, segment| {
+            transcript
+                + &format!(
+                    "{},{},{},{}\n",
+                    segment.start.as_millis(),
+                    segment.stop.as_millis(),
+                    csv_field(&speaker_ids(segment)),
+                    csv_field(segment.text.trim())
+                )
+        })
+    }
+
+    pub fn as_lrc(&self) -> String {
+        self.segments.iter().fold(String::new(), |transcript, segment| {
+            transcript + &format!("{}{}\n", lrc_timestamp(segment.start), single_line(segment))
+        })
+    }
+
+    pub fn as_ttml(&self) -> String {
+        let paragraphs = self.segments.iter().fold(String::new(), |transcript, segment| {
+            transcript
+                + &format!(
+                    "      <p begin=\"{}\" end=\"{}\">{}</p>\n",
+                    segment.start.format(true, "."),
+                    segment.stop.format(true, "."),
+                    escape_xml(&segment.with_translation(segment.text.trim().to_string())).replace('\n', "<br/>")
+                )
+        });
+        // Empty when the language isn't known
+        let language = escape_xml(self.language.as_deref().unwrap_or_default());
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <tt xmlns=\"http://www.w3.org/ns/ttml\" xml:lang=\"{language}\">\n  <body>\n    <div>\n{paragraphs}    </div>\n  </body>\n</tt>\n"
+        )
+    }
+
+    pub fn as_sbv(&self) -> String {
+        self.segments
+            .iter()
+            .map(|segment| {
+                format!(
+                    "{},{}\n{}\n",
+                    sbv_timestamp(segment.start),
+                    sbv_timestamp(segment.stop),
+                    segment.with_translation(segment.text.trim().to_string())
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Paragraph per segment with its start time. A heading whenever the speaker changes
+    pub fn as_markdown(&self) -> String {
+        self.markdown(&SpeakerLabels::default())
+    }
+
+    fn markdown(&self, speakers: &SpeakerLabels) -> String {
+        let mut markdown = String::new();
+        let mut current_speaker = None;
+        for segment in &self.segments {
+            let label = speakers.label(segment);
+            if label.is_some() && label != current_speaker {
+                markdown += &format!("## {}\n\n", label.as_deref().unwrap_or("?"));
+                current_speaker = label;
+            }
+            markdown += &format!(
+                "**[{}]** {}\n\n",
+                segment.start.format(true, ".").split('.').next().unwrap_or_default(),
+                segment.text.trim()
+            );
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Overlap;
+
+    fn transcript() -> Transcript {
+        let segment = |start, stop, text: &str, speaker: Option<&str>| Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            speaker: speaker.map(Into::into),
+            ..Default::default()
+        };
+        Transcript {
+            processing_time_sec: 0,
+            segments: vec![
+                segment(150, 320, " Hello, \"world\"", Some("0")),
+                segment(320, 370_050, " Fish & <chips>", Some("1")),
+            ],
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        for format in TranscriptFormat::ALL {
+            assert_eq!(format.as_str().parse::<TranscriptFormat>().unwrap(), *format);
+        }
+        assert_eq!("DFXP".parse::<TranscriptFormat>().unwrap(), TranscriptFormat::Ttml);
+        assert!("doc".parse::<TranscriptFormat>().is_err());
+    }
+
+    #[test]
+    fn test_tsv_and_csv() {
+        let transcript = transcript();
+        assert_eq!(
+            transcript.as_tsv(),
+            "start\tend\tspeaker\ttext\n1500\t3200\t0\tHello, \"world\"\n3200\t3700500\t1\tFish & <chips>\n"
+        );
+        assert_eq!(
+            transcript.as_csv(),
+            "start,end,speaker,text\n1500,3200,0,\"Hello, \"\"world\"\"\"\n3200,3700500,1,Fish & <chips>\n"
+        );
+    }
+
+    #[test]
+    fn test_lrc_sbv_ttml() {
+        let transcript = transcript();
+        assert_eq!(transcript.as_lrc(), "[00:01.50]Hello, \"world\"\n[00:03.20]Fish & <chips>\n");
+        assert_eq!(
+            transcript.as_sbv(),
+            "0:00:01.500,0:00:03.200\nHello, \"world\"\n\n0:00:03.200,1:01:40.500\nFish & <chips>\n"
+        );
+        let ttml = transcript.as_ttml();
+        assert!(ttml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tt xmlns=\"http://www.w3.org/ns/ttml\" xml:lang=\"\""));
+        assert!(ttml.contains("<p begin=\"00:00:03.200\" end=\"01:01:40.500\">Fish &amp; &lt;chips&gt;</p>\n"));
+        assert!(ttml.ends_with("</div>\n  </body>\n</tt>\n"));
+        let ttml = Transcript {
+            language: Some("he".into()),
+            ..transcript
+        }
+        .as_ttml();
+        assert!(ttml.contains("<tt xmlns=\"http://www.w3.org/ns/ttml\" xml:lang=\"he\">\n"));
+    }
+
+    #[test]
+    fn test_markdown() {
+        let mut transcript = transcript();
+        transcript.segments[1].speaker = Some("0".into());
+        assert_eq!(
+            transcript.as_markdown(),
+            "## Speaker 1\n\n**[00:00:01]** Hello, \"world\"\n\n**[00:00:03]** Fish & <chips>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_speakers() {
+        let mut transcript = transcript();
+        transcript.segments[1].overlap = Some(Overlap {
+            start: Timestamp::from_centis(400),
+            stop: Timestamp::from_centis(600),
+            speakers: vec!["1".into(), "0".into()],
+        });
+        assert!(transcript.as_tsv().ends_with("\t1+0\tFish & <chips>\n"));
+        assert!(transcript
+            .as_srt()
+            .ends_with("00:00:03,200 --> 01:01:40,500\nSpeaker 2 & Speaker 1: Fish & <chips>\n"));
+        assert!(transcript.as_markdown().contains("## Speaker 2 & Speaker 1\n\n**[00:00:03]** Fish & <chips>"));
+    }
+}
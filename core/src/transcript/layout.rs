@@ -0,0 +1,360 @@
+use super::{Segment, Timestamp, Transcript, Word};
+use serde::{Deserialize, Serialize};
+
+/// Subtitle rules applied by `Transcript::layout`. Defaults follow the Netflix style guide
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct SubtitleLayout {
+    pub max_chars_per_line: usize,
+    pub max_lines: usize,
+    /// Seconds
+    pub min_duration: f32,
+    /// Seconds
+    pub max_duration: f32,
+    /// Reading speed. Cues too fast to read before the next one starts are split earlier,
+    /// and short cues are extended into the following gap to keep it
+    pub max_chars_per_second: f32,
+}
+
+impl Default for SubtitleLayout {
+    fn default() -> Self {
+        Self {
+            max_chars_per_line: 42,
+            max_lines: 2,
+            min_duration: 5.0 / 6.0,
+            max_duration: 7.0,
+            max_chars_per_second: 20.0,
+        }
+    }
+}
+
+/// Break after these when a cue has to be split
+const BREAK_AFTER: &[char] = &['.', '?', '!', ',', ';', ':'];
+
+fn len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Greedy line filling. Words longer than `width` get a line of their own
+fn fill(words: &[&str], width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if len(line) + 1 + len(word) <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// Wrap words in at most `max_lines` lines of `width` chars, as balanced as possible.
+/// None if they don't fit
+fn wrap(words: &[&str], width: usize, max_lines: usize) -> Option<Vec<String>> {
+    let lines = fill(words, width);
+    if lines.len() > max_lines || lines.iter().any(|line| len(line) > width) {
+        return None;
+    }
+    // The narrowest width with the same number of lines balances them
+    let mut best = lines;
+    for narrower in (1..width).rev() {
+        let candidate = fill(words, narrower);
+        if candidate.len() > best.len() || candidate.iter().any(|line| len(line) > narrower) {
+            break;
+        }
+        best = candidate;
+    }
+    Some(best)
+}
+
+/// Words of a segment. Without word timestamps the segment time is spread by word length
+pub(super) fn timed_words(segment: &Segment) -> Vec<Word> {
+    if !segment.words.is_empty() {
+        return segment
+            .words
+            .iter()
+            .filter(|word| !word.text.trim().is_empty())
+            .map(|word| Word {
+                text: word.text.trim().to_string(),
+                ..word.clone()
+            })
+            .collect();
+    }
+    let texts: Vec<&str> = segment.text.split_whitespace().collect();
+    let total: usize = texts.iter().map(|text| len(text) + 1).sum();
+    let duration = (segment.stop - segment.start).as_millis();
+    let at = |chars: usize| segment.start + Timestamp::from_millis(duration * chars as i64 / total.max(1) as i64);
+    let mut chars = 0;
+    texts
+        .iter()
+        .map(|text| {
+            let start = at(chars);
+            chars += len(text) + 1;
+            let stop = at(chars);
+            Word {
+                text: text.to_string(),
+                start,
+                stop,
+                probability: 1.0,
+            }
+        })
+        .collect()
+}
+
+impl SubtitleLayout {
+    fn fits(&self, words: &[Word]) -> bool {
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        wrap(&texts, self.max_chars_per_line, self.max_lines).is_some()
+    }
+
+    /// Time to read `chars` characters at `max_chars_per_second`
+    fn reading_time(&self, chars: usize) -> Timestamp {
+        Timestamp::from_millis((chars as f32 / self.max_chars_per_second.max(1.0) * 1000.0).ceil() as i64)
+    }
+
+    /// Whether the cue of `words` can be read before `until`, when the next cue starts
+    fn readable(&self, words: &[Word], until: Timestamp) -> bool {
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        words.first().is_none_or(|first| first.start + self.reading_time(len(&texts.join(" "))) <= until)
+    }
+
+    fn cue(&self, segment: &Segment, words: &[Word], keep_words: bool) -> Segment {
+        let texts: Vec<&str> = words.iter().map(|word| word.text.as_str()).collect();
+        let lines = wrap(&texts, self.max_chars_per_line, self.max_lines).unwrap_or_else(|| fill(&texts, self.max_chars_per_line));
+        Segment {
+            start: words.first().map_or(segment.start, |word| word.start),
+            stop: words.last().map_or(segment.stop, |word| word.stop),
+            text: lines.join("\n"),
+            words: if keep_words { words.to_vec() } else { Vec::new() },
+            ..segment.clone()
+        }
+    }
+
+    /// Split a segment into cues that fit the line and duration limits
+    fn split(&self, segment: &Segment) -> Vec<Segment> {
+        let words = timed_words(segment);
+        let keep_words = !segment.words.is_empty();
+        let max_duration = Timestamp::from_secs_f64(self.max_duration as f64);
+        let mut cues = Vec::new();
+        let mut current: Vec<Word> = Vec::new();
+        for word in words {
+            if let Some(first) = current.first() {
+                let too_long = word.stop - first.start > max_duration;
+                let mut candidate = current.clone();
+                candidate.push(word.clone());
+                if too_long || !self.fits(&candidate) {
+                    let rest_fits = |split: usize| {
+                        let mut rest = current[split..].to_vec();
+                        rest.push(word.clone());
+                        self.fits(&rest) && word.stop - rest[0].start <= max_duration
+                    };
+                    let next_start = |split: usize| current.get(split).map_or(word.start, |next| next.start);
+                    // Prefer breaking at punctuation in the second half of the cue
+                    let split = current
+                        .iter()
+                        .rposition(|word| word.text.ends_with(BREAK_AFTER))
+                        .map(|index| index + 1)
+                        .filter(|split| *split < current.len() && split * 2 >= current.len())
+                        .filter(|split| rest_fits(*split))
+                        .unwrap_or(current.len());
+                    // A cue too fast to read before the next one starts leaves its last words to the next one
+                    let split = if self.readable(&current[..split], next_start(split)) {
+                        split
+                    } else {
+                        (1..split)
+                            .rev()
+                            .find(|split| self.readable(&current[..*split], next_start(*split)) && rest_fits(*split))
+                            .unwrap_or(split)
+                    };
+                    let rest = current.split_off(split);
+                    cues.push(self.cue(segment, &current, keep_words));
+                    current = rest;
+                }
+            }
+            current.push(word);
+        }
+        if !current.is_empty() {
+            cues.push(self.cue(segment, &current, keep_words));
+        }
+        if let Some(ref translation) = segment.translation {
+            self.split_translation(translation, &mut cues);
+        }
+        cues
+    }
+
+    /// Spread the translation of a segment over its cues by their share of the text, wrapped like the text
+    fn split_translation(&self, translation: &str, cues: &mut [Segment]) {
+        let words: Vec<&str> = translation.split_whitespace().collect();
+        let lengths: Vec<usize> = cues.iter().map(|cue| len(&cue.text)).collect();
+        let total = lengths.iter().sum::<usize>().max(1);
+        let (mut end, mut from) = (0, 0);
+        for (cue, length) in cues.iter_mut().zip(lengths) {
+            end += length;
+            let to = ((words.len() * end + total / 2) / total).min(words.len());
+            cue.translation = (to > from).then(|| fill(&words[from..to], self.max_chars_per_line).join("\n"));
+            from = to;
+        }
+    }
+
+    /// Extend cues that are too short to read, without running into the next cue
+    fn retime(&self, cues: &mut [Segment]) {
+        let min_duration = Timestamp::from_secs_f64(self.min_duration as f64);
+        let max_duration = Timestamp::from_secs_f64(self.max_duration as f64);
+        for i in 0..cues.len() {
+            let next_start = cues.get(i + 1).map(|next| next.start);
+            let cue = &mut cues[i];
+            let reading = self.reading_time(cue.text.chars().filter(|c| *c != '\n').count());
+            let wanted = (cue.start + reading.max(min_duration).min(max_duration)).max(cue.stop);
+            cue.stop = match next_start {
+                Some(next_start) => wanted.min(next_start).max(cue.stop),
+                None => wanted,
+            };
+        }
+    }
+}
+
+impl Transcript {
+    /// Split and reflow segments into subtitle cues following `layout`.
+    /// Uses word timestamps when available, otherwise the time of a segment is spread by word length
+    pub fn layout(&self, layout: &SubtitleLayout) -> Transcript {
+        let mut segments: Vec<Segment> = self.segments.iter().flat_map(|segment| layout.split(segment)).collect();
+        layout.retime(&mut segments);
+        Transcript {
+            processing_time_sec: self.processing_time_sec,
+            segments,
+            language: self.language.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(segments: Vec<Segment>) -> Transcript {
+        Transcript {
+            processing_time_sec: 0,
+            segments,
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_wrap_balances_lines() {
+        let words = ["one", "two", "three", "four", "five", "six"];
+        assert_eq!(wrap(&words, 20, 2).unwrap(), vec!["one two three", "four five six"]);
+        assert_eq!(wrap(&words, 40, 2).unwrap(), vec!["one two three four five six"]);
+        assert!(wrap(&words, 8, 2).is_none());
+    }
+
+    #[test]
+    fn test_layout_splits_long_segments() {
+        let text = " This is a very long segment that whisper produced without any care for subtitles, and it just keeps going on and on until nobody can read it anymore.";
+        let segment = Segment {
+            start: Timestamp::ZERO,
+            stop: Timestamp::from_centis(1000),
+            text: text.into(),
+            speaker: Some("1".into()),
+            ..Default::default()
+        };
+        let layout = SubtitleLayout::default();
+        let laid_out = transcript(vec![segment]).layout(&layout);
+        assert!(laid_out.segments.len() > 1);
+        for cue in &laid_out.segments {
+            let lines: Vec<&str> = cue.text.lines().collect();
+            assert!(lines.len() <= 2, "{:?}", cue.text);
+            assert!(lines.iter().all(|line| len(line) <= 42), "{:?}", cue.text);
+            assert!(cue.stop - cue.start <= Timestamp::from_secs_f64(7.0));
+            assert_eq!(cue.speaker.as_deref(), Some("1"));
+        }
+        // Split after the comma
+        assert!(laid_out.segments[0].text.ends_with("subtitles,"), "{:?}", laid_out.segments[0].text);
+        let words: Vec<String> = laid_out.segments.iter().map(|cue| cue.text.replace('\n', " ")).collect();
+        assert_eq!(words.join(" "), text.trim());
+        assert_eq!(laid_out.segments.last().unwrap().stop, Timestamp::from_centis(1000));
+    }
+
+    #[test]
+    fn test_layout_uses_word_timings() {
+        let word = |text: &str, start, stop| Word {
+            text: text.into(),
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            probability: 1.0,
+        };
+        let segment = Segment {
+            start: Timestamp::ZERO,
+            stop: Timestamp::from_centis(1200),
+            text: " Hello there. Long pause".into(),
+            words: vec![word("Hello", 0, 50), word("there.", 50, 100), word("Long", 1000, 1100), word("pause", 1100, 1200)],
+            ..Default::default()
+        };
+        let laid_out = transcript(vec![segment]).layout(&SubtitleLayout::default());
+        assert_eq!(laid_out.segments.len(), 2);
+        assert_eq!(laid_out.segments[0].text, "Hello there.");
+        assert_eq!(laid_out.segments[0].start, Timestamp::ZERO);
+        assert_eq!(laid_out.segments[0].words.len(), 2);
+        assert_eq!(laid_out.segments[1].start, Timestamp::from_centis(1000));
+        assert_eq!(laid_out.segments[1].stop, Timestamp::from_centis(1200));
+    }
+
+    #[test]
+    fn test_layout_extends_short_cues() {
+        let segments = vec![
+            Segment {
+                start: Timestamp::ZERO,
+                stop: Timestamp::from_centis(20),
+                text: " A sentence that needs time to be read".into(),
+                ..Default::default()
+            },
+            Segment {
+                start: Timestamp::from_centis(100),
+                stop: Timestamp::from_centis(150),
+                text: " Hi".into(),
+                ..Default::default()
+            },
+        ];
+        let laid_out = transcript(segments).layout(&SubtitleLayout::default());
+        // Extended up to the next cue
+        assert_eq!(laid_out.segments[0].stop, Timestamp::from_centis(100));
+        // Extended to the minimum duration
+        assert_eq!(laid_out.segments[1].stop, Timestamp::from_millis(1833));
+    }
+
+    #[test]
+    fn test_layout_keeps_reading_speed() {
+        let word = |text: &str, start, stop| Word {
+            text: text.into(),
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            probability: 1.0,
+        };
+        let segment = Segment {
+            start: Timestamp::ZERO,
+            stop: Timestamp::from_centis(400),
+            text: " Unbelievably fast-talking speaker".into(),
+            words: vec![word("Unbelievably", 0, 100), word("fast-talking", 100, 120), word("speaker", 120, 400)],
+            ..Default::default()
+        };
+        let layout = SubtitleLayout {
+            max_chars_per_line: 25,
+            max_lines: 1,
+            max_chars_per_second: 15.0,
+            ..Default::default()
+        };
+        let laid_out = transcript(vec![segment]).layout(&layout);
+        let texts: Vec<&str> = laid_out.segments.iter().map(|cue| cue.text.as_str()).collect();
+        // "Unbelievably fast-talking" fits the line but can't be read in 1.2 seconds
+        assert_eq!(texts, vec!["Unbelievably", "fast-talking speaker"]);
+        for (cue, next) in laid_out.segments.iter().zip(&laid_out.segments[1..]) {
+            assert!(cue.stop <= next.start);
+        }
+        for cue in &laid_out.segments {
+            let cps = len(&cue.text) as f64 / (cue.stop - cue.start).as_secs_f64();
+            assert!(cps <= 15.0, "{:?} at {} chars per second", cue.text, cps);
+        }
+    }
+}
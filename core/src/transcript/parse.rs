@@ -0,0 +1,247 @@
+use super::format::TranscriptFormat;
+use super::{Segment, Timestamp, Transcript};
+use eyre::{bail, Context, ContextCompat, Result};
+use std::path::Path;
+
+/// Parse `HH:MM:SS,mmm`, `MM:SS.mmm` or `HH:MM:SS.mmm`
+fn parse_timestamp(timestamp: &str) -> Result<Timestamp> {
+    let timestamp = timestamp.trim();
+    let (time, fraction) = timestamp
+        .rsplit_once([',', '.'])
+        .with_context(|| format!("missing milliseconds in timestamp {}", timestamp))?;
+    let mut seconds = 0;
+    for part in time.split(':') {
+        let value: i64 = part.parse().with_context(|| format!("invalid timestamp {}", timestamp))?;
+        seconds = seconds * 60 + value;
+    }
+    if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        bail!("invalid timestamp {}", timestamp)
+    }
+    // Milliseconds usually, but be lenient with the number of digits
+    let milliseconds: i64 = format!("{:0<3}", fraction)[..3].parse()?;
+    Ok(Timestamp::from_millis(seconds * 1000 + milliseconds))
+}
+
+/// Parse `start --> end [cue settings]`
+fn parse_timing(line: &str) -> Result<(Timestamp, Timestamp)> {
+    let (start, rest) = line.split_once("-->").context("missing -->")?;
+    let end = rest.split_whitespace().next().context("missing end timestamp")?;
+    Ok((parse_timestamp(start)?, parse_timestamp(end)?))
+}
+
+/// Blocks of lines separated by blank lines
+fn blocks(content: &str) -> Vec<Vec<&str>> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut blocks = Vec::new();
+    let mut block = Vec::new();
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+        } else {
+            block.push(line);
+        }
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+    blocks
+}
+
+fn unescape_vtt(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&")
+}
+
+/// Remove WebVTT tags such as `<00:01.600>`, `<c.yellow>` and `<b>`, and take the speaker of `<v Name>`
+fn parse_vtt_text(text: &str) -> (String, Option<String>) {
+    let mut result = String::new();
+    let mut speaker = None;
+    let mut rest = text;
+    while let Some(open) = rest.find('<') {
+        result.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('>') else {
+            result.push_str(&rest[open..]);
+            rest = "";
+            break;
+        };
+        let tag = &rest[open + 1..open + close];
+        // <v Name> or <v.class Name>
+        if let Some(voice) = tag.strip_prefix('v').filter(|voice| voice.starts_with([' ', '.'])) {
+            if let Some((_, name)) = voice.split_once(' ') {
+                speaker.get_or_insert_with(|| unescape_vtt(name.trim()));
+            }
+        }
+        rest = &rest[open + close + 1..];
+    }
+    result.push_str(rest);
+    (unescape_vtt(&result), speaker)
+}
+
+impl Transcript {
+    /// Parse SubRip subtitles
+    pub fn from_srt(content: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        for block in blocks(content) {
+            // The index line is optional in practice
+            let Some(timing_index) = block.iter().position(|line| line.contains("-->")) else {
+                tracing::debug!("skip srt block without timing: {:?}", block);
+                continue;
+            };
+            let (start, stop) = parse_timing(block[timing_index]).with_context(|| format!("invalid srt cue {:?}", block))?;
+            segments.push(Segment {
+                start,
+                stop,
+                text: block[timing_index + 1..].join("\n"),
+                ..Default::default()
+            });
+        }
+        Ok(Self {
+            processing_time_sec: 0,
+            segments,
+            language: None,
+        })
+    }
+
+    /// Parse WebVTT subtitles. Voice tags become speakers
+    pub fn from_vtt(content: &str) -> Result<Self> {
+        let blocks = blocks(content);
+        let Some(header) = blocks.first() else {
+            bail!("empty vtt")
+        };
+        if !header[0].starts_with("WEBVTT") {
+            bail!("missing WEBVTT header")
+        }
+        let mut segments = Vec::new();
+        for block in &blocks[1..] {
+            if ["NOTE", "STYLE", "REGION"].iter().any(|name| block[0].starts_with(name)) {
+                continue;
+            }
+            // Optional cue identifier before the timing
+            let Some(timing_index) = block.iter().position(|line| line.contains("-->")) else {
+                continue;
+            };
+            let (start, stop) = parse_timing(block[timing_index]).with_context(|| format!("invalid vtt cue {:?}", block))?;
+            let (text, speaker) = parse_vtt_text(&block[timing_index + 1..].join("\n"));
+            segments.push(Segment {
+                start,
+                stop,
+                text,
+                speaker,
+                ..Default::default()
+            });
+        }
+        Ok(Self {
+            processing_time_sec: 0,
+            segments,
+            language: None,
+        })
+    }
+
+    /// Parse a transcript saved by `as_json`, or the plain segments array the desktop app exports
+    pub fn from_json(content: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content).context("invalid json")?;
+        if value.is_array() {
+            let segments: Vec<Segment> = serde_json::from_value(value).context("invalid segments")?;
+            return Ok(Self {
+                processing_time_sec: 0,
+                segments,
+                language: None,
+            });
+        }
+        serde_json::from_value(value).context("invalid transcript")
+    }
+
+    pub fn parse(content: &str, format: TranscriptFormat) -> Result<Self> {
+        match format {
+            TranscriptFormat::Srt => Self::from_srt(content),
+            TranscriptFormat::Vtt => Self::from_vtt(content),
+            TranscriptFormat::Json => Self::from_json(content),
+            _ => bail!("parsing {} is not supported", format),
+        }
+    }
+
+    /// Load a transcript file, with the format taken from the extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let format: TranscriptFormat = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .with_context(|| format!("missing extension in {}", path.display()))?
+            .parse()?;
+        let content = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&content, format).with_context(|| format!("failed to parse {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:00:01,500").unwrap(), Timestamp::from_millis(1500));
+        assert_eq!(parse_timestamp("01:02:03.040").unwrap(), Timestamp::from_millis(3_723_040));
+        assert_eq!(parse_timestamp("02:03.046").unwrap(), Timestamp::from_millis(123_046));
+        assert_eq!(parse_timestamp("00:00:01.5").unwrap(), Timestamp::from_millis(1500));
+        assert!(parse_timestamp("00:00:01").is_err());
+        assert!(parse_timestamp("aa:00:01,000").is_err());
+    }
+
+    #[test]
+    fn test_parse_srt() {
+        let content = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nworld\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nAgain\r\n";
+        let transcript = Transcript::from_srt(content).unwrap();
+        assert_eq!(transcript.segments.len(), 2);
+        assert_eq!(transcript.segments[0].start, Timestamp::from_centis(100));
+        assert_eq!(transcript.segments[0].stop, Timestamp::from_centis(250));
+        assert_eq!(transcript.segments[0].text, "Hello\nworld");
+        assert_eq!(transcript.segments[1].text, "Again");
+
+        // Written by as_srt and read back
+        let roundtrip = Transcript::from_srt(&transcript.as_srt()).unwrap();
+        assert_eq!(roundtrip.segments.len(), 2);
+        assert_eq!(roundtrip.segments[1].start, Timestamp::from_centis(300));
+    }
+
+    #[test]
+    fn test_parse_vtt() {
+        let content = "WEBVTT - title\n\nNOTE a comment\n\nSTYLE\n::cue { color: red }\n\ncue-1\n00:01.000 --> 00:02.500 align:start\n<v Alice>Hello <00:01.600><c>world</c>\n\n00:00:03.000 --> 00:00:04.000\nFish &amp; chips\n";
+        let transcript = Transcript::from_vtt(content).unwrap();
+        assert_eq!(transcript.segments.len(), 2);
+        assert_eq!(transcript.segments[0].start, Timestamp::from_centis(100));
+        assert_eq!(transcript.segments[0].stop, Timestamp::from_centis(250));
+        assert_eq!(transcript.segments[0].text, "Hello world");
+        assert_eq!(transcript.segments[0].speaker.as_deref(), Some("Alice"));
+        assert_eq!(transcript.segments[1].text, "Fish & chips");
+        assert_eq!(transcript.segments[1].speaker, None);
+
+        assert!(Transcript::from_vtt("1\n00:01.000 --> 00:02.000\nhi").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let transcript = Transcript {
+            processing_time_sec: 3,
+            segments: vec![Segment {
+                start: Timestamp::ZERO,
+                stop: Timestamp::from_centis(100),
+                text: " hi".into(),
+                speaker: Some("0".into()),
+                ..Default::default()
+            }],
+            language: None,
+        };
+        let parsed = Transcript::from_json(&transcript.as_json().unwrap()).unwrap();
+        assert_eq!(parsed.processing_time_sec, 3);
+        assert_eq!(parsed.segments[0].text, " hi");
+
+        let parsed = Transcript::from_json(r#"[{"start": 0, "stop": 100, "text": "hi", "speaker": "1"}]"#).unwrap();
+        assert_eq!(parsed.segments[0].speaker.as_deref(), Some("1"));
+
+        assert!(Transcript::parse("", TranscriptFormat::Lrc).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use super::Segment;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How diarized speakers are labelled in text and subtitle output
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct SpeakerLabels {
+    /// Numbered speakers without a name are labelled "{prefix} {id + 1}", so speaker "0" is "Speaker 1"
+    pub prefix: String,
+    /// Real names by speaker id, e.g. {"0": "Alice"}
+    pub names: HashMap<String, String>,
+    /// Label only the first segment of every speaker turn
+    pub on_change_only: bool,
+}
+
+impl Default for SpeakerLabels {
+    fn default() -> Self {
+        Self {
+            prefix: "Speaker".into(),
+            names: HashMap::new(),
+            on_change_only: false,
+        }
+    }
+}
+
+impl SpeakerLabels {
+    pub fn name(&self, speaker: &str) -> String {
+        match self.names.get(speaker) {
+            Some(name) => name.clone(),
+            None => match speaker.parse::<u32>() {
+                Ok(id) => format!("{} {}", self.prefix, id + 1).trim().to_string(),
+                // Enrolled speakers are already named
+                Err(_) => speaker.to_string(),
+            },
+        }
+    }
+
+    /// Label of a segment, naming every speaker of overlapping speech, e.g. "Speaker 1 & Alice"
+    pub fn label(&self, segment: &Segment) -> Option<String> {
+        match segment.overlap {
+            Some(ref overlap) if overlap.speakers.len() > 1 => {
+                let names: Vec<String> = overlap.speakers.iter().map(|speaker| self.name(speaker)).collect();
+                Some(names.join(" & "))
+            }
+            _ => segment.speaker.as_deref().map(|speaker| self.name(speaker)),
+        }
+    }
+
+    /// Label of every segment. None for segments without a speaker,
+    /// and with `on_change_only` for segments continuing the turn of the previous one
+    pub fn labels(&self, segments: &[Segment]) -> Vec<Option<String>> {
+        let mut previous = None;
+        segments
+            .iter()
+            .map(|segment| {
+                let label = self.label(segment);
+                let changed = label != previous;
+                previous = label.clone();
+                label.filter(|_| changed || !self.on_change_only)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::Overlap;
+
+    #[test]
+    fn test_labels() {
+        let segment = |speaker: Option<&str>| Segment {
+            speaker: speaker.map(Into::into),
+            ..Default::default()
+        };
+        let segments = vec![segment(Some("0")), segment(Some("0")), segment(Some("1")), segment(None), segment(Some("1"))];
+        let mut speakers = SpeakerLabels {
+            names: HashMap::from([("1".into(), "Alice".into())]),
+            ..Default::default()
+        };
+        let labels = |speakers: &SpeakerLabels| speakers.labels(&segments);
+        assert_eq!(
+            labels(&speakers),
+            vec![Some("Speaker 1".into()), Some("Speaker 1".into()), Some("Alice".into()), None, Some("Alice".into())]
+        );
+        speakers.on_change_only = true;
+        assert_eq!(labels(&speakers), vec![Some("Speaker 1".into()), None, Some("Alice".into()), None, Some("Alice".into())]);
+        assert_eq!(speakers.name("Bob"), "Bob");
+
+        // Overlapping speech names every speaker, and ends the turn
+        let mut segments = vec![segment(Some("0")), segment(Some("0")), segment(Some("0"))];
+        segments[1].overlap = Some(Overlap {
+            start: Default::default(),
+            stop: Default::default(),
+            speakers: vec!["0".into(), "1".into()],
+        });
+        assert_eq!(
+            speakers.labels(&segments),
+            vec![Some("Speaker 1".into()), Some("Speaker 1 & Alice".into()), Some("Speaker 1".into())]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::{Add, Sub};
+use std::time::Duration;
+
+/// A point on the timeline of a transcript, in milliseconds.
+/// Serialized as centiseconds, the unit of whisper and of transcripts saved by older versions.
+/// Timestamps between centiseconds are written with a fraction, so that no precision is lost
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp(0);
+
+    pub const fn from_millis(milliseconds: i64) -> Self {
+        Self(milliseconds)
+    }
+
+    /// Whisper timestamps are centiseconds
+    pub const fn from_centis(centiseconds: i64) -> Self {
+        Self(centiseconds * 10)
+    }
+
+    /// Rounded to the nearest millisecond
+    pub fn from_secs_f64(seconds: f64) -> Self {
+        Self((seconds * 1000.0).round() as i64)
+    }
+
+    pub fn from_samples(samples: usize, sample_rate: usize) -> Self {
+        Self((samples as u64 * 1000 / sample_rate as u64) as i64)
+    }
+
+    pub const fn as_millis(self) -> i64 {
+        self.0
+    }
+
+    /// Rounded to the nearest centisecond
+    pub const fn as_centis(self) -> i64 {
+        (self.0 + 5).div_euclid(10)
+    }
+
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+
+    /// Sample index in audio of `sample_rate`. Zero for negative timestamps
+    pub fn as_samples(self, sample_rate: usize) -> usize {
+        (self.0.max(0) as u64 * sample_rate as u64 / 1000) as usize
+    }
+
+    /// Zero for negative timestamps
+    pub fn as_duration(self) -> Duration {
+        Duration::from_millis(self.0.max(0) as u64)
+    }
+
+    /// `[hh:]mm:ss<marker>mmm`. Negative timestamps, such as after shifting a transcript, are written as zero
+    pub fn format(self, always_include_hours: bool, decimal_marker: &str) -> String {
+        let milliseconds = self.0.max(0);
+        let hours = milliseconds / 3_600_000;
+        let minutes = milliseconds / 60_000 % 60;
+        let seconds = milliseconds / 1000 % 60;
+        let milliseconds = milliseconds % 1000;
+        let hours_marker = if always_include_hours || hours != 0 {
+            format!("{:02}:", hours)
+        } else {
+            String::new()
+        };
+        format!("{hours_marker}{minutes:02}:{seconds:02}{decimal_marker}{milliseconds:03}")
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(true, "."))
+    }
+}
+
+impl From<Duration> for Timestamp {
+    fn from(duration: Duration) -> Self {
+        Self(duration.as_millis() as i64)
+    }
+}
+
+impl Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, other: Timestamp) -> Timestamp {
+        Timestamp(self.0 + other.0)
+    }
+}
+
+impl Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, other: Timestamp) -> Timestamp {
+        Timestamp(self.0 - other.0)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 % 10 == 0 {
+            serializer.serialize_i64(self.0 / 10)
+        } else {
+            serializer.serialize_f64(self.0 as f64 / 10.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Centiseconds {
+            Integer(i64),
+            Float(f64),
+        }
+        Ok(match Centiseconds::deserialize(deserializer)? {
+            Centiseconds::Integer(centiseconds) => Self::from_centis(centiseconds),
+            Centiseconds::Float(centiseconds) => Self::from_secs_f64(centiseconds / 100.0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Timestamp::from_centis(150).format(false, "."), "00:01.500");
+        assert_eq!(Timestamp::from_millis(3_723_004).format(true, ","), "01:02:03,004");
+        assert_eq!(Timestamp::from_secs_f64(1.2345).format(true, "."), "00:00:01.235");
+        // Used to panic
+        assert_eq!(Timestamp::from_millis(-20).format(false, "."), "00:00.000");
+    }
+
+    #[test]
+    fn test_conversions() {
+        // Diarized segments used to lose everything after the second
+        assert_eq!(Timestamp::from_secs_f64(2.75).as_centis(), 275);
+        assert_eq!(Timestamp::from_samples(24_000, 16_000), Timestamp::from_millis(1500));
+        assert_eq!(Timestamp::from_millis(1500).as_samples(16_000), 24_000);
+        assert_eq!(Timestamp::from_millis(-5).as_samples(16_000), 0);
+        assert_eq!(Timestamp::from(Duration::from_millis(1250)).as_duration(), Duration::from_millis(1250));
+        assert_eq!(Timestamp::from_millis(1004) - Timestamp::from_millis(4), Timestamp::from_centis(100));
+    }
+
+    #[test]
+    fn test_serde_centiseconds() {
+        // Whole centiseconds stay integers for older readers
+        assert_eq!(serde_json::to_string(&Timestamp::from_millis(1230)).unwrap(), "123");
+        assert_eq!(serde_json::to_string(&Timestamp::from_millis(1234)).unwrap(), "123.4");
+        for millis in [1234, 1, -5, 3_723_007] {
+            let timestamp = Timestamp::from_millis(millis);
+            let json = serde_json::to_string(&timestamp).unwrap();
+            assert_eq!(serde_json::from_str::<Timestamp>(&json).unwrap(), timestamp);
+        }
+        assert_eq!(serde_json::from_str::<Timestamp>("150").unwrap(), Timestamp::from_millis(1500));
+        assert_eq!(serde_json::from_str::<Timestamp>("150.5").unwrap(), Timestamp::from_millis(1505));
+        assert!(serde_json::from_str::<Timestamp>("\"1s\"").is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::transcript::{Segment, Transcript};
+use eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+pub mod http;
+pub mod local;
+
+/// Translates texts to another language
+pub trait Translator {
+    /// Translations in the order of `texts`. `source` is None when the language isn't known
+    fn translate(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>>;
+}
+
+/// What a translated transcript shows
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum TranslationMode {
+    /// The translation replaces the text
+    #[default]
+    Translated,
+    /// The translation is kept in `Segment.translation` and shown under the text in subtitles
+    Bilingual,
+}
+
+/// Translation backends
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum TranslatorOptions {
+    /// Offline translation program. See `local::LocalTranslator`
+    Local {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        model_path: Option<String>,
+    },
+    /// Chat completions of OpenAI or a compatible server such as Ollama, e.g. http://localhost:11434/v1
+    #[serde(rename = "openai")]
+    OpenAi {
+        url: String,
+        model: String,
+        api_key: Option<String>,
+    },
+    /// LibreTranslate server, e.g. http://localhost:5000
+    #[serde(rename = "libretranslate")]
+    LibreTranslate { url: String, api_key: Option<String> },
+}
+
+impl TranslatorOptions {
+    pub fn translator(&self) -> Box<dyn Translator> {
+        match self.clone() {
+            TranslatorOptions::Local {
+                command,
+                args,
+                model_path,
+            } => Box::new(local::LocalTranslator {
+                command,
+                args,
+                model_path,
+            }),
+            TranslatorOptions::OpenAi { url, model, api_key } => Box::new(http::OpenAiTranslator { url, model, api_key }),
+            TranslatorOptions::LibreTranslate { url, api_key } => Box::new(http::LibreTranslator { url, api_key }),
+        }
+    }
+}
+
+/// Translation applied after transcribing
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct TranslateOptions {
+    /// Language code to translate to
+    pub target: String,
+    /// Language code of segments without a detected language. Backends guess it when None
+    pub source: Option<String>,
+    #[serde(default)]
+    pub mode: TranslationMode,
+    pub backend: TranslatorOptions,
+}
+
+impl Transcript {
+    /// Translate with the backend of `options`
+    pub fn translate(&self, options: &TranslateOptions) -> Result<Transcript> {
+        let translator = options.backend.translator();
+        self.translate_with(translator.as_ref(), options.source.as_deref(), &options.target, options.mode)
+    }
+
+    /// Translate every segment with `translator`. Segments are sent together by language:
+    /// the one detected for the segment, then the transcript language, then `source`.
+    /// Segments already in the target language are kept as they are
+    pub fn translate_with(
+        &self,
+        translator: &dyn Translator,
+        source: Option<&str>,
+        target: &str,
+        mode: TranslationMode,
+    ) -> Result<Transcript> {
+        let source_of = |i: usize| self.segments[i].language.as_deref().or(self.language.as_deref()).or(source);
+        let mut sources: Vec<Option<&str>> = Vec::new();
+        for i in 0..self.segments.len() {
+            if !sources.contains(&source_of(i)) {
+                sources.push(source_of(i));
+            }
+        }
+
+        let mut translations: Vec<Option<String>> = vec![None; self.segments.len()];
+        for source in sources.into_iter().filter(|source| *source != Some(target)) {
+            let indices: Vec<usize> = (0..self.segments.len()).filter(|i| source_of(*i) == source).collect();
+            // Programs and models translate line by line
+            let texts: Vec<String> = indices
+                .iter()
+                .map(|i| self.segments[*i].text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect();
+            tracing::debug!("translate {} segments from {:?} to {}", texts.len(), source, target);
+            let translated = translator.translate(&texts, source, target)?;
+            if translated.len() != texts.len() {
+                bail!("got {} translations for {} segments", translated.len(), texts.len())
+            }
+            for (i, translation) in indices.into_iter().zip(translated) {
+                translations[i] = Some(translation.trim().to_string());
+            }
+        }
+
+        let segments = self
+            .segments
+            .iter()
+            .zip(translations)
+            .map(|(segment, translation)| match (translation, mode) {
+                (None, _) => segment.clone(),
+                (Some(translation), TranslationMode::Translated) => Segment {
+                    // Keep the leading space of whisper segments
+                    text: format!(" {}", translation),
+                    words: Vec::new(),
+                    language: Some(target.to_string()),
+                    ..segment.clone()
+                },
+                (Some(translation), TranslationMode::Bilingual) => Segment {
+                    translation: Some(translation),
+                    ..segment.clone()
+                },
+            })
+            .collect();
+        Ok(Transcript {
+            processing_time_sec: self.processing_time_sec,
+            segments,
+            language: match mode {
+                TranslationMode::Translated => Some(target.to_string()),
+                TranslationMode::Bilingual => self.language.clone(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::layout::SubtitleLayout;
+    use crate::transcript::Timestamp;
+    use std::cell::RefCell;
+
+    /// Upper cases the text and records the requests
+    struct FakeTranslator {
+        requests: RefCell<Vec<(Vec<String>, Option<String>)>>,
+    }
+
+    impl Translator for FakeTranslator {
+        fn translate(&self, texts: &[String], source: Option<&str>, _target: &str) -> Result<Vec<String>> {
+            self.requests.borrow_mut().push((texts.to_vec(), source.map(Into::into)));
+            Ok(texts.iter().map(|text| text.to_uppercase()).collect())
+        }
+    }
+
+    fn segment(start: i64, stop: i64, text: &str, language: Option<&str>) -> Segment {
+        Segment {
+            start: Timestamp::from_centis(start),
+            stop: Timestamp::from_centis(stop),
+            text: text.into(),
+            language: language.map(Into::into),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_translate_by_language() {
+        let transcript = Transcript {
+            processing_time_sec: 0,
+            segments: vec![
+                segment(0, 100, " shalom", Some("he")),
+                segment(100, 200, " hello\nthere", Some("en")),
+                segment(200, 300, " ma nishma", None),
+            ],
+            language: Some("he".into()),
+        };
+        let translator = FakeTranslator {
+            requests: RefCell::new(Vec::new()),
+        };
+        let translated = transcript
+            .translate_with(&translator, None, "en", TranslationMode::Translated)
+            .unwrap();
+        let texts: Vec<&str> = translated.segments.iter().map(|segment| segment.text.as_str()).collect();
+        assert_eq!(texts, vec![" SHALOM", " hello\nthere", " MA NISHMA"]);
+        assert_eq!(translated.language.as_deref(), Some("en"));
+        // Only the hebrew segments were sent, in one request
+        assert_eq!(
+            translator.requests.borrow().as_slice(),
+            &[(vec!["shalom".to_string(), "ma nishma".to_string()], Some("he".to_string()))]
+        );
+    }
+
+    #[test]
+    fn test_bilingual_subtitles() {
+        let transcript = Transcript {
+            processing_time_sec: 0,
+            segments: vec![segment(0, 100, " bonjour", None)],
+            language: None,
+        };
+        let translator = FakeTranslator {
+            requests: RefCell::new(Vec::new()),
+        };
+        let bilingual = transcript
+            .translate_with(&translator, Some("fr"), "en", TranslationMode::Bilingual)
+            .unwrap();
+        assert_eq!(bilingual.as_srt(), "\n1\n00:00:00,000 --> 00:00:01,000\nbonjour\nBONJOUR\n");
+
+        // Laid out cues get the part of the translation matching their text
+        let transcript = Transcript {
+            processing_time_sec: 0,
+            segments: vec![Segment {
+                translation: Some("un deux trois quatre cinq six".into()),
+                ..segment(0, 600, " one two three, four five six", None)
+            }],
+            language: None,
+        };
+        let layout = SubtitleLayout {
+            max_chars_per_line: 14,
+            max_lines: 1,
+            ..Default::default()
+        };
+        let cues = transcript.layout(&layout).segments;
+        let translations: Vec<Option<&str>> = cues.iter().map(|cue| cue.translation.as_deref()).collect();
+        assert_eq!(translations, vec![Some("un deux trois"), Some("quatre cinq\nsix")]);
+    }
+}
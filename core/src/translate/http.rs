@@ -0,0 +1,251 @@
+use super::Translator;
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use serde_json::{json, Value};
+use std::future::Future;
+
+/// Texts sent in one request
+const BATCH_SIZE: usize = 50;
+
+/// Run a request on its own runtime in another thread.
+/// Translation runs inside sync code, which may itself be called from an async command
+fn block_on<F>(future: F) -> Result<F::Output>
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .context("failed to create runtime")?;
+                Ok(runtime.block_on(future))
+            })
+            .join()
+            .map_err(|_| eyre!("translation thread panicked"))?
+    })
+}
+
+/// POST json and return the json response. Errors include the response body, which usually explains them
+async fn post(url: &str, api_key: Option<&str>, body: &Value) -> Result<Value> {
+    let mut request = reqwest::Client::new()
+        .post(url)
+        .header("Content-Type", "application/json")
+        .body(body.to_string());
+    if let Some(api_key) = api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("failed to send request to {}", url))?;
+    let status = response.status();
+    let text = response.text().await.context("failed to read response")?;
+    if !status.is_success() {
+        bail!("translation request failed with {}: {}", status, text)
+    }
+    serde_json::from_str(&text).with_context(|| format!("invalid response {}", text))
+}
+
+/// LibreTranslate API. Sends a batch of texts in every request
+pub struct LibreTranslator {
+    pub url: String,
+    pub api_key: Option<String>,
+}
+
+impl LibreTranslator {
+    async fn translate_batch(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        let mut body = json!({
+            "q": texts,
+            "source": source.unwrap_or("auto"),
+            "target": target,
+            "format": "text",
+        });
+        // LibreTranslate takes the key in the body
+        if let Some(ref api_key) = self.api_key {
+            body["api_key"] = json!(api_key);
+        }
+        let url = format!("{}/translate", self.url.trim_end_matches('/'));
+        let response = post(&url, None, &body).await?;
+        serde_json::from_value(response["translatedText"].clone()).with_context(|| format!("invalid response {}", response))
+    }
+}
+
+impl Translator for LibreTranslator {
+    fn translate(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        block_on(async {
+            let mut translations = Vec::new();
+            for batch in texts.chunks(BATCH_SIZE) {
+                translations.extend(self.translate_batch(batch, source, target).await?);
+            }
+            Ok(translations)
+        })?
+    }
+}
+
+/// Chat completions API of OpenAI and compatible servers.
+/// Batches are sent as a JSON array, and texts are sent one by one when the model doesn't answer with one
+/// translation per text
+pub struct OpenAiTranslator {
+    pub url: String,
+    pub model: String,
+    pub api_key: Option<String>,
+}
+
+/// Models often wrap JSON in a markdown code block
+fn strip_code_block(content: &str) -> &str {
+    let content = content.trim();
+    match content.strip_prefix("```") {
+        Some(rest) => rest.trim_start_matches("json").trim_end().trim_end_matches("```").trim(),
+        None => content,
+    }
+}
+
+impl OpenAiTranslator {
+    async fn complete(&self, instructions: &str, content: &str) -> Result<String> {
+        let body = json!({
+            "model": self.model,
+            "temperature": 0,
+            "messages": [
+                {"role": "system", "content": instructions},
+                {"role": "user", "content": content},
+            ],
+        });
+        let url = format!("{}/chat/completions", self.url.trim_end_matches('/'));
+        let response = post(&url, self.api_key.as_deref(), &body).await?;
+        let content = response["choices"][0]["message"]["content"]
+            .as_str()
+            .with_context(|| format!("invalid response {}", response))?;
+        Ok(content.to_string())
+    }
+
+    async fn translate_batch(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        let from = source.map(|source| format!(" from {}", source)).unwrap_or_default();
+        let instructions = format!(
+            "Translate every string of the JSON array{from} to the language with the code {target}. \
+             Answer only with a JSON array of the translations, in the same order."
+        );
+        let content = self.complete(&instructions, &serde_json::to_string(texts)?).await?;
+        match serde_json::from_str::<Vec<String>>(strip_code_block(&content)) {
+            Ok(translations) if translations.len() == texts.len() => return Ok(translations),
+            _ => tracing::debug!("translations don't match the texts, translate one by one. got {}", content),
+        }
+        let instructions =
+            format!("Translate the text{from} to the language with the code {target}. Answer only with the translation.");
+        let mut translations = Vec::new();
+        for text in texts {
+            translations.push(self.complete(&instructions, text).await?.trim().to_string());
+        }
+        Ok(translations)
+    }
+}
+
+impl Translator for OpenAiTranslator {
+    fn translate(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        block_on(async {
+            let mut translations = Vec::new();
+            for batch in texts.chunks(BATCH_SIZE) {
+                translations.extend(self.translate_batch(batch, source, target).await?);
+            }
+            Ok(translations)
+        })?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Local server answering every request with the next response. Sends the request bodies to the channel
+    fn mock_server(responses: Vec<Value>) -> (String, mpsc::Receiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                sender.send(serde_json::from_slice(&body).unwrap()).unwrap();
+                let response = response.to_string();
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    fn texts() -> Vec<String> {
+        vec!["Hello".into(), "How are you?".into()]
+    }
+
+    #[test]
+    fn test_libretranslate() {
+        let (url, requests) = mock_server(vec![json!({"translatedText": ["Hola", "¿Cómo estás?"]})]);
+        let translator = LibreTranslator {
+            url,
+            api_key: Some("key".into()),
+        };
+        let translations = translator.translate(&texts(), None, "es").unwrap();
+        assert_eq!(translations, vec!["Hola", "¿Cómo estás?"]);
+        let request = requests.recv().unwrap();
+        assert_eq!(request["q"], json!(["Hello", "How are you?"]));
+        assert_eq!(request["source"], "auto");
+        assert_eq!(request["target"], "es");
+        assert_eq!(request["api_key"], "key");
+    }
+
+    #[test]
+    fn test_openai() {
+        let answer = |content: &str| json!({"choices": [{"message": {"role": "assistant", "content": content}}]});
+        let (url, requests) = mock_server(vec![
+            answer("```json\n[\"Hola\", \"¿Cómo estás?\"]\n```"),
+            // Not one translation per text, sent again one by one
+            answer("[\"Hola ¿Cómo estás?\"]"),
+            answer("Hola"),
+            answer(" ¿Cómo estás?\n"),
+        ]);
+        let translator = OpenAiTranslator {
+            url: format!("{}/v1/", url),
+            model: "model".into(),
+            api_key: None,
+        };
+        assert_eq!(
+            translator.translate(&texts(), Some("en"), "es").unwrap(),
+            vec!["Hola", "¿Cómo estás?"]
+        );
+        let request = requests.recv().unwrap();
+        assert_eq!(request["model"], "model");
+        assert_eq!(request["messages"][1]["content"], "[\"Hello\",\"How are you?\"]");
+
+        assert_eq!(
+            translator.translate(&texts(), Some("en"), "es").unwrap(),
+            vec!["Hola", "¿Cómo estás?"]
+        );
+        assert_eq!(requests.iter().nth(1).unwrap()["messages"][1]["content"], "Hello");
+    }
+}
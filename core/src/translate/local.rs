@@ -0,0 +1,105 @@
+use super::Translator;
+use eyre::{bail, eyre, Context, ContextCompat, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Offline translation program, such as translateLocally or argos-translate, reading one text per line
+/// on stdin and writing one translation per line on stdout.
+/// `{source}`, `{target}` and `{model}` in the arguments are replaced with the languages and model path
+pub struct LocalTranslator {
+    pub command: String,
+    pub args: Vec<String>,
+    pub model_path: Option<String>,
+}
+
+impl LocalTranslator {
+    fn args(&self, source: Option<&str>, target: &str) -> Vec<String> {
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{source}", source.unwrap_or("auto"))
+                    .replace("{target}", target)
+                    .replace("{model}", self.model_path.as_deref().unwrap_or_default())
+            })
+            .collect()
+    }
+}
+
+impl Translator for LocalTranslator {
+    fn translate(&self, texts: &[String], source: Option<&str>, target: &str) -> Result<Vec<String>> {
+        let args = self.args(source, target);
+        tracing::debug!("translate with {} {:?}", self.command, args);
+        let mut child = Command::new(&self.command)
+            .args(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.command))?;
+        let input: String = texts.iter().map(|text| text.replace(['\n', '\r'], " ") + "\n").collect();
+        // Written from another thread, the program may fill stdout before reading everything
+        let mut stdin = child.stdin.take().context("stdin")?;
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child.wait_with_output().context("failed to wait for translation")?;
+        writer
+            .join()
+            .map_err(|_| eyre!("failed to write texts"))?
+            .context("failed to write texts")?;
+        if !output.status.success() {
+            bail!(
+                "{} failed with {}: {}",
+                self.command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            )
+        }
+        let translations: Vec<String> = String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect();
+        if translations.len() != texts.len() {
+            bail!(
+                "{} wrote {} lines for {} texts",
+                self.command,
+                translations.len(),
+                texts.len()
+            )
+        }
+        Ok(translations)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        let translator = LocalTranslator {
+            command: "translateLocally".into(),
+            args: vec![
+                "-m".into(),
+                "{model}".into(),
+                "--from={source}".into(),
+                "--to={target}".into(),
+            ],
+            model_path: Some("/models/en-de".into()),
+        };
+        assert_eq!(
+            translator.args(None, "de"),
+            vec!["-m", "/models/en-de", "--from=auto", "--to=de"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_translate_lines() {
+        let translator = LocalTranslator {
+            command: "tr".into(),
+            args: vec!["a-z".into(), "A-Z".into()],
+            model_path: None,
+        };
+        let texts = vec!["hello".to_string(), "two\nlines".to_string()];
+        assert_eq!(translator.translate(&texts, None, "en").unwrap(), vec!["HELLO", "TWO LINES"]);
+    }
+}
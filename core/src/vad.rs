@@ -0,0 +1,332 @@
+use crate::transcript::Timestamp;
+use eyre::{Context, OptionExt, Result};
+use ndarray::{Array3, Axis};
+use ort::session::builder::GraphOptimizationLevel;
+use ort::session::Session;
+use ort::value::TensorRef;
+use serde::{Deserialize, Serialize};
+
+const SAMPLE_RATE: usize = 16000;
+const SAMPLES_PER_MS: usize = SAMPLE_RATE / 1000;
+
+/// Voice activity detection backend used before transcribing
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase", tag = "type")]
+pub enum VadOptions {
+    /// Frame energy against the noise floor. No model required.
+    Energy {
+        /// Speech threshold in dBFS. Derived from the noise floor when not set
+        threshold: Option<f32>,
+    },
+    /// pyannote segmentation model, the same one diarization uses
+    Pyannote { model_path: String },
+}
+
+/// Range of speech in samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeechRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+pub trait VoiceActivityDetector {
+    /// Speech regions of 16KHz mono samples, sorted and non overlapping
+    fn detect(&mut self, samples: &[i16]) -> Result<Vec<SpeechRegion>>;
+}
+
+pub fn create_detector(options: &VadOptions) -> Box<dyn VoiceActivityDetector> {
+    match options {
+        VadOptions::Energy { threshold } => Box::new(EnergyVad {
+            threshold: *threshold,
+            ..Default::default()
+        }),
+        VadOptions::Pyannote { model_path } => Box::new(PyannoteVad::new(model_path.clone())),
+    }
+}
+
+pub struct EnergyVad {
+    /// Speech threshold in dBFS
+    pub threshold: Option<f32>,
+    pub frame_ms: usize,
+    /// Shorter speech is dropped
+    pub min_speech_ms: usize,
+    /// Shorter silence between speech is kept
+    pub min_silence_ms: usize,
+    /// Added around every region so word edges aren't cut
+    pub padding_ms: usize,
+}
+
+impl Default for EnergyVad {
+    fn default() -> Self {
+        Self {
+            threshold: None,
+            frame_ms: 30,
+            min_speech_ms: 250,
+            min_silence_ms: 500,
+            padding_ms: 200,
+        }
+    }
+}
+
+impl EnergyVad {
+    fn frame_db(frame: &[i16]) -> f32 {
+        let sum: f64 = frame.iter().map(|&s| (s as f64 / i16::MAX as f64).powi(2)).sum();
+        let rms = (sum / frame.len().max(1) as f64).sqrt();
+        20.0 * (rms.max(1e-10) as f32).log10()
+    }
+}
+
+impl VoiceActivityDetector for EnergyVad {
+    fn detect(&mut self, samples: &[i16]) -> Result<Vec<SpeechRegion>> {
+        let frame_len = self.frame_ms * SAMPLES_PER_MS;
+        let energies: Vec<f32> = samples.chunks(frame_len).map(Self::frame_db).collect();
+        if energies.is_empty() {
+            return Ok(Vec::new());
+        }
+        let threshold = self.threshold.unwrap_or_else(|| {
+            let mut sorted = energies.clone();
+            sorted.sort_by(|a, b| a.total_cmp(b));
+            let noise_floor = sorted[sorted.len() / 10];
+            (noise_floor + 12.0).max(-55.0)
+        });
+        tracing::debug!("energy vad threshold {:.1} dBFS", threshold);
+
+        let mut regions: Vec<SpeechRegion> = Vec::new();
+        for (i, energy) in energies.iter().enumerate() {
+            if *energy < threshold {
+                continue;
+            }
+            let start = i * frame_len;
+            let end = (start + frame_len).min(samples.len());
+            match regions.last_mut() {
+                Some(last) if start - last.end < self.min_silence_ms * SAMPLES_PER_MS => last.end = end,
+                _ => regions.push(SpeechRegion { start, end }),
+            }
+        }
+        regions.retain(|r| r.end - r.start >= self.min_speech_ms * SAMPLES_PER_MS);
+        Ok(pad_regions(&regions, self.padding_ms * SAMPLES_PER_MS, samples.len()))
+    }
+}
+
+/// Windows the segmentation model classifies, and its frames, as in pyannote_rs::get_segments
+const PYANNOTE_WINDOW: usize = 10 * SAMPLE_RATE;
+const PYANNOTE_FRAME_SIZE: usize = 270;
+const PYANNOTE_FRAME_START: usize = 721;
+
+/// pyannote segmentation model. pyannote_rs::get_segments loads the model on every call,
+/// so the session is created on the first detection and reused for the next chunks
+pub struct PyannoteVad {
+    pub model_path: String,
+    session: Option<Session>,
+}
+
+impl PyannoteVad {
+    pub fn new(model_path: String) -> Self {
+        Self {
+            model_path,
+            session: None,
+        }
+    }
+
+    fn session(&mut self) -> Result<&mut Session> {
+        if self.session.is_none() {
+            tracing::debug!("load vad model {}", self.model_path);
+            let session = Session::builder()?
+                .with_optimization_level(GraphOptimizationLevel::Level3)?
+                .with_intra_threads(1)?
+                .with_inter_threads(1)?
+                .commit_from_file(&self.model_path)
+                .with_context(|| format!("failed to load vad model {}", self.model_path))?;
+            self.session = Some(session);
+        }
+        self.session.as_mut().ok_or_eyre("vad model not loaded")
+    }
+}
+
+impl VoiceActivityDetector for PyannoteVad {
+    fn detect(&mut self, samples: &[i16]) -> Result<Vec<SpeechRegion>> {
+        let session = self.session()?;
+        let mut regions = Vec::new();
+        let mut speech_start = None;
+        let mut offset = PYANNOTE_FRAME_START;
+        for window in samples.chunks(PYANNOTE_WINDOW) {
+            // The last window is padded with silence
+            let mut input = Array3::<f32>::zeros((1, 1, PYANNOTE_WINDOW));
+            for (value, sample) in input.iter_mut().zip(window) {
+                *value = *sample as f32;
+            }
+            let outputs = session.run(ort::inputs![TensorRef::from_array_view(&input)?])?;
+            // Scores of the speaker classes of every frame. Class 0 is silence
+            let scores = outputs["output"].try_extract_array::<f32>()?;
+            for frame in scores.index_axis(Axis(0), 0).outer_iter() {
+                let class = frame
+                    .iter()
+                    .enumerate()
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map_or(0, |(class, _)| class);
+                match (class != 0, speech_start) {
+                    (true, None) => speech_start = Some(offset),
+                    (false, Some(start)) => {
+                        regions.push(SpeechRegion { start, end: offset });
+                        speech_start = None;
+                    }
+                    _ => {}
+                }
+                offset += PYANNOTE_FRAME_SIZE;
+            }
+        }
+        if let Some(start) = speech_start {
+            regions.push(SpeechRegion { start, end: offset });
+        }
+        let regions: Vec<SpeechRegion> = regions
+            .into_iter()
+            .map(|region| SpeechRegion {
+                start: region.start.min(samples.len()),
+                end: region.end.min(samples.len()),
+            })
+            .filter(|region| region.end > region.start)
+            .collect();
+        Ok(pad_regions(&regions, 0, samples.len()))
+    }
+}
+
+/// Pad regions, clamp them to the audio and merge the ones that overlap
+fn pad_regions(regions: &[SpeechRegion], padding: usize, len: usize) -> Vec<SpeechRegion> {
+    let mut padded: Vec<SpeechRegion> = Vec::new();
+    for region in regions {
+        let start = region.start.saturating_sub(padding);
+        let end = (region.end + padding).min(len);
+        match padded.last_mut() {
+            Some(last) if start <= last.end => last.end = last.end.max(end),
+            _ => padded.push(SpeechRegion { start, end }),
+        }
+    }
+    padded
+}
+
+/// Speech regions glued together, with the way back to the original timeline
+#[derive(Debug, Clone)]
+pub struct SpeechTimeline {
+    /// (offset in the speech samples, region in the original samples)
+    regions: Vec<(usize, SpeechRegion)>,
+}
+
+impl SpeechTimeline {
+    pub fn new(regions: &[SpeechRegion]) -> Self {
+        let mut offset = 0;
+        let regions = regions
+            .iter()
+            .map(|region| {
+                let entry = (offset, *region);
+                offset += region.end - region.start;
+                entry
+            })
+            .collect();
+        Self { regions }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Only the speech samples
+    pub fn speech_samples(&self, samples: &[i16]) -> Vec<i16> {
+        let mut speech = Vec::new();
+        for (_, region) in &self.regions {
+            speech.extend_from_slice(&samples[region.start..region.end]);
+        }
+        speech
+    }
+
+    /// Map a timestamp of the speech samples back to the original audio
+    pub fn to_original(&self, timestamp: Timestamp) -> Timestamp {
+        let sample = timestamp.as_samples(SAMPLE_RATE);
+        let index = self.regions.partition_point(|(offset, _)| *offset <= sample).saturating_sub(1);
+        let Some((offset, region)) = self.regions.get(index) else {
+            return timestamp;
+        };
+        let original = (region.start + (sample - offset)).min(region.end);
+        Timestamp::from_samples(original, SAMPLE_RATE)
+    }
+}
+
+/// Run the configured detector on the samples
+pub fn detect_speech(options: &VadOptions, samples: &[i16]) -> Result<SpeechTimeline> {
+    detect_speech_with(create_detector(options).as_mut(), samples)
+}
+
+/// Run `detector` on the samples. Chunks of the same file reuse one detector, so its model is loaded once
+pub fn detect_speech_with(detector: &mut dyn VoiceActivityDetector, samples: &[i16]) -> Result<SpeechTimeline> {
+    let regions = detector.detect(samples)?;
+    let speech: usize = regions.iter().map(|r| r.end - r.start).sum();
+    tracing::debug!(
+        "vad found {} speech regions. {:.1}s of {:.1}s",
+        regions.len(),
+        speech as f32 / SAMPLE_RATE as f32,
+        samples.len() as f32 / SAMPLE_RATE as f32
+    );
+    Ok(SpeechTimeline::new(&regions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(seconds: f32) -> Vec<i16> {
+        let len = (seconds * SAMPLE_RATE as f32) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (0.3 * (2.0 * std::f32::consts::PI * 220.0 * t).sin() * i16::MAX as f32) as i16
+            })
+            .collect()
+    }
+
+    fn silence(seconds: f32) -> Vec<i16> {
+        // Low noise floor instead of digital silence
+        (0..(seconds * SAMPLE_RATE as f32) as usize).map(|i| (i % 7) as i16 - 3).collect()
+    }
+
+    #[test]
+    fn test_energy_vad_finds_speech() {
+        let samples = [silence(2.0), tone(1.0), silence(2.0), tone(0.1), silence(1.0)].concat();
+        let regions = EnergyVad::default().detect(&samples).unwrap();
+        // The short burst is dropped
+        assert_eq!(regions.len(), 1);
+        let region = regions[0];
+        assert!(region.start >= (1.7 * SAMPLE_RATE as f32) as usize && region.start <= 2 * SAMPLE_RATE);
+        assert!(region.end >= 3 * SAMPLE_RATE && region.end <= (3.3 * SAMPLE_RATE as f32) as usize);
+    }
+
+    #[test]
+    fn test_energy_vad_merges_short_silence() {
+        let samples = [silence(1.0), tone(1.0), silence(0.2), tone(1.0), silence(1.0)].concat();
+        let regions = EnergyVad::default().detect(&samples).unwrap();
+        assert_eq!(regions.len(), 1);
+    }
+
+    #[test]
+    fn test_timeline_maps_back() {
+        let regions = [
+            SpeechRegion {
+                start: 16000,
+                end: 32000,
+            },
+            SpeechRegion {
+                start: 80000,
+                end: 96000,
+            },
+        ];
+        let timeline = SpeechTimeline::new(&regions);
+        let samples: Vec<i16> = (0..100000).map(|i| (i % 100) as i16).collect();
+        assert_eq!(timeline.speech_samples(&samples).len(), 32000);
+
+        // 0.5s into the speech is 1.5s in the original
+        assert_eq!(timeline.to_original(Timestamp::from_centis(50)), Timestamp::from_centis(150));
+        // 1.5s into the speech is in the second region at 5.5s
+        assert_eq!(timeline.to_original(Timestamp::from_centis(150)), Timestamp::from_centis(550));
+        // Past the end is clamped to the last region
+        assert_eq!(timeline.to_original(Timestamp::from_centis(500)), Timestamp::from_centis(600));
+    }
+}
@@ -1,12 +1,25 @@
 use clap::Parser;
-use eyre::{Context, ContextCompat, Result};
+use eyre::{bail, Context, ContextCompat, Result};
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::time::Instant;
 use tauri::AppHandle;
+use vibe_core::batch::{BatchEvent, BatchJob, BatchQueue, JobStatus};
 use vibe_core::config::TranscribeOptions;
-use vibe_core::transcribe;
+use vibe_core::diarize::{DiarizeMode, DEFAULT_PROFILE_THRESHOLD};
+use vibe_core::glossary::Glossary;
+use vibe_core::language;
+use vibe_core::speaker_profiles::{self, SpeakerProfiles};
+use vibe_core::transcribe::{self, DiarizeOptions, WhisperContext};
+use vibe_core::transcript::cleanup::{CleanupAction, CleanupOptions};
+use vibe_core::transcript::edit::Edit;
+use vibe_core::transcript::format::TranscriptFormat;
+use vibe_core::transcript::layout::SubtitleLayout;
+use vibe_core::transcript::speakers::SpeakerLabels;
+use vibe_core::transcript::{Timestamp, Transcript};
+use vibe_core::translate::{TranslateOptions, TranslationMode, TranslatorOptions};
+use vibe_core::vad::VadOptions;
 
 use crate::cmd::get_models_folder;
 
@@ -54,18 +67,43 @@ struct Args {
     #[arg(long, short)]
     model: Option<PathBuf>,
 
-    /// Path to file to transcribe
+    /// Path to file to transcribe. srt, vtt and json transcripts are converted to --format instead
     #[arg(long)]
     file: Option<String>,
 
-    /// Language to transcribe
+    /// Language to transcribe. "auto" detects it from the first 30 seconds
     #[arg(short, long, default_value = "english", value_parser = get_possible_languages())]
     language: String,
 
+    /// Detect the language of every chunk or diarized segment, for audio that switches languages
+    #[arg(long)]
+    multilingual: bool,
+
+    /// Language codes to choose from with --multilingual, for example he,en
+    #[arg(long, value_delimiter = ',', requires = "multilingual")]
+    languages: Option<Vec<String>>,
+
     /// Temperature (default: 0.4)
     #[arg(short, long, default_value = "0.4")]
     temperature: Option<f32>,
 
+    /// Temperature fallback schedule, for example 0,0.2,0.4,0.6,0.8,1.
+    /// Segments failing the thresholds are decoded again with the next temperature
+    #[arg(long, value_delimiter = ',')]
+    temperatures: Option<Vec<f32>>,
+
+    /// Decode again above this compression ratio with --temperatures (default: 2.4)
+    #[arg(long)]
+    compression_ratio_threshold: Option<f32>,
+
+    /// Decode again below this average log probability with --temperatures (default: -1.0)
+    #[arg(long)]
+    logprob_threshold: Option<f32>,
+
+    /// Drop low probability segments above this no-speech probability with --temperatures and --detect-no-speech (default: 0.6)
+    #[arg(long)]
+    no_speech_threshold: Option<f32>,
+
     /// Number of threads (default: 4)
     #[arg(short, long, default_value = "4")]
     n_threads: Option<i32>,
@@ -82,6 +120,11 @@ struct Args {
     #[arg(short, long)]
     init_prompt: Option<String>,
 
+    /// Glossary of domain terms, added to the prompt and used to correct the transcript.
+    /// json, or text with a "Term: alias, alias" or "from => to" replacement per line
+    #[arg(long)]
+    glossary: Option<PathBuf>,
+
     /// Path to write transcript
     #[arg(short, long)]
     write: Option<PathBuf>,
@@ -101,10 +144,165 @@ struct Args {
     // TODO: use possible values. confusing crate!
     max_sentence_len: Option<i32>,
 
+    /// Split and reflow subtitles into cues that follow the line and reading speed limits below
+    #[arg(long)]
+    subtitle_layout: bool,
+
+    /// Max characters per subtitle line. Implies --subtitle-layout (default: 42)
+    #[arg(long)]
+    max_line_chars: Option<usize>,
+
+    /// Max lines per subtitle cue. Implies --subtitle-layout (default: 2)
+    #[arg(long)]
+    max_lines: Option<usize>,
+
+    /// Min seconds a subtitle cue stays on screen. Implies --subtitle-layout (default: 0.83)
+    #[arg(long)]
+    min_cue_duration: Option<f32>,
+
+    /// Max seconds of a subtitle cue. Implies --subtitle-layout (default: 7)
+    #[arg(long)]
+    max_cue_duration: Option<f32>,
+
+    /// Max reading speed in characters per second. Implies --subtitle-layout (default: 20)
+    #[arg(long)]
+    max_cps: Option<f32>,
+
+    /// Transcribe in windows of this many seconds to keep memory flat on long files
+    #[arg(long)]
+    chunk_duration: Option<u32>,
+
+    /// Overlap in seconds between windows when using --chunk-duration (default: 5)
+    #[arg(long)]
+    chunk_overlap: Option<u32>,
+
+    /// Save progress and resume an interrupted transcription of the same file
+    #[arg(long)]
+    checkpoint: bool,
+
+    /// Skip silence with voice activity detection before transcribing.
+    /// pyannote uses the model of --diarize-vad-model
+    #[arg(long, value_parser = ["energy", "pyannote"])]
+    vad: Option<String>,
+
+    /// Compute the no-speech probability of every segment (included in json output)
+    #[arg(long)]
+    detect_no_speech: bool,
+
+    /// Drop or flag repetition loops and other hallucinations
+    #[arg(long, value_parser = ["drop", "flag"])]
+    cleanup: Option<String>,
+
+    /// Segments with more characters per second are hallucinations with --cleanup (default: 25)
+    #[arg(long)]
+    max_chars_per_second: Option<f32>,
+
+    /// Translate the transcript to this language code with --translator, for example de
+    #[arg(long)]
+    translate_to: Option<String>,
+
+    /// Language code of the speech for --translate-to, when it's not given or detected
+    #[arg(long)]
+    translate_from: Option<String>,
+
+    /// Translation backend for --translate-to
+    #[arg(long, default_value = "libretranslate", value_parser = ["libretranslate", "openai", "local"])]
+    translator: String,
+
+    /// Server of --translator (default: http://localhost:5000 for libretranslate, https://api.openai.com/v1 for openai)
+    #[arg(long)]
+    translator_url: Option<String>,
+
+    /// Model name with --translator openai, or model path replacing {model} in --translator-command
+    #[arg(long)]
+    translator_model: Option<String>,
+
+    /// API key of --translator
+    #[arg(long)]
+    translator_api_key: Option<String>,
+
+    /// Program with --translator local, reading a text per line on stdin and writing translations to stdout.
+    /// {source}, {target} and {model} are replaced, for example "translateLocally -m {model}"
+    #[arg(long)]
+    translator_command: Option<String>,
+
+    /// Show the translation under the original text instead of replacing it
+    #[arg(long, requires = "translate_to")]
+    bilingual: bool,
+
+    /// Transcribe every audio and video file in this folder. Transcripts are written next to each file
+    #[arg(long)]
+    folder: Option<PathBuf>,
+
+    /// Number of files to transcribe in parallel with --folder
+    #[arg(long, default_value = "2")]
+    workers: usize,
+
     /// Enable diarize (speaker labels)
     #[arg(long)]
     diarize: bool,
 
+    /// Exact number of speakers with --diarize. Found automatically when not given
+    #[arg(long, requires = "diarize")]
+    speakers: Option<usize>,
+
+    /// Most speakers found automatically with --diarize
+    #[arg(long, default_value = "5")]
+    max_speakers: usize,
+
+    /// Minimum similarity of segments of the same speaker with --diarize, between 0 and 1
+    #[arg(long, default_value = "0.5")]
+    diarize_threshold: f32,
+
+    /// Mark segments in which several speakers talk at once with --diarize
+    #[arg(long, requires = "diarize")]
+    detect_overlap: bool,
+
+    /// Speaker profiles database. Speakers enrolled in it are labelled with their name with --diarize
+    #[arg(long)]
+    speaker_profiles: Option<PathBuf>,
+
+    /// Minimum similarity of a speaker to an enrolled profile to be labelled with its name, between 0 and 1
+    #[arg(long, default_value_t = DEFAULT_PROFILE_THRESHOLD)]
+    profile_threshold: f32,
+
+    /// Enroll the voice in --file under this name in --speaker-profiles, and exit.
+    /// The file should have only this speaker. Enrolling a name again improves its profile
+    #[arg(long, requires = "speaker_profiles")]
+    enroll_speaker: Option<String>,
+
+    /// Transcribe every speaker turn on its own, or the whole audio once and split it by the speaker of every word
+    #[arg(long, default_value = "segments", value_parser = ["segments", "words"])]
+    diarize_mode: String,
+
+    /// Name of a diarized speaker in the output by id, for example --speaker-name 0=Alice names "Speaker 1". Can be repeated
+    #[arg(long, value_parser = parse_speaker_name)]
+    speaker_name: Vec<(String, String)>,
+
+    /// Label unnamed speakers as "<prefix> <id + 1>"
+    #[arg(long, default_value = "Speaker")]
+    speaker_prefix: String,
+
+    /// Label only the first segment of every speaker turn
+    #[arg(long)]
+    label_speaker_changes: bool,
+
+    /// Rename a speaker by id, for example --rename-speaker 1=0 merges "Speaker 2" into "Speaker 1". Can be repeated
+    #[arg(long, value_parser = parse_speaker_name)]
+    rename_speaker: Vec<(String, String)>,
+
+    /// Shift all timestamps by this many seconds. Can be negative
+    #[arg(long, allow_hyphen_values = true)]
+    shift: Option<f64>,
+
+    /// Multiply all timestamps by this factor, for example 25/23.976 for framerate conversions
+    #[arg(long, value_parser = parse_factor)]
+    scale: Option<f64>,
+
+    /// Replace matches of a regular expression in the text. The replacement can refer to groups with $1
+    #[arg(long, num_args = 2, value_names = ["PATTERN", "REPLACEMENT"])]
+    replace: Option<Vec<String>>,
+
     /// Path to vad model
     #[arg(long)]
     pub diarize_vad_model: Option<String>,
@@ -137,8 +335,28 @@ fn get_possible_languages() -> Vec<String> {
     languages
 }
 
+/// Same as audioExtensions and videoExtensions in the frontend config
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "mp3", "wav", "aac", "flac", "oga", "ogg", "opic", "opus", "m4a", "wma", "mp4", "mkv", "avi", "mov", "wmv", "webm",
+];
+
+/// Parse `id=name`
+fn parse_speaker_name(value: &str) -> Result<(String, String), String> {
+    let (id, name) = value.split_once('=').ok_or("expected id=name")?;
+    Ok((id.trim().to_string(), name.trim().to_string()))
+}
+
+/// Parse a number or a fraction such as 25/23.976
+fn parse_factor(value: &str) -> Result<f64, String> {
+    let parse = |number: &str| number.trim().parse::<f64>().map_err(|error| format!("{}: {}", number, error));
+    match value.split_once('/') {
+        Some((numerator, denominator)) => Ok(parse(numerator)? / parse(denominator)?),
+        None => parse(value),
+    }
+}
+
 pub fn get_possible_formats() -> Vec<String> {
-    vec!["txt".into(), "srt".into(), "vtt".into()]
+    TranscriptFormat::ALL.iter().map(|format| format.to_string()).collect()
 }
 
 fn prepare_model_path(path: &Path, app_handle: &tauri::AppHandle) -> Result<PathBuf> {
@@ -157,6 +375,199 @@ fn prepare_model_path(path: &Path, app_handle: &tauri::AppHandle) -> Result<Path
     Ok(path.to_path_buf())
 }
 
+/// Edits given with --rename-speaker, --shift, --scale and --replace
+fn get_edits(args: &Args) -> Vec<Edit> {
+    let mut edits: Vec<Edit> = args
+        .rename_speaker
+        .iter()
+        .map(|(from, to)| Edit::RenameSpeaker {
+            from: from.clone(),
+            to: to.clone(),
+        })
+        .collect();
+    if let Some(seconds) = args.shift {
+        edits.push(Edit::Shift {
+            offset: Timestamp::from_secs_f64(seconds),
+        });
+    }
+    if let Some(factor) = args.scale {
+        edits.push(Edit::Scale { factor });
+    }
+    if let Some([pattern, replacement]) = args.replace.as_deref() {
+        edits.push(Edit::Replace {
+            pattern: pattern.clone(),
+            replacement: replacement.clone(),
+        });
+    }
+    edits
+}
+
+/// Speaker recognition with --diarize
+fn get_diarize_options(args: &Args) -> Result<Option<DiarizeOptions>> {
+    if !args.diarize {
+        return Ok(None);
+    }
+    Ok(Some(DiarizeOptions {
+        segment_model_path: args
+            .diarize_vad_model
+            .clone()
+            .context("Please provide model path with --diarize-vad-model")?,
+        embedding_model_path: args
+            .diarize_speaker_id_model
+            .clone()
+            .context("Please provide model path with --diarize-speaker-id-model")?,
+        threshold: args.diarize_threshold,
+        max_speakers: args.max_speakers,
+        speakers: args.speakers,
+        mode: if args.diarize_mode == "words" {
+            DiarizeMode::Words
+        } else {
+            DiarizeMode::Segments
+        },
+        profiles: match args.speaker_profiles {
+            Some(ref db_path) if db_path.exists() => SpeakerProfiles::new(db_path)?.get_all()?,
+            _ => Vec::new(),
+        },
+        profile_threshold: args.profile_threshold,
+        detect_overlap: args.detect_overlap,
+    }))
+}
+
+/// Enroll the voice of --file with --enroll-speaker
+fn enroll_speaker(args: &Args, name: &str) -> Result<()> {
+    let db_path = args
+        .speaker_profiles
+        .as_deref()
+        .context("Please provide database path with --speaker-profiles")?;
+    let path = PathBuf::from(args.file.as_deref().context("Please provide a clip of the speaker with --file")?);
+    let wav_path = if transcribe::should_normalize(path.clone()) {
+        transcribe::create_normalized_audio(path, None)?
+    } else {
+        path
+    };
+    let samples = vibe_core::audio::parse_wav_file(&wav_path)?;
+    let embedding = speaker_profiles::clip_embedding(
+        &samples,
+        args.diarize_vad_model
+            .as_deref()
+            .context("Please provide model path with --diarize-vad-model")?,
+        args.diarize_speaker_id_model
+            .as_deref()
+            .context("Please provide model path with --diarize-speaker-id-model")?,
+    )?;
+    SpeakerProfiles::new(db_path)?.enroll(name, &embedding)?;
+    Ok(())
+}
+
+/// Translation given with --translate-to and the --translator options
+fn get_translation(args: &Args) -> Result<Option<TranslateOptions>> {
+    let Some(ref target) = args.translate_to else {
+        return Ok(None);
+    };
+    let backend = match args.translator.as_str() {
+        "openai" => TranslatorOptions::OpenAi {
+            url: args.translator_url.clone().unwrap_or_else(|| "https://api.openai.com/v1".into()),
+            model: args.translator_model.clone().context("Please provide model name with --translator-model")?,
+            api_key: args.translator_api_key.clone(),
+        },
+        "local" => {
+            let command = args
+                .translator_command
+                .as_deref()
+                .context("Please provide program with --translator-command")?;
+            let mut parts = command.split_whitespace().map(String::from);
+            TranslatorOptions::Local {
+                command: parts.next().context("empty --translator-command")?,
+                args: parts.collect(),
+                model_path: args.translator_model.clone(),
+            }
+        }
+        _ => TranslatorOptions::LibreTranslate {
+            url: args.translator_url.clone().unwrap_or_else(|| "http://localhost:5000".into()),
+            api_key: args.translator_api_key.clone(),
+        },
+    };
+    Ok(Some(TranslateOptions {
+        target: target.clone(),
+        source: args.translate_from.clone(),
+        mode: if args.bilingual {
+            TranslationMode::Bilingual
+        } else {
+            TranslationMode::Translated
+        },
+        backend,
+    }))
+}
+
+/// How transcripts are written
+struct Output {
+    format: TranscriptFormat,
+    layout: Option<SubtitleLayout>,
+    speakers: SpeakerLabels,
+    /// Applied in order before the layout
+    edits: Vec<Edit>,
+}
+
+impl Output {
+    fn render(&self, transcript: &Transcript) -> Result<String> {
+        let mut transcript = transcript.clone();
+        for edit in &self.edits {
+            transcript = transcript.apply(edit)?;
+        }
+        match self.layout {
+            Some(ref layout) => transcript.layout(layout).format_with_speakers(self.format, &self.speakers),
+            None => transcript.format_with_speakers(self.format, &self.speakers),
+        }
+    }
+}
+
+/// Transcribe the media files of a folder with parallel workers
+fn transcribe_folder(
+    ctx: &WhisperContext,
+    folder: &Path,
+    options: TranscribeOptions,
+    diarize_options: Option<DiarizeOptions>,
+    workers: usize,
+    output: &Output,
+) -> Result<()> {
+    let patterns: Vec<String> = MEDIA_EXTENSIONS.iter().map(|ext| format!(".{}", ext)).collect();
+    let files = vibe_core::batch::glob_files(folder, &patterns, false)?;
+    if files.is_empty() {
+        bail!("No audio files found in {}", folder.display())
+    }
+
+    let queue = BatchQueue::new();
+    for file in &files {
+        let mut options = options.clone();
+        options.path = file.to_str().context("tostr")?.to_string();
+        queue.submit(BatchJob {
+            diarize_options: diarize_options.clone(),
+            ..BatchJob::new(options)
+        });
+    }
+    eprintln!("Transcribe {} files with {} workers... 🔄", files.len(), workers);
+    queue.run(ctx, workers, |event| match event {
+        BatchEvent::Finished { id, transcript } => {
+            let write_path = files[id].with_extension(output.format.extension());
+            let result = output
+                .render(&transcript)
+                .and_then(|content| std::fs::write(&write_path, content).context("write"));
+            match result {
+                Ok(_) => eprintln!("{} ✅", write_path.display()),
+                Err(err) => eprintln!("Error writing transcript to file: {:?}", err),
+            }
+        }
+        BatchEvent::Status {
+            id,
+            status: JobStatus::Failed(error),
+        } => {
+            eprintln!("{} failed ❌: {}", files[id].display(), error);
+        }
+        _ => {}
+    });
+    Ok(())
+}
+
 fn language_name_to_whisper_lang(name: &str) -> Result<String> {
     let languages_json = include_str!("../../src/assets/whisper-languages.json");
     let languages: Value = serde_json::from_str(languages_json).context("tostr")?;
@@ -174,22 +585,95 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
         panic!("Please provide model path with --diarize-vad-model")
     }
     if args.diarize {
+        // Words mode splits segments where the speaker changes, so segment boundaries are whisper's own
         args.word_timestamps = true;
-        args.max_sentence_len = Some(24);
-        args.format = "json".into();
     }
 
     #[cfg(feature = "server")]
     if args.server {
         crate::server::run(app_handle.clone(), args.host, args.port).await?;
     }
+    if let Some(ref name) = args.enroll_speaker {
+        enroll_speaker(&args, name)?;
+        eprintln!("Enrolled {} 🗣️", name);
+        app_handle.cleanup_before_exit();
+        process::exit(0);
+    }
     let lang = language_name_to_whisper_lang(&args.language)?;
+    let default_layout = SubtitleLayout::default();
+    let layout = SubtitleLayout {
+        max_chars_per_line: args.max_line_chars.unwrap_or(default_layout.max_chars_per_line),
+        max_lines: args.max_lines.unwrap_or(default_layout.max_lines),
+        min_duration: args.min_cue_duration.unwrap_or(default_layout.min_duration),
+        max_duration: args.max_cue_duration.unwrap_or(default_layout.max_duration),
+        max_chars_per_second: args.max_cps.unwrap_or(default_layout.max_chars_per_second),
+    };
+    let has_layout_args = args.max_line_chars.is_some()
+        || args.max_lines.is_some()
+        || args.min_cue_duration.is_some()
+        || args.max_cue_duration.is_some()
+        || args.max_cps.is_some();
+    let output = Output {
+        format: args.format.parse()?,
+        layout: (args.subtitle_layout || has_layout_args).then_some(layout),
+        speakers: SpeakerLabels {
+            prefix: args.speaker_prefix.clone(),
+            names: args.speaker_name.iter().cloned().collect(),
+            on_change_only: args.label_speaker_changes,
+        },
+        edits: get_edits(&args),
+    };
+    let translation = get_translation(&args)?;
+    let diarize_options = get_diarize_options(&args)?;
+    // Subtitles and transcripts are converted to --format without transcribing
+    if let Some(file) = args.file.as_deref().map(Path::new) {
+        if matches!(file.extension().and_then(|ext| ext.to_str()), Some("srt" | "vtt" | "json")) {
+            let mut transcript = Transcript::load(file)?;
+            if let Some(ref translation) = translation {
+                transcript = transcript.translate(translation)?;
+            }
+            let content = output.render(&transcript)?;
+            match args.write {
+                Some(write_path) => std::fs::write(write_path, content).context("write")?,
+                None => println!("{}", content),
+            }
+            app_handle.cleanup_before_exit();
+            process::exit(0);
+        }
+    }
+    let vad = match args.vad.as_deref() {
+        Some("pyannote") => Some(VadOptions::Pyannote {
+            model_path: args
+                .diarize_vad_model
+                .clone()
+                .context("Please provide model path with --diarize-vad-model")?,
+        }),
+        Some(_) => Some(VadOptions::Energy { threshold: None }),
+        None => None,
+    };
+    let cleanup = args.cleanup.as_deref().map(|action| CleanupOptions {
+        action: if action == "flag" {
+            CleanupAction::Flag
+        } else {
+            CleanupAction::Drop
+        },
+        max_chars_per_second: args.max_chars_per_second,
+        ..Default::default()
+    });
     let options = TranscribeOptions {
-        path: args.file.context("file")?,
+        path: if args.folder.is_some() {
+            String::new()
+        } else {
+            args.file.context("file")?
+        },
         lang: Some(lang),
         init_prompt: args.init_prompt,
         n_threads: args.n_threads,
         temperature: args.temperature,
+        temperatures: args.temperatures,
+        compression_ratio_threshold: args.compression_ratio_threshold,
+        logprob_threshold: args.logprob_threshold,
+        no_speech_threshold: args.no_speech_threshold,
         translate: args.translate,
         verbose: Some(false),
         max_text_ctx: args.max_text_ctx,
@@ -197,44 +681,41 @@ pub async fn run(app_handle: &AppHandle) -> Result<()> {
         max_sentence_len: args.max_sentence_len,
         sampling_strategy: None,
         sampling_bestof_or_beam_size: None,
+        chunk_duration: args.chunk_duration,
+        chunk_overlap: args.chunk_overlap,
+        checkpoint: Some(args.checkpoint),
+        vad,
+        detect_no_speech: Some(args.detect_no_speech),
+        cleanup,
+        glossary: args.glossary.as_deref().map(Glossary::load).transpose()?,
+        multilingual: Some(args.multilingual),
+        languages: args.languages,
+        translation,
     };
     let model_path = prepare_model_path(&args.model.context("model")?, app_handle)?;
 
     eprintln!("Transcribe... 🔄");
     let start = Instant::now(); // Measure start time
     let ctx = transcribe::create_context(&model_path, None, None)?;
+    if let Some(folder) = args.folder {
+        transcribe_folder(&ctx, &folder, options, diarize_options, args.workers, &output)?;
+        app_handle.cleanup_before_exit();
+        eprintln!("Done ✅");
+        process::exit(0);
+    }
     #[allow(unused_mut)]
-    let mut transcript = transcribe::transcribe(&ctx, &options, None, None, None, None, None)?;
+    let mut transcript = transcribe::transcribe(&ctx, &options, None, None, None, diarize_options, None)?;
 
     let elapsed = start.elapsed();
-    println!(
-        "{}",
-        match args.format.as_str() {
-            "srt" => transcript.as_srt(),
-            "vtt" => transcript.as_vtt(),
-            "txt" => transcript.as_text(),
-            "json" => transcript.as_json()?,
-            _ => {
-                eprintln!("Invalid format specified. Defaulting to SRT format.");
-                transcript.as_srt()
-            }
-        }
-    );
+    if let (Some(language::AUTO), Some(detected)) = (options.lang.as_deref(), transcript.language.as_deref()) {
+        eprintln!("Detected language: {} 🌐", detected);
+    }
+    let content = output.render(&transcript)?;
+    println!("{}", content);
 
     // Write transcript if write path is provided
     if let Some(write_path) = args.write {
-        if let Err(err) = std::fs::write(
-            write_path,
-            match args.format.as_str() {
-                "srt" => transcript.as_srt(),
-                "vtt" => transcript.as_vtt(),
-                "txt" => transcript.as_text(),
-                _ => {
-                    eprintln!("Invalid format specified. Defaulting to SRT format.");
-                    transcript.as_srt()
-                }
-            },
-        ) {
+        if let Err(err) = std::fs::write(write_path, content) {
             eprintln!("Error writing transcript to file: {}", err);
         }
     }
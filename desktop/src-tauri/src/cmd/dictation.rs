@@ -5,12 +5,15 @@
 /// - Managing dictation history
 /// - Configuring dictation settings
 
+use crate::setup::ModelContext;
 use crate::text_input::{PasteResult, TextInput};
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
-use tauri::command;
+use tauri::{command, Manager};
+use tokio::sync::Mutex;
 use vibe_core::dictation_history::{DictationHistory, DictationHistoryEntry, NewDictationEntry};
+use vibe_core::glossary::Glossary;
 
 /// Result of a text paste operation (for frontend)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,6 +53,44 @@ pub async fn paste_text(text: String) -> Result<PasteResponse, String> {
     Ok(result.into())
 }
 
+/// Transcribe recorded dictation audio with the loaded model
+///
+/// # Arguments
+/// * `samples` - Raw i16 PCM samples at 16kHz mono
+/// * `language` - Optional language code. If None, auto-detect
+/// * `init_prompt` - Optional initial prompt to guide transcription style
+/// * `glossary` - Glossary of the dictation settings, added to the prompt and used to correct the text
+///
+/// # Returns
+/// * Transcribed text, or empty string if no speech detected
+#[command]
+pub async fn transcribe_dictation(
+    app_handle: tauri::AppHandle,
+    samples: Vec<i16>,
+    language: Option<String>,
+    init_prompt: Option<String>,
+    glossary: Option<Glossary>,
+) -> Result<String, String> {
+    tracing::debug!("transcribe_dictation command called with {} samples", samples.len());
+
+    // Whisper blocks, so it runs off the async runtime
+    tauri::async_runtime::spawn_blocking(move || {
+        let model_context_state = app_handle.state::<Mutex<Option<ModelContext>>>();
+        let model_context = model_context_state.blocking_lock();
+        let ctx = model_context.as_ref().ok_or_else(|| "Please load model first".to_string())?;
+        vibe_core::dictation_transcribe::transcribe_dictation(
+            &ctx.handle,
+            &samples,
+            language,
+            init_prompt,
+            glossary.as_ref(),
+        )
+        .map_err(|e| format!("Failed to transcribe dictation: {}", e))
+    })
+    .await
+    .map_err(|e| format!("Failed to run dictation transcription: {}", e))?
+}
+
 /// Get the name of the currently focused application
 ///
 /// # Returns
@@ -17,13 +17,25 @@ use tauri::{
 use tauri::{Emitter, Listener, State};
 use tauri_plugin_store::StoreExt;
 use tokio::sync::Mutex;
+use vibe_core::batch::{BatchEvent, BatchJob, BatchQueue, JobId, JobStatus};
+use vibe_core::diarize::DiarizeMode;
 use vibe_core::get_vibe_temp_folder;
+use vibe_core::glossary::Glossary;
+use vibe_core::language::{self, LanguageProbability};
+use vibe_core::speaker_profiles::SpeakerProfiles;
+use vibe_core::transcribe::Progress;
+use vibe_core::transcript::edit::Edit;
+use vibe_core::transcript::format::TranscriptFormat;
+use vibe_core::transcript::layout::SubtitleLayout;
+use vibe_core::transcript::speakers::SpeakerLabels;
 use vibe_core::transcript::Segment;
 use vibe_core::transcript::Transcript;
+use vibe_core::translate::TranslateOptions;
 
 pub mod audio;
 pub mod dictation;
 pub mod permissions;
+pub mod speakers;
 pub mod ytdlp;
 
 /// Return true if there's internet connection
@@ -190,6 +202,19 @@ pub struct DiarizeOptions {
     threshold: f32,
     max_speakers: usize,
     enabled: bool,
+    /// Exact number of speakers, when known
+    #[serde(default)]
+    speakers: Option<usize>,
+    #[serde(default)]
+    mode: DiarizeMode,
+    /// Speaker profiles database. Enrolled speakers are recognized by name
+    #[serde(default)]
+    profiles_db: Option<String>,
+    /// Minimum similarity to an enrolled speaker to use its name. The core default when not given
+    #[serde(default)]
+    profile_threshold: Option<f32>,
+    #[serde(default)]
+    detect_overlap: bool,
 }
 
 impl Default for DiarizeOptions {
@@ -198,6 +223,11 @@ impl Default for DiarizeOptions {
             enabled: false,
             threshold: 0.0,
             max_speakers: 0,
+            speakers: None,
+            mode: DiarizeMode::default(),
+            profiles_db: None,
+            profile_threshold: None,
+            detect_overlap: false,
         }
     }
 }
@@ -231,35 +261,57 @@ impl FfmpegOptions {
 
 #[tauri::command]
 pub async fn glob_files(folder: String, patterns: Vec<String>, recursive: bool) -> Vec<String> {
-    let mut files = Vec::new();
-
-    // Construct the search pattern based on the recursive flag
-    let search_pattern = if recursive {
-        format!("{}/**/*", folder) // Recursive search
-    } else {
-        format!("{}/*", folder) // Non-recursive search (only in the folder)
-    };
-
-    match glob::glob(&search_pattern) {
-        Ok(paths) => {
-            for entry in paths.filter_map(Result::ok) {
-                if entry.is_file() {
-                    if let Some(file_name) = entry.file_name().and_then(|n| n.to_str()) {
-                        if patterns.iter().any(|p| file_name.ends_with(p)) {
-                            if let Ok(path_str) = entry.into_os_string().into_string() {
-                                files.push(path_str);
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    match vibe_core::batch::glob_files(Path::new(&folder), &patterns, recursive) {
+        Ok(files) => files
+            .into_iter()
+            .filter_map(|path| path.into_os_string().into_string().ok())
+            .collect(),
         Err(e) => {
-            eprintln!("Failed to read pattern {}: {}", search_pattern, e);
+            eprintln!("Failed to read folder {}: {}", folder, e);
+            Vec::new()
         }
     }
+}
 
-    files
+/// Paths of the segmentation and the speaker embedding models
+fn get_diarize_model_paths(app_handle: &tauri::AppHandle) -> Result<(String, String)> {
+    let embedding_model_path = get_models_folder(app_handle.clone())?
+        .join(crate::config::EMBEDDING_MODEL_FILENAME)
+        .to_str()
+        .ok_or_eyre("tostr")?
+        .to_string();
+
+    let segment_model_path = get_models_folder(app_handle.clone())?
+        .join(crate::config::SEGMENT_MODEL_FILENAME)
+        .to_str()
+        .ok_or_eyre("tostr")?
+        .to_string();
+    Ok((segment_model_path, embedding_model_path))
+}
+
+fn get_core_diarize_options(
+    app_handle: &tauri::AppHandle,
+    diarize_options: &DiarizeOptions,
+) -> Result<Option<vibe_core::transcribe::DiarizeOptions>> {
+    if !diarize_options.enabled {
+        return Ok(None);
+    }
+    let (segment_model_path, embedding_model_path) = get_diarize_model_paths(app_handle)?;
+    let profiles = match diarize_options.profiles_db {
+        Some(ref db_path) if Path::new(db_path).exists() => SpeakerProfiles::new(Path::new(db_path))?.get_all()?,
+        _ => Vec::new(),
+    };
+    Ok(Some(vibe_core::transcribe::DiarizeOptions {
+        embedding_model_path,
+        segment_model_path,
+        max_speakers: diarize_options.max_speakers,
+        threshold: diarize_options.threshold,
+        speakers: diarize_options.speakers,
+        mode: diarize_options.mode,
+        profiles,
+        profile_threshold: diarize_options.profile_threshold.unwrap_or(vibe_core::diarize::DEFAULT_PROFILE_THRESHOLD),
+        detect_overlap: diarize_options.detect_overlap,
+    }))
 }
 
 #[tauri::command]
@@ -298,32 +350,14 @@ pub async fn transcribe(
 
     let app_handle_c = app_handle.clone();
     let app_handle_c1 = app_handle.clone();
-    let progress_callback = move |progress: i32| {
-        let _ = set_progress_bar(&app_handle, Some(progress.into()));
+    let progress_callback = move |progress: Progress| {
+        let _ = app_handle.emit_to("main", "transcribe_phase", progress.phase);
+        let _ = set_progress_bar(&app_handle, Some(progress.percent.into()));
     };
 
     // prevent panic crash. sometimes whisper.cpp crash without nice errors.
 
-    let mut core_diarize_options = None;
-    if diarize_options.enabled {
-        let embedding_model_path = get_models_folder(app_handle_c1.clone())?
-            .join(crate::config::EMBEDDING_MODEL_FILENAME)
-            .to_str()
-            .ok_or_eyre("tostr")?
-            .to_string();
-
-        let segment_model_path = get_models_folder(app_handle_c1.clone())?
-            .join(crate::config::SEGMENT_MODEL_FILENAME)
-            .to_str()
-            .ok_or_eyre("tostr")?
-            .to_string();
-        core_diarize_options = Some(vibe_core::transcribe::DiarizeOptions {
-            embedding_model_path,
-            segment_model_path,
-            max_speakers: diarize_options.max_speakers,
-            threshold: diarize_options.threshold,
-        });
-    }
+    let core_diarize_options = get_core_diarize_options(&app_handle_c1, &diarize_options)?;
     let ffmpeg_options = ffmpeg_options.to_vec();
     tracing::debug!("ffmpeg additional options: {:?}", ffmpeg_options);
     let unwind_result = catch_unwind(AssertUnwindSafe(|| {
@@ -350,6 +384,149 @@ pub async fn transcribe(
     }
 }
 
+/// Rank the languages spoken in the first seconds of a file with the loaded model
+#[tauri::command]
+pub async fn detect_language(
+    path: String,
+    seconds: Option<u32>,
+    model_context_state: State<'_, Mutex<Option<ModelContext>>>,
+) -> Result<Vec<LanguageProbability>> {
+    let model_context = model_context_state.lock().await;
+    let ctx = model_context.as_ref().context("Please load model first")?;
+    let path = PathBuf::from(path);
+    let wav_path = if vibe_core::transcribe::should_normalize(path.clone()) {
+        vibe_core::transcribe::create_normalized_audio(path, None)?
+    } else {
+        path
+    };
+    let seconds = seconds.unwrap_or(language::DEFAULT_DETECT_SECONDS);
+    language::detect_language_from_file(&ctx.handle, &wav_path, seconds, 4)
+}
+
+/// Transcribe many files with parallel workers sharing the loaded model.
+/// Emits `batch_event` for every job status, progress and segment.
+/// Listens to `batch_cancel`, `batch_pause` and `batch_resume` with the job id as payload.
+#[tauri::command]
+pub async fn transcribe_batch(
+    app_handle: tauri::AppHandle,
+    paths: Vec<String>,
+    options: vibe_core::config::TranscribeOptions,
+    workers: Option<usize>,
+    diarize_options: DiarizeOptions,
+    ffmpeg_options: FfmpegOptions,
+) -> Result<Vec<JobStatus>> {
+    let core_diarize_options = get_core_diarize_options(&app_handle, &diarize_options)?;
+    let ffmpeg_options = ffmpeg_options.to_vec();
+    let queue = BatchQueue::new();
+    for path in paths {
+        let mut options = options.clone();
+        options.path = path;
+        queue.submit(BatchJob {
+            options,
+            diarize_options: core_diarize_options.clone(),
+            additional_ffmpeg_args: Some(ffmpeg_options.clone()),
+        });
+    }
+
+    let controls: [(&str, fn(&BatchQueue, JobId) -> Result<()>); 3] = [
+        ("batch_cancel", BatchQueue::cancel),
+        ("batch_pause", BatchQueue::pause),
+        ("batch_resume", BatchQueue::resume),
+    ];
+    let listeners: Vec<_> = controls
+        .into_iter()
+        .map(|(event_name, control)| {
+            let queue = queue.clone();
+            app_handle.listen(event_name, move |event| {
+                match serde_json::from_str::<JobId>(event.payload()) {
+                    Ok(id) => {
+                        control(&queue, id).log_error();
+                    }
+                    Err(error) => tracing::error!("invalid job id {:?}: {:?}", event.payload(), error),
+                }
+            })
+        })
+        .collect();
+
+    // Transcribing blocks, and so does a paused job, so the batch runs off the async runtime.
+    // The model stays locked until every job is done
+    let app_handle_c = app_handle.clone();
+    let queue_c = queue.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<()> {
+        let model_context_state = app_handle_c.state::<Mutex<Option<ModelContext>>>();
+        let model_context = model_context_state.blocking_lock();
+        let ctx = model_context.as_ref().context("Please load model first")?;
+        queue_c.run(&ctx.handle, workers.unwrap_or(2), |event: BatchEvent| {
+            app_handle_c
+                .emit_to("main", "batch_event", event)
+                .map_err(|e| eyre!("{:?}", e))
+                .log_error();
+        });
+        Ok(())
+    })
+    .await;
+
+    for listener in listeners {
+        app_handle.unlisten(listener);
+    }
+    result.map_err(|e| eyre!("{:?}", e))??;
+    Ok(queue.statuses())
+}
+
+/// Render segments in one of the transcript formats
+#[tauri::command]
+pub fn format_transcript(segments: Vec<Segment>, format: TranscriptFormat, speakers: Option<SpeakerLabels>) -> Result<String> {
+    let transcript = Transcript {
+        processing_time_sec: 0,
+        segments,
+        language: None,
+    };
+    transcript.format_with_speakers(format, &speakers.unwrap_or_default())
+}
+
+/// Load a glossary file for `TranscribeOptions::glossary`
+#[tauri::command]
+pub fn load_glossary(path: String) -> Result<Glossary> {
+    Glossary::load(Path::new(&path))
+}
+
+/// Apply an editing operation to segments
+#[tauri::command]
+pub fn edit_segments(segments: Vec<Segment>, edit: Edit) -> Result<Vec<Segment>> {
+    let transcript = Transcript {
+        processing_time_sec: 0,
+        segments,
+        language: None,
+    };
+    Ok(transcript.apply(&edit)?.segments)
+}
+
+/// Translate segments with a translation backend
+#[tauri::command]
+pub async fn translate_segments(segments: Vec<Segment>, options: TranslateOptions) -> Result<Vec<Segment>> {
+    let transcript = Transcript {
+        processing_time_sec: 0,
+        segments,
+        language: None,
+    };
+    // Backends run a process or wait for http requests, so they don't block the async runtime
+    let translated = tauri::async_runtime::spawn_blocking(move || transcript.translate(&options))
+        .await
+        .map_err(|e| eyre!("{:?}", e))??;
+    Ok(translated.segments)
+}
+
+/// Split and reflow segments into subtitle cues
+#[tauri::command]
+pub fn layout_segments(segments: Vec<Segment>, layout: SubtitleLayout) -> Vec<Segment> {
+    let transcript = Transcript {
+        processing_time_sec: 0,
+        segments,
+        language: None,
+    };
+    transcript.layout(&layout).segments
+}
+
 #[tauri::command]
 pub fn get_path_dst(src: String, suffix: String) -> Result<String> {
     let src = PathBuf::from(src);
@@ -0,0 +1,100 @@
+/// Speaker profile Tauri commands
+///
+/// Provides commands for enrolling named voices from sample clips,
+/// which diarization then recognizes across recordings
+
+use std::path::PathBuf;
+use tauri::command;
+use vibe_core::speaker_profiles::{self, SpeakerProfile, SpeakerProfiles};
+
+/// Get all enrolled speakers
+///
+/// # Arguments
+/// * `db_path` - Path to the speaker profiles database
+///
+/// # Returns
+/// * Vector of speaker profiles ordered by name
+#[command]
+pub async fn get_speaker_profiles(db_path: String) -> Result<Vec<SpeakerProfile>, String> {
+    tracing::debug!("get_speaker_profiles command called");
+
+    let path = PathBuf::from(db_path);
+    let profiles = SpeakerProfiles::new(&path).map_err(|e| format!("Failed to open speaker profiles database: {}", e))?;
+
+    profiles
+        .get_all()
+        .map_err(|e| format!("Failed to get speaker profiles: {}", e))
+}
+
+/// Enroll a voice from a clip of a single speaker. Enrolling an existing name again improves its profile
+///
+/// # Arguments
+/// * `db_path` - Path to the speaker profiles database
+/// * `name` - Name of the speaker
+/// * `path` - Audio or video file with only this speaker
+///
+/// # Returns
+/// * ID of the profile
+#[command]
+pub async fn enroll_speaker(app_handle: tauri::AppHandle, db_path: String, name: String, path: String) -> Result<i64, String> {
+    tracing::debug!("enroll_speaker command called for {}", name);
+
+    let (segment_model_path, embedding_model_path) =
+        super::get_diarize_model_paths(&app_handle).map_err(|e| format!("Failed to get diarize models: {}", e))?;
+    let path = PathBuf::from(path);
+    let wav_path = if vibe_core::transcribe::should_normalize(path.clone()) {
+        vibe_core::transcribe::create_normalized_audio(path, None).map_err(|e| format!("Failed to normalize audio: {}", e))?
+    } else {
+        path
+    };
+    let samples = vibe_core::audio::parse_wav_file(&wav_path).map_err(|e| format!("Failed to read audio: {}", e))?;
+    let embedding = speaker_profiles::clip_embedding(&samples, &segment_model_path, &embedding_model_path)
+        .map_err(|e| format!("Failed to compute voice embedding: {}", e))?;
+
+    let profiles = SpeakerProfiles::new(&PathBuf::from(db_path))
+        .map_err(|e| format!("Failed to open speaker profiles database: {}", e))?;
+    profiles
+        .enroll(&name, &embedding)
+        .map_err(|e| format!("Failed to enroll speaker: {}", e))
+}
+
+/// Rename an enrolled speaker
+///
+/// # Arguments
+/// * `db_path` - Path to the speaker profiles database
+/// * `id` - ID of the profile
+/// * `name` - New name
+///
+/// # Returns
+/// * Unit result
+#[command]
+pub async fn rename_speaker_profile(db_path: String, id: i64, name: String) -> Result<(), String> {
+    tracing::debug!("rename_speaker_profile command called for id: {}", id);
+
+    let path = PathBuf::from(db_path);
+    let profiles = SpeakerProfiles::new(&path).map_err(|e| format!("Failed to open speaker profiles database: {}", e))?;
+
+    profiles
+        .rename(id, &name)
+        .map_err(|e| format!("Failed to rename speaker profile: {}", e))
+}
+
+/// Delete an enrolled speaker
+///
+/// # Arguments
+/// * `db_path` - Path to the speaker profiles database
+/// * `id` - ID of the profile
+///
+/// # Returns
+/// * Unit result
+#[command]
+pub async fn delete_speaker_profile(db_path: String, id: i64) -> Result<(), String> {
+    tracing::debug!("delete_speaker_profile command called for id: {}", id);
+
+    let path = PathBuf::from(db_path);
+    let profiles = SpeakerProfiles::new(&path).map_err(|e| format!("Failed to open speaker profiles database: {}", e))?;
+
+    profiles
+        .delete(id)
+        .map_err(|e| format!("Failed to delete speaker profile: {}", e))
+}
@@ -2,6 +2,7 @@ use eyre::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
+use vibe_core::glossary::Glossary;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +14,9 @@ pub struct DictationSettings {
     pub model_name: String,
     pub show_floating_widget: bool,
     pub audio_feedback_enabled: bool,
+    /// Terms added to the prompt and corrected in the dictated text
+    #[serde(default)]
+    pub glossary: Option<Glossary>,
 }
 
 impl Default for DictationSettings {
@@ -28,6 +32,7 @@ impl Default for DictationSettings {
             model_name: "small".to_string(),
             show_floating_widget: true,
             audio_feedback_enabled: true,
+            glossary: None,
         }
     }
 }
@@ -124,6 +129,7 @@ mod tests {
             model_name: "medium".to_string(),
             show_floating_widget: false,
             audio_feedback_enabled: false,
+            glossary: Some(Glossary::parse("Kubernetes").unwrap()),
         };
 
         settings.save(&settings_path).unwrap();
@@ -135,6 +141,7 @@ mod tests {
         assert_eq!(loaded.model_name, "medium");
         assert!(!loaded.show_floating_widget);
         assert!(!loaded.audio_feedback_enabled);
+        assert_eq!(loaded.glossary, settings.glossary);
     }
 
     #[test]
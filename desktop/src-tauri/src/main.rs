@@ -74,6 +74,8 @@ fn main() -> Result<()> {
             cmd::download_file,
             cmd::get_cargo_features,
             cmd::transcribe,
+            cmd::detect_language,
+            cmd::transcribe_batch,
             cmd::glob_files,
             cmd::download_model,
             cmd::load_model,
@@ -83,6 +85,11 @@ fn main() -> Result<()> {
             cmd::is_avx2_enabled,
             cmd::is_online,
             cmd::get_path_dst,
+            cmd::format_transcript,
+            cmd::layout_segments,
+            cmd::edit_segments,
+            cmd::load_glossary,
+            cmd::translate_segments,
             cmd::get_logs,
             cmd::open_path,
             cmd::get_x86_features,
@@ -102,6 +109,7 @@ fn main() -> Result<()> {
             cmd::is_crashed_recently,
             cmd::rename_crash_file,
             cmd::dictation::paste_text,
+            cmd::dictation::transcribe_dictation,
             cmd::dictation::get_active_app,
             cmd::dictation::can_paste,
             cmd::dictation::get_dictation_history,
@@ -110,6 +118,10 @@ fn main() -> Result<()> {
             cmd::dictation::update_dictation_entry,
             cmd::dictation::delete_dictation_entry,
             cmd::dictation::cleanup_old_history,
+            cmd::speakers::get_speaker_profiles,
+            cmd::speakers::enroll_speaker,
+            cmd::speakers::rename_speaker_profile,
+            cmd::speakers::delete_speaker_profile,
             cmd::permissions::check_dictation_accessibility_permission,
             cmd::permissions::check_dictation_microphone_permission,
             cmd::permissions::get_dictation_accessibility_instructions,
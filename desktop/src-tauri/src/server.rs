@@ -1,8 +1,8 @@
 use crate::cmd::{self, DiarizeOptions, FfmpegOptions};
 use crate::setup::ModelContext;
-use axum::extract::State;
-use axum::http::StatusCode;
-use axum::response::Result;
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response, Result};
 use axum::routing::post;
 use axum::Json;
 use axum::{routing::get, Router};
@@ -14,12 +14,13 @@ use tokio::sync::Mutex;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 use vibe_core::config::TranscribeOptions;
+use vibe_core::transcript::format::TranscriptFormat;
 use vibe_core::transcript::{Segment, Transcript};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(list_models, load, transcribe),
-    components(schemas(TranscribeOptions, LoadPayload, Transcript, Segment))
+    components(schemas(TranscribeOptions, LoadPayload, Transcript, Segment, TranscriptFormat))
 )]
 struct ApiDoc;
 
@@ -90,18 +91,40 @@ async fn list_models(State(app_handle): State<tauri::AppHandle>) -> Result<Json<
     Ok(Json(Value::Array(model_files.into_iter().map(Value::String).collect())))
 }
 
+#[derive(Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TranscribeQuery {
+    /// Respond with the transcript in this format instead of json
+    format: Option<TranscriptFormat>,
+}
+
+fn content_type(format: TranscriptFormat) -> &'static str {
+    match format {
+        TranscriptFormat::Json => "application/json",
+        TranscriptFormat::Srt => "application/x-subrip",
+        TranscriptFormat::Vtt => "text/vtt",
+        TranscriptFormat::Tsv => "text/tab-separated-values",
+        TranscriptFormat::Csv => "text/csv",
+        TranscriptFormat::Ttml => "application/ttml+xml",
+        TranscriptFormat::Md => "text/markdown",
+        TranscriptFormat::Txt | TranscriptFormat::Lrc | TranscriptFormat::Sbv => "text/plain",
+    }
+}
+
 /// Transcribe file
 #[utoipa::path(
 	post,
 	path = "/transcribe",
+	params(TranscribeQuery),
 	responses(
 		(status = 200, description = "List all models", body = Transcript)
 	)
 )]
 async fn transcribe(
     State(app_handle): State<tauri::AppHandle>,
+    Query(query): Query<TranscribeQuery>,
     Json(payload): Json<TranscribeOptions>,
-) -> Result<Json<Transcript>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     let model_context_state: tauri::State<'_, Mutex<Option<ModelContext>>> = app_handle.state();
     let transcript = cmd::transcribe(
         app_handle.clone(),
@@ -113,5 +136,13 @@ async fn transcribe(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    Ok(Json(transcript))
+    match query.format {
+        Some(format) => {
+            let content = transcript
+                .format(format)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            Ok(([(header::CONTENT_TYPE, content_type(format))], content).into_response())
+        }
+        None => Ok(Json(transcript).into_response()),
+    }
 }
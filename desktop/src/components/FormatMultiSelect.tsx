@@ -1,7 +1,7 @@
 import { Dispatch, SetStateAction } from 'react'
 import { useTranslation } from 'react-i18next'
 
-export type TextFormat = 'normal' | 'srt' | 'vtt' | 'html' | 'pdf' | 'json' | 'docx'
+export type TextFormat = 'normal' | 'srt' | 'vtt' | 'html' | 'pdf' | 'json' | 'docx' | 'tsv' | 'csv' | 'lrc' | 'ttml' | 'sbv' | 'md'
 export type FormatExtensions = {
 	[name in TextFormat]: string
 }
@@ -13,6 +13,12 @@ export const formatExtensions: FormatExtensions = {
 	pdf: '.pdf',
 	json: '.json',
 	docx: '.docx',
+	tsv: '.tsv',
+	csv: '.csv',
+	lrc: '.lrc',
+	ttml: '.ttml',
+	sbv: '.sbv',
+	md: '.md',
 }
 
 interface FormatMultiSelectProps {
@@ -40,7 +46,7 @@ export default function FormatMultiSelect({ formats, setFormats }: FormatMultiSe
 			</div>
 
 			<div className="flex flex-wrap gap-2 justify-center">
-				{['normal', 'srt', 'docx', 'vtt', 'json'].map((formatOption) => (
+				{['normal', 'srt', 'docx', 'vtt', 'json', 'tsv', 'csv', 'lrc', 'ttml', 'sbv', 'md'].map((formatOption) => (
 					<button
 						key={formatOption}
 						className={`btn btn-xs ${formats.includes(formatOption as TextFormat) ? 'btn-primary' : ''}`}
@@ -1,7 +1,7 @@
 import { Dispatch, SetStateAction } from 'react'
 import { useTranslation } from 'react-i18next'
 
-export type TextFormat = 'normal' | 'srt' | 'vtt' | 'html' | 'pdf' | 'json' | 'docx'
+export type TextFormat = 'normal' | 'srt' | 'vtt' | 'html' | 'pdf' | 'json' | 'docx' | 'tsv' | 'csv' | 'lrc' | 'ttml' | 'sbv' | 'md'
 export type FormatExtensions = {
 	[name in TextFormat]: string
 }
@@ -13,8 +13,17 @@ export const formatExtensions: FormatExtensions = {
 	pdf: '.pdf',
 	json: '.json',
 	docx: '.docx',
+	tsv: '.tsv',
+	csv: '.csv',
+	lrc: '.lrc',
+	ttml: '.ttml',
+	sbv: '.sbv',
+	md: '.md',
 }
 
+// Formats rendered by the transcript writers of the core
+export const coreFormats: TextFormat[] = ['tsv', 'csv', 'lrc', 'ttml', 'sbv', 'md']
+
 interface FormatSelectProps {
 	format: TextFormat
 	setFormat: Dispatch<SetStateAction<TextFormat>>
@@ -37,6 +46,11 @@ export default function FormatSelect({ format, setFormat }: FormatSelectProps) {
 				<option value="docx">docx</option>
 				<option value="vtt">vtt</option>
 				<option value="json">json</option>
+				{coreFormats.map((format) => (
+					<option key={format} value={format}>
+						{format}
+					</option>
+				))}
 			</select>
 		</label>
 	)
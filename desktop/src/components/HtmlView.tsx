@@ -49,7 +49,7 @@ export default function HTMLView({ segments, file, preference }: HTMLViewProps)
 						<div className="timestamp" style={{ fontSize: '13px', paddingBottom: '6px', opacity: 0.7 }}>
 							{formatDuration(segment.start, segment.stop)}
 						</div>
-						{segment.speaker ? formatSpeaker(segment.speaker, t('common.speaker-prefix')) : ''}
+						{segment.speaker ? formatSpeaker(segment.speaker, t('common.speaker-prefix'), segment.overlap) : ''}
 						{segment.speaker && <br />}
 						{segment.text}
 					</div>
@@ -4,7 +4,7 @@ import { ReactComponent as ChevronDown } from '~/icons/chevron-down.svg'
 import { ReactComponent as ChevronUp } from '~/icons/chevron-up.svg'
 import { ModifyState, cx } from '~/lib/utils'
 import { InfoTooltip } from './InfoTooltip'
-import { ModelOptions as IModelOptions, usePreferenceProvider } from '~/providers/Preference'
+import { DiarizeMode, Glossary, ModelOptions as IModelOptions, usePreferenceProvider } from '~/providers/Preference'
 import { useToastProvider } from '~/providers/Toast'
 import { listen } from '@tauri-apps/api/event'
 import { ask } from '@tauri-apps/plugin-dialog'
@@ -17,6 +17,8 @@ import { toast as hotToast } from 'react-hot-toast'
 
 import * as dialog from '@tauri-apps/plugin-dialog'
 import { Claude, defaultClaudeConfig, defaultOllamaConfig, Llm, Ollama } from '~/lib/llm'
+import { defaultSubtitleLayout, SubtitleLayout } from '~/lib/transcript'
+import { deleteSpeakerProfile, enrollSpeaker, getSpeakerProfiles, SpeakerProfile } from '~/lib/speakers'
 
 interface ParamsProps {
 	options: IModelOptions
@@ -29,6 +31,8 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 	const { t } = useTranslation()
 	const toast = useToastProvider()
 	const [llm, setLlm] = useState<Llm | null>(null)
+	const [speakerProfiles, setSpeakerProfiles] = useState<SpeakerProfile[]>([])
+	const [enrollName, setEnrollName] = useState('')
 
 	useEffect(() => {
 		if (preference.llmConfig?.platform === 'ollama') {
@@ -46,6 +50,31 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 		}
 	}, [preference.recognizeSpeakers, options.word_timestamps])
 
+	useEffect(() => {
+		if (preference.recognizeSpeakers && isTauri()) {
+			getSpeakerProfiles().then(setSpeakerProfiles).catch(console.error)
+		}
+	}, [preference.recognizeSpeakers])
+
+	async function selectEnrollClip() {
+		const selected = await dialog.open({ multiple: false })
+		if (!selected || !enrollName.trim()) {
+			return
+		}
+		try {
+			await enrollSpeaker(enrollName.trim(), selected)
+			setEnrollName('')
+			setSpeakerProfiles(await getSpeakerProfiles())
+		} catch (error) {
+			await dialog.message(String(error), { kind: 'error' })
+		}
+	}
+
+	async function onDeleteSpeakerProfile(id: number) {
+		await deleteSpeakerProfile(id)
+		setSpeakerProfiles(await getSpeakerProfiles())
+	}
+
 	async function handleProgressEvents() {
 		if (!isTauri()) {
 			return
@@ -114,6 +143,22 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 		return valid
 	}
 
+	async function selectGlossary() {
+		const selected = await dialog.open({
+			multiple: false,
+			filters: [{ name: 'Glossary', extensions: ['txt', 'json'] }],
+		})
+		if (!selected) {
+			return
+		}
+		try {
+			const glossary = await invoke<Glossary>('load_glossary', { path: selected })
+			setOptions({ ...options, glossary })
+		} catch (error) {
+			await dialog.message(String(error), { kind: 'error' })
+		}
+	}
+
 	const llmConfig = preference.llmConfig
 	const setLlmConfig = preference.setLlmConfig
 
@@ -175,6 +220,53 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 						/>
 					</label>
 
+					<label className="form-control w-full">
+						<div className="label">
+							<span className="label-text flex items-center gap-1">
+								<InfoTooltip text={t('common.info-diarize-mode')} />
+								{t('common.diarize-mode')}
+							</span>
+						</div>
+						<select
+							value={preference.diarizeMode}
+							onChange={(e) => preference.setDiarizeMode(e.target.value as DiarizeMode)}
+							className="select select-bordered">
+							<option value="segments">{t('common.diarize-mode-segments')}</option>
+							<option value="words">{t('common.diarize-mode-words')}</option>
+						</select>
+					</label>
+
+					<div className="form-control w-full mt-2">
+						<label className="label cursor-pointer">
+							<span className="label-text flex items-center gap-1 cursor-default">
+								<InfoTooltip text={t('common.info-detect-overlap')} />
+								{t('common.detect-overlap')}
+							</span>
+							<input
+								type="checkbox"
+								className="toggle toggle-primary"
+								checked={preference.detectOverlap}
+								onChange={(e) => preference.setDetectOverlap(e.target.checked)}
+							/>
+						</label>
+					</div>
+
+					<label className="form-control w-full">
+						<div className="label">
+							<span className="label-text flex items-center gap-1">
+								<InfoTooltip text={t('common.info-number-of-speakers')} />
+								{t('common.number-of-speakers')}
+							</span>
+						</div>
+						<input
+							onChange={(e) => preference.setNumberOfSpeakers(parseInt(e.target.value) || 0)}
+							value={preference.numberOfSpeakers}
+							className="input input-bordered"
+							type="number"
+							min={0}
+						/>
+					</label>
+
 					<label className="form-control w-full">
 						<div className="label">
 							<span className="label-text flex items-center gap-1">
@@ -193,6 +285,34 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 						/>
 					</label>
 
+					<div className="form-control w-full">
+						<div className="label">
+							<span className="label-text flex items-center gap-1">
+								<InfoTooltip text={t('common.info-speaker-profiles')} />
+								{t('common.speaker-profiles')}
+							</span>
+						</div>
+						{speakerProfiles.map((profile) => (
+							<div key={profile.id} className="flex items-center justify-between gap-2">
+								<span>{profile.name}</span>
+								<button onClick={() => onDeleteSpeakerProfile(profile.id)} className="btn btn-sm btn-ghost">
+									{t('common.delete')}
+								</button>
+							</div>
+						))}
+						<div className="flex gap-2 mt-2">
+							<input
+								value={enrollName}
+								onChange={(e) => setEnrollName(e.target.value)}
+								placeholder={t('common.speaker-name')}
+								className="input input-bordered input-sm flex-1"
+							/>
+							<button onClick={selectEnrollClip} disabled={!enrollName.trim()} className="btn btn-sm btn-outline">
+								{t('common.enroll-speaker')}
+							</button>
+						</div>
+					</div>
+
 					<div className="label mt-10">
 						<span className="label-text text-2xl font-bold">{t('common.process-with-llm')} ✨</span>
 					</div>
@@ -307,6 +427,26 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 							className="textarea textarea-bordered w-full"></textarea>
 					</label>
 
+					<div className="form-control w-full mt-3">
+						<div className="label">
+							<span className="label-text flex items-center gap-1">
+								<InfoTooltip text={t('common.info-glossary')} />
+								{t('common.glossary')}
+								{options.glossary ? ` (${options.glossary.terms.length} ${t('common.terms')})` : ''}
+							</span>
+						</div>
+						<div className="flex gap-2">
+							<button onClick={selectGlossary} className="btn btn-sm btn-outline flex-1">
+								{t('common.select-glossary')}
+							</button>
+							{options.glossary && (
+								<button onClick={() => setOptions({ ...options, glossary: null })} className="btn btn-sm btn-ghost">
+									{t('common.clear')}
+								</button>
+							)}
+						</div>
+					</div>
+
 					<label className="form-control w-full">
 						<div className="label">
 							<span className="label-text flex items-center gap-1">
@@ -372,6 +512,22 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 							className="textarea textarea-bordered w-full"></textarea>
 					</label>
 
+					<div className="form-control w-full mt-3">
+						<label className="label cursor-pointer">
+							<span className="label-text flex items-center gap-1 cursor-default">
+								<InfoTooltip text={t('common.info-multilingual')} />
+								{t('common.multilingual')}
+							</span>
+
+							<input
+								type="checkbox"
+								className="toggle toggle-primary"
+								checked={options.multilingual ?? false}
+								onChange={(e) => setOptions({ ...options, multilingual: e.target.checked })}
+							/>
+						</label>
+					</div>
+
 					<div className="form-control w-full mt-3">
 						<label className="label cursor-pointer">
 							<span className="label-text flex items-center gap-1 cursor-default">
@@ -406,6 +562,45 @@ export default function ModelOptions({ options, setOptions }: ParamsProps) {
 							type="number"
 						/>
 					</label>
+					<div className="form-control w-full mt-3">
+						<label className="label cursor-pointer">
+							<span className="label-text flex items-center gap-1 cursor-default">
+								<InfoTooltip text={t('common.info-subtitle-layout')} />
+								{t('common.subtitle-layout')}
+							</span>
+							<input
+								type="checkbox"
+								className="toggle toggle-primary"
+								checked={preference.subtitleLayout !== null}
+								onChange={(e) => preference.setSubtitleLayout(e.target.checked ? defaultSubtitleLayout : null)}
+							/>
+						</label>
+					</div>
+					{preference.subtitleLayout &&
+						(
+							[
+								['max_chars_per_line', 'max-chars-per-line', 1],
+								['max_lines', 'max-lines', 1],
+								['min_duration', 'min-cue-duration', 0.1],
+								['max_duration', 'max-cue-duration', 0.1],
+								['max_chars_per_second', 'max-chars-per-second', 1],
+							] as [keyof SubtitleLayout, string, number][]
+						).map(([key, label, step]) => (
+							<label key={key} className="form-control w-full">
+								<div className="label">
+									<span className="label-text">{t(`common.${label}`)}</span>
+								</div>
+								<input
+									step={step}
+									value={preference.subtitleLayout?.[key]}
+									onChange={(e) =>
+										preference.setSubtitleLayout({ ...preference.subtitleLayout!, [key]: parseFloat(e.target.value) || defaultSubtitleLayout[key] })
+									}
+									className="input input-bordered"
+									type="number"
+								/>
+							</label>
+						))}
 					<label className="form-control w-full">
 						<div className="label">
 							<span className="label-text flex items-center gap-1">
@@ -6,9 +6,9 @@ import { ReactComponent as AlignRightIcon } from '~/icons/align-right.svg'
 import { ReactComponent as CopyIcon } from '~/icons/copy.svg'
 import { ReactComponent as DownloadIcon } from '~/icons/download.svg'
 import { ReactComponent as PrintIcon } from '~/icons/print.svg'
-import { Segment, asJson, asSrt, asText, asVtt } from '~/lib/transcript'
+import { Segment, asJson, asSrt, asSubtitles, asText, asVtt, formatSegments, subtitleFormats } from '~/lib/transcript'
 import { ModifyState, NamedPath, cx, openPath } from '~/lib/utils'
-import { TextFormat, formatExtensions } from './FormatSelect'
+import { TextFormat, coreFormats, formatExtensions } from './FormatSelect'
 import { usePreferenceProvider } from '~/providers/Preference'
 import HTMLView from './HtmlView'
 import toast from 'react-hot-toast'
@@ -146,20 +146,32 @@ export default function TextArea({
 	const segmentsInFocusRef = useRef<boolean>(false)
 
 	useEffect(() => {
+		async function render(segments: Segment[]) {
+			if (preference.subtitleLayout && subtitleFormats.includes(preference.textFormat)) {
+				return await asSubtitles(segments, preference.textFormat, preference.subtitleLayout, { prefix: t('common.speaker-prefix') })
+			}
+			if (coreFormats.includes(preference.textFormat)) {
+				return await formatSegments(segments, preference.textFormat, { prefix: t('common.speaker-prefix') })
+			}
+			return preference.textFormat === 'vtt'
+				? asVtt(segments, t('common.speaker-prefix'))
+				: preference.textFormat === 'srt'
+				? asSrt(segments, t('common.speaker-prefix'))
+				: preference.textFormat === 'json'
+				? asJson(segments)
+				: asText(segments, t('common.speaker-prefix'))
+		}
 		if (segments) {
-			setText(
-				preference.textFormat === 'vtt'
-					? asVtt(segments, t('common.speaker-prefix'))
-					: preference.textFormat === 'srt'
-					? asSrt(segments, t('common.speaker-prefix'))
-					: preference.textFormat === 'json'
-					? asJson(segments)
-					: asText(segments, t('common.speaker-prefix'))
-			)
+			render(segments)
+				.then(setText)
+				.catch((error) => {
+					console.error(error)
+					toast.error(String(error))
+				})
 		} else {
 			setText('')
 		}
-	}, [preference.textFormat, segments])
+	}, [preference.textFormat, preference.subtitleLayout, segments])
 
 	async function download(text: string, format: TextFormat, file: NamedPath) {
 		if (format === 'html') {
@@ -313,6 +325,11 @@ export default function TextArea({
 						<option value="srt">srt</option>
 						<option value="vtt">vtt</option>
 						<option value="json">json</option>
+						{coreFormats.map((format) => (
+							<option key={format} value={format}>
+								{format}
+							</option>
+						))}
 					</select>
 				</div>
 			</div>
@@ -0,0 +1,33 @@
+import { invoke } from '@tauri-apps/api/core'
+import * as path from '@tauri-apps/api/path'
+
+// Named voice recognized by diarization
+export interface SpeakerProfile {
+	id: number
+	name: string
+	created_at: string
+	// Number of clips averaged into the profile
+	clips: number
+}
+
+// Stored next to the dictation history
+export async function getSpeakerProfilesDbPath() {
+	return await path.join(await path.appDataDir(), 'speaker_profiles.db')
+}
+
+export async function getSpeakerProfiles() {
+	return await invoke<SpeakerProfile[]>('get_speaker_profiles', { dbPath: await getSpeakerProfilesDbPath() })
+}
+
+// Enroll the voice of a clip with only this speaker. Enrolling a name again improves its profile
+export async function enrollSpeaker(name: string, clipPath: string) {
+	return await invoke<number>('enroll_speaker', { dbPath: await getSpeakerProfilesDbPath(), name, path: clipPath })
+}
+
+export async function renameSpeakerProfile(id: number, name: string) {
+	await invoke('rename_speaker_profile', { dbPath: await getSpeakerProfilesDbPath(), id, name })
+}
+
+export async function deleteSpeakerProfile(id: number) {
+	await invoke('delete_speaker_profile', { dbPath: await getSpeakerProfilesDbPath(), id })
+}
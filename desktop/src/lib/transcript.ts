@@ -1,4 +1,5 @@
-import { formatSpeaker } from './utils'
+import { invoke } from '@tauri-apps/api/core'
+import { formatSpeaker, speakerName } from './utils'
 
 export interface Duration {
 	secs: number
@@ -9,6 +10,32 @@ export interface Transcript {
 	processing_time?: Duration
 	segments: Segment[]
 	word_segments?: Segment[]
+	// Language code, when it was given or detected
+	language?: string
+}
+
+export interface LanguageProbability {
+	code: string
+	name: string
+	probability: number
+}
+
+// Rank the languages spoken in the first seconds of a file with the loaded model
+export async function detectLanguage(path: string, seconds?: number) {
+	return await invoke<LanguageProbability[]>('detect_language', { path, seconds })
+}
+
+export interface Word {
+	start: number
+	stop: number
+	text: string
+	probability: number
+}
+
+export interface Confidence {
+	avg_logprob: number
+	no_speech_prob?: number
+	compression_ratio: number
 }
 
 export interface Segment {
@@ -16,6 +43,47 @@ export interface Segment {
 	stop: number
 	text: string
 	speaker?: string
+	words?: Word[]
+	confidence?: Confidence
+	hallucination?: 'repetition' | 'known_phrase' | 'chars_per_second'
+	// Detected language code, when transcribed with multilingual
+	language?: string
+	// Shown under the text in bilingual subtitles
+	translation?: string
+	// Several speakers talking at once, found by diarization
+	overlap?: Overlap
+}
+
+// Diarization finds the speakers of the whole audio before transcribing it
+export type ProgressPhase = 'segmentation' | 'embedding' | 'transcription'
+
+export interface Progress {
+	phase: ProgressPhase
+	// Starts over in every phase
+	percent: number
+}
+
+export interface Overlap {
+	start: number
+	stop: number
+	// The speaker of the segment first
+	speakers: string[]
+}
+
+export type TranslatorOptions =
+	| { type: 'local'; command: string; args?: string[]; model_path?: string }
+	| { type: 'openai'; url: string; model: string; api_key?: string }
+	| { type: 'libretranslate'; url: string; api_key?: string }
+
+export interface TranslateOptions {
+	target: string
+	source?: string
+	mode?: 'translated' | 'bilingual'
+	backend: TranslatorOptions
+}
+
+export async function translateSegments(segments: Segment[], options: TranslateOptions) {
+	return await invoke<Segment[]>('translate_segments', { segments, options })
 }
 
 export function formatTimestamp(seconds: number, alwaysIncludeHours: boolean, decimalMarker: string, includeMilliseconds: boolean = true): string {
@@ -45,6 +113,11 @@ export function formatTimestamp(seconds: number, alwaysIncludeHours: boolean, de
 	return result
 }
 
+// Overlapping speech is kept apart from the turns of its speaker
+function speakerKey(segment: Segment) {
+	return segment.overlap && segment.overlap.speakers.length > 1 ? segment.overlap.speakers.join('+') : segment.speaker
+}
+
 export function mergeSpeakerSegments(segments: Segment[]) {
 	let currentSpeaker: string | undefined
 	const newSegments: Segment[] = []
@@ -53,7 +126,7 @@ export function mergeSpeakerSegments(segments: Segment[]) {
 	if (segments?.[0]?.speaker) {
 		for (const segment of segments) {
 			// First segment or speaker change
-			if (!currentSpeaker || segment.speaker !== currentSpeaker) {
+			if (!currentSpeaker || speakerKey(segment) !== currentSpeaker) {
 				// If it's not the first segment, push the previous segment
 				if (currentSpeaker !== undefined) {
 					newSegments.push(currentSegment)
@@ -67,7 +140,7 @@ export function mergeSpeakerSegments(segments: Segment[]) {
 				currentSegment.stop = segment.stop // Update the stop time
 			}
 
-			currentSpeaker = segment.speaker // Update the current speaker
+			currentSpeaker = speakerKey(segment) // Update the current speaker
 		}
 
 		// Push the last segment after the loop
@@ -85,7 +158,7 @@ export function asSrt(segments: Segment[], speakerPrefix = 'Speaker') {
 			transcript +
 			`${i > 0 ? '\n' : ''}${i + 1}\n` +
 			`${formatTimestamp(segment.start, true, ',')} --> ${formatTimestamp(segment.stop, true, ',')}\n` +
-			`${segment.speaker ? formatSpeaker(segment.speaker, speakerPrefix) : ''}${segment.text.trim().replace('-->', '->')}\n`
+			`${segment.speaker ? formatSpeaker(segment.speaker, speakerPrefix, segment.overlap) : ''}${segment.text.trim().replace('-->', '->')}\n`
 		)
 	}, '')
 }
@@ -96,16 +169,22 @@ export function asVtt(segments: Segment[], speakerPrefix = 'Speaker') {
 		return (
 			transcript +
 			`${formatTimestamp(segment.start, false, '.')} --> ${formatTimestamp(segment.stop, false, '.')}\n` +
-			`${segment.speaker ? formatSpeaker(segment.speaker, speakerPrefix) : ''}${segment.text.trim().replace('-->', '->')}\n`
+			`${segment.speaker ? formatVoice(segment.speaker, speakerPrefix) : ''}${segment.text.trim().replace('-->', '->')}\n`
 		)
 	}, '')
 }
 
+// WebVTT voice tag, so players can show and style the speaker
+function formatVoice(speaker: string, speakerPrefix: string) {
+	const label = speakerName(speaker, speakerPrefix).replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;')
+	return `<v ${label}>`
+}
+
 export function asText(segments: Segment[], speakerPrefix = 'Speaker') {
 	segments = mergeSpeakerSegments(segments)
 	return segments.reduce((transcript, segment) => {
 		return (
-			transcript + `${segment.speaker ? formatSpeaker(segment.speaker, speakerPrefix) + '\n' : ''}${segment.text.trim()}\n${segment.speaker ? '\n' : ''}`
+			transcript + `${segment.speaker ? formatSpeaker(segment.speaker, speakerPrefix, segment.overlap) + '\n' : ''}${segment.text.trim()}\n${segment.speaker ? '\n' : ''}`
 		)
 	}, '')
 }
@@ -113,3 +192,62 @@ export function asText(segments: Segment[], speakerPrefix = 'Speaker') {
 export function asJson(segments: Segment[]) {
 	return JSON.stringify(segments, null, 4)
 }
+
+// How diarized speakers are labelled by the core writers
+export interface SpeakerLabels {
+	prefix?: string
+	// Real names by speaker id
+	names?: Record<string, string>
+	// Label only the first segment of every speaker turn
+	on_change_only?: boolean
+}
+
+export interface SubtitleLayout {
+	max_chars_per_line: number
+	max_lines: number
+	min_duration: number
+	max_duration: number
+	max_chars_per_second: number
+}
+
+export const defaultSubtitleLayout: SubtitleLayout = {
+	max_chars_per_line: 42,
+	max_lines: 2,
+	min_duration: 0.83,
+	max_duration: 7,
+	max_chars_per_second: 20,
+}
+
+// Formats made of timed cues, which the subtitle layout applies to
+export const subtitleFormats = ['srt', 'vtt', 'sbv', 'ttml']
+
+// Split and reflow segments into subtitle cues
+export async function layoutSegments(segments: Segment[], layout: SubtitleLayout) {
+	return await invoke<Segment[]>('layout_segments', { segments, layout })
+}
+
+// Render subtitles with the layout applied. Speakers are merged first so the layout splits their turns
+export async function asSubtitles(segments: Segment[], format: string, layout: SubtitleLayout, speakers?: SpeakerLabels) {
+	const cues = await layoutSegments(mergeSpeakerSegments(segments), layout)
+	// Cues split from the same turn are labelled once
+	return await formatSegments(cues, format, { on_change_only: true, ...speakers })
+}
+
+// Editing operations of the core. Timestamps are centiseconds like in segments
+export type SegmentEdit =
+	| { type: 'merge'; first: number; last: number }
+	| { type: 'split'; index: number; at: { word: number } | { time: number } }
+	| { type: 'shift'; offset: number }
+	| { type: 'scale'; factor: number }
+	| { type: 'rename_speaker'; from: string; to: string }
+	| { type: 'merge_speakers'; speakers: string[]; into: string }
+	| { type: 'replace'; pattern: string; replacement: string }
+
+export async function editSegments(segments: Segment[], edit: SegmentEdit) {
+	return await invoke<Segment[]>('edit_segments', { segments, edit })
+}
+
+// Render segments with the transcript writers of the core
+export async function formatSegments(segments: Segment[], format: string, speakers?: SpeakerLabels) {
+	return await invoke<string>('format_transcript', { segments, format, speakers: speakers ?? null })
+}
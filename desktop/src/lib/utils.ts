@@ -82,8 +82,21 @@ export async function openPath(path: NamedPath) {
 
 export async function getModelsFolder() {}
 
-export function formatSpeaker(speaker?: string, prefix = 'Speaker') {
-	return `${prefix} ${speaker ?? '?'}: `
+export function speakerName(speaker: string | undefined, prefix: string) {
+	// Enrolled speakers are already named
+	if (speaker && isNaN(Number(speaker))) {
+		return speaker
+	}
+	// Speaker ids start from 0, labels from 1
+	return `${prefix} ${speaker ? Number(speaker) + 1 : '?'}`
+}
+
+export function formatSpeaker(speaker?: string, prefix = 'Speaker', overlap?: { speakers: string[] }) {
+	// Every speaker of overlapping speech is named
+	if (overlap && overlap.speakers.length > 1) {
+		return `${overlap.speakers.map((speaker) => speakerName(speaker, prefix)).join(' & ')}: `
+	}
+	return `${speakerName(speaker, prefix)}: `
 }
 
 export async function startKeepAwake() {
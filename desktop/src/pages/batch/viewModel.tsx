@@ -1,8 +1,8 @@
 import { invoke } from '@tauri-apps/api/core'
 import { useEffect, useRef, useState } from 'react'
 import { useLocation, useNavigate } from 'react-router-dom'
-import { TextFormat, formatExtensions } from '~/components/FormatSelect'
-import { Segment, Transcript, asJson, asSrt, asText, asVtt } from '~/lib/transcript'
+import { TextFormat, coreFormats, formatExtensions } from '~/components/FormatSelect'
+import { Segment, Transcript, asJson, asSrt, asSubtitles, asText, asVtt, formatSegments, subtitleFormats } from '~/lib/transcript'
 import { NamedPath, pathToNamedPath, startKeepAwake, stopKeepAwake } from '~/lib/utils'
 import * as webview from '@tauri-apps/api/webviewWindow'
 import * as dialog from '@tauri-apps/plugin-dialog'
@@ -18,6 +18,16 @@ import * as transcript from '~/lib/transcript'
 import { path } from '@tauri-apps/api'
 import { toDocx } from '~/lib/docx'
 import toast from 'react-hot-toast'
+import { getSpeakerProfilesDbPath } from '~/lib/speakers'
+
+type JobStatus = { status: 'queued' | 'running' | 'paused' | 'done' | 'cancelled' } | { status: 'failed'; error: string }
+
+// Events of transcribe_batch for the job at index id of the submitted paths
+type BatchEvent =
+	| { type: 'status'; id: number; status: JobStatus }
+	| { type: 'progress'; id: number; progress: transcript.Progress }
+	| { type: 'segment'; id: number; segment: Segment }
+	| { type: 'finished'; id: number; transcript: Transcript }
 
 export function viewModel() {
 	const { files, setFiles } = useFilesContext()
@@ -28,6 +38,7 @@ export function viewModel() {
 	const [inProgress, setInProgress] = useState(false)
 	const [isAborting, setIsAborting] = useState(false)
 	const isAbortingRef = useRef<boolean>(false)
+	const jobCountRef = useRef(0)
 	const preference = usePreferenceProvider()
 	const navigate = useNavigate()
 	const [llm, setLlm] = useState<Llm | null>(null)
@@ -44,7 +55,13 @@ export function viewModel() {
 		}
 	}, [preference.llmConfig])
 
-	function getText(segments: Segment[], format: TextFormat) {
+	async function getText(segments: Segment[], format: TextFormat) {
+		if (preference.subtitleLayout && subtitleFormats.includes(format)) {
+			return await asSubtitles(segments, format, preference.subtitleLayout)
+		}
+		if (coreFormats.includes(format)) {
+			return await formatSegments(segments, format)
+		}
 		if (format === 'srt') {
 			return asSrt(segments)
 		}
@@ -144,91 +161,71 @@ export function viewModel() {
 		await invoke('load_model', { modelPath: preference.modelPath, gpuDevice: preference.gpuDevice, useGpu: preference.useGpu })
 		setCurrentIndex(localIndex)
 		const loopStartTime = performance.now()
+
+		// Destination of every job, in the order they're submitted
+		const jobs: { file: NamedPath; dst: string }[] = []
 		for (const file of files) {
-			try {
-				if (isAbortingRef.current) {
-					break
-				}
-				setProgress(null)
-				const options = {
-					path: file.path,
-					...preference.modelOptions,
-				}
-				const startTime = performance.now()
+			// Check if exists
+			const someFormat = formatExtensions[formats[0]]
+			const ext = await path.extname(file.path)
+			let dst = file.path.slice(0, -ext.length - 1) + someFormat
+			const baseName = await path.basename(dst)
+			if (!preference.advancedTranscribeOptions.saveNextToAudioFile && outputFolder) {
+				dst = await path.join(outputFolder, baseName)
+			}
 
-				// Check if exists
-				const someFormat = formatExtensions[formats[0]]
-				const ext = await path.extname(file.path)
-				let dst = file.path.slice(0, -ext.length - 1) + someFormat
-				const baseName = await path.basename(dst)
-				if (!preference.advancedTranscribeOptions.saveNextToAudioFile && outputFolder) {
-					dst = await path.join(outputFolder, baseName)
-				}
-				file.path = dst
+			if (preference.advancedTranscribeOptions.skipIfExists && !outputFolder && (await fs.exists(dst))) {
+				// ^ We can't know if it's not next to the audio file, multiple files can have the same names
+				localIndex += 1
+				continue
+			}
+			jobs.push({ file, dst })
+		}
+		setCurrentIndex(localIndex)
+		jobCountRef.current = jobs.length
 
-				if (preference.advancedTranscribeOptions.skipIfExists && !outputFolder && (await fs.exists(dst))) {
-					// ^ We can't know if it's not next to the audio file, multiple files can have the same names
-					localIndex += 1
-					setCurrentIndex(localIndex)
-					continue
+		// Results are saved while the other jobs are still running
+		const saves: Promise<void>[] = []
+		const onJobFinished = () => {
+			localIndex += 1
+			setCurrentIndex(localIndex)
+			setProgress(null)
+		}
+		const unlisten = await listen<BatchEvent>('batch_event', ({ payload }) => {
+			const job = jobs[payload.id]
+			if (payload.type === 'progress') {
+				setProgress(payload.progress.percent)
+			} else if (payload.type === 'finished') {
+				saves.push(saveTranscript(payload.transcript, job.dst).catch((error) => console.error(`error while saving ${job.file.name}: `, error)))
+			} else if (payload.type === 'status') {
+				if (payload.status.status === 'failed') {
+					console.error(`error while transcribe ${job.file.name}: `, payload.status.error)
 				}
+				if (['done', 'failed', 'cancelled'].includes(payload.status.status)) {
+					onJobFinished()
+				}
+			}
+		})
 
-				const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, enabled: preference.recognizeSpeakers }
-				const res: Transcript = await invoke('transcribe', {
-					options,
-					modelPath: preference.modelPath,
+		try {
+			if (jobs.length > 0) {
+				const diarizeOptions = { threshold: preference.diarizeThreshold, max_speakers: preference.maxSpeakers, speakers: preference.numberOfSpeakers || null, mode: preference.diarizeMode, detect_overlap: preference.detectOverlap, profiles_db: await getSpeakerProfilesDbPath(), enabled: preference.recognizeSpeakers }
+				await invoke('transcribe_batch', {
+					paths: jobs.map((job) => job.file.path),
+					// The path of every job is set from paths
+					options: { path: '', ...preference.modelOptions },
 					diarizeOptions,
 					ffmpegOptions: preference.ffmpegOptions,
 				})
-
-				// Calculate time
-				let total = Math.round((performance.now() - startTime) / 1000)
-				console.info(`Transcribe ${file.name} took ${total} seconds.`)
-
-				let llmSegments: Segment[] | null = null
-				if (llm && preference.llmConfig?.enabled) {
-					try {
-						const question = `${preference.llmConfig.prompt.replace('%s', transcript.asText(res.segments))}`
-						const answer = await llm.ask(question)
-						if (answer) {
-							llmSegments = [{ start: 0, stop: res.segments?.[res.segments?.length - 1].stop ?? 0, text: answer }]
-						}
-					} catch (e) {
-						toast.error(String(e))
-						console.error(e)
-					}
-				}
-
-				for (const format of formats) {
-					const dst = await invoke<string>('get_path_dst', { src: file.path, suffix: formatExtensions[format] })
-					// Write file
-					if (format === 'docx') {
-						const fileName = await path.basename(dst)
-						const doc = await toDocx(fileName, res.segments, preference.textAreaDirection)
-						const arrayBuffer = await doc.arrayBuffer()
-						const buffer = new Uint8Array(arrayBuffer)
-						fs.writeFile(dst, buffer)
-					} else {
-						await fs.writeTextFile(dst, getText(res.segments, format))
-					}
-				}
-				if (llmSegments) {
-					const summaryPath = await invoke<string>('get_path_dst', { src: file.path, suffix: '.summary.txt' })
-					await fs.writeTextFile(summaryPath, getText(llmSegments, 'srt'))
-				}
-				localIndex += 1
-				await new Promise((resolve) => setTimeout(resolve, 100))
-				setCurrentIndex(localIndex)
-			} catch (error) {
-				stopKeepAwake()
-				if (isAbortingRef.current) {
-					navigate('/')
-				} else {
-					console.error(`error while transcribe ${file.name}: `, error)
-				}
-				localIndex += 1
-				setCurrentIndex(localIndex)
 			}
+			await Promise.all(saves)
+		} catch (error) {
+			console.error('error while transcribe batch: ', error)
+		} finally {
+			unlisten()
+		}
+		if (isAbortingRef.current) {
+			navigate('/')
 		}
 		stopKeepAwake()
 		setCurrentIndex(files.length + 1)
@@ -247,18 +244,42 @@ export function viewModel() {
 		console.info(`Transcribed ${files.length} files in ${total}`)
 	}
 
-	async function ListenForProgress() {
-		await listen<number>('transcribe_progress', (event) => {
-			const value = event.payload
-			if (value >= 0 && value <= 100) {
-				setProgress(value)
+	async function saveTranscript(res: Transcript, src: string) {
+		let llmSegments: Segment[] | null = null
+		if (llm && preference.llmConfig?.enabled) {
+			try {
+				const question = `${preference.llmConfig.prompt.replace('%s', transcript.asText(res.segments))}`
+				const answer = await llm.ask(question)
+				if (answer) {
+					llmSegments = [{ start: 0, stop: res.segments?.[res.segments?.length - 1].stop ?? 0, text: answer }]
+				}
+			} catch (e) {
+				toast.error(String(e))
+				console.error(e)
 			}
-		})
+		}
+
+		for (const format of formats) {
+			const dst = await invoke<string>('get_path_dst', { src, suffix: formatExtensions[format] })
+			// Write file
+			if (format === 'docx') {
+				const fileName = await path.basename(dst)
+				const doc = await toDocx(fileName, res.segments, preference.textAreaDirection)
+				const arrayBuffer = await doc.arrayBuffer()
+				const buffer = new Uint8Array(arrayBuffer)
+				await fs.writeFile(dst, buffer)
+			} else {
+				await fs.writeTextFile(dst, await getText(res.segments, format))
+			}
+		}
+		if (llmSegments) {
+			const summaryPath = await invoke<string>('get_path_dst', { src, suffix: '.summary.txt' })
+			await fs.writeTextFile(summaryPath, asSrt(llmSegments))
+		}
 	}
 
 	useEffect(() => {
 		handleDrop()
-		ListenForProgress()
 	}, [])
 
 	async function cancel() {
@@ -267,7 +288,10 @@ export function viewModel() {
 		}
 		isAbortingRef.current = true
 
-		emit('abort_transcribe')
+		// Queued jobs are skipped and the running ones are aborted
+		for (let id = 0; id < jobCountRef.current; id++) {
+			emit('batch_cancel', id)
+		}
 		setIsAborting(true)
 		setInProgress(false)
 	}
@@ -129,7 +129,7 @@ export default function Home() {
 						)}
 					</div>
 					<div className="h-20" />
-					{vm.loading && <ProgressPanel isAborting={vm.isAborting} onAbort={vm.onAbort} progress={vm.progress} />}
+					{vm.loading && <ProgressPanel isAborting={vm.isAborting} onAbort={vm.onAbort} progress={vm.progress} phase={vm.progressPhase} />}
 					{vm.summarizeSegments && (
 						<div role="tablist" className="tabs tabs-lifted tabs-lg self-center">
 							<a
@@ -1,13 +1,17 @@
 import { useTranslation } from 'react-i18next'
+import { ProgressPhase } from '~/lib/transcript'
 
 interface ProgressPanelProps {
 	isAborting: boolean
 	onAbort: () => void
 	progress: number | null
+	phase: ProgressPhase | null
 }
 
-export default function ProgressPanel({ isAborting, onAbort, progress }: ProgressPanelProps) {
+export default function ProgressPanel({ isAborting, onAbort, progress, phase }: ProgressPanelProps) {
 	const { t } = useTranslation()
+	// The percent starts over in every phase
+	const phaseLabel = phase === 'segmentation' ? t('common.finding-speech') : phase === 'embedding' ? t('common.recognizing-voices') : t('common.transcribing')
 	return (
 		<div className="w-full flex flex-col items-center">
 			<div className="flex flex-row items-center text-center gap-3 bg-base-200 p-4 rounded-2xl">
@@ -16,7 +20,7 @@ export default function ProgressPanel({ isAborting, onAbort, progress }: Progres
 					<p>{t('common.aborting')}...</p>
 				) : (
 					<p>
-						{t('common.transcribing')} {progress ? `${Math.round(progress)}%` : '0%'}
+						{phaseLabel} {progress ? `${Math.round(progress)}%` : '0%'}
 					</p>
 				)}
 				{!isAborting && (
@@ -27,6 +27,7 @@ import { useFilesContext } from '~/providers/FilesProvider'
 import { ModelOptions, usePreferenceProvider } from '~/providers/Preference'
 import { useToastProvider } from '~/providers/Toast'
 import { UpdaterContext } from '~/providers/Updater'
+import { getSpeakerProfilesDbPath } from '~/lib/speakers'
 
 export interface BatchOptions {
 	files: NamedPath[]
@@ -46,6 +47,7 @@ export function viewModel() {
 	const [summarizeSegments, setSummarizeSegments] = useState<transcript.Segment[] | null>(null)
 	const [audio, setAudio] = useState<HTMLAudioElement | null>(null)
 	const [progress, setProgress] = useState<number | null>(0)
+	const [progressPhase, setProgressPhase] = useState<transcript.ProgressPhase | null>(null)
 	const { t } = useTranslation()
 	const toast = useToastProvider()
 	const [llm, setLlm] = useState<Llm | null>(null)
@@ -179,6 +181,9 @@ export function viewModel() {
 				setProgress(value)
 			}
 		})
+		await listen<transcript.ProgressPhase>('transcribe_phase', (event) => {
+			setProgressPhase(event.payload)
+		})
 		await listen<transcript.Segment>('new_segment', (event) => {
 			const { payload } = event
 			setSegments((prev) => (prev ? [...prev, payload] : [payload]))
@@ -381,7 +386,7 @@ export function viewModel() {
 				...preferenceRef.current.modelOptions,
 			}
 			const startTime = performance.now()
-			const diarizeOptions = { threshold: preferenceRef.current.diarizeThreshold, max_speakers: preferenceRef.current.maxSpeakers, enabled: preferenceRef.current.recognizeSpeakers }
+			const diarizeOptions = { threshold: preferenceRef.current.diarizeThreshold, max_speakers: preferenceRef.current.maxSpeakers, speakers: preferenceRef.current.numberOfSpeakers || null, mode: preferenceRef.current.diarizeMode, detect_overlap: preferenceRef.current.detectOverlap, profiles_db: await getSpeakerProfilesDbPath(), enabled: preferenceRef.current.recognizeSpeakers }
 			const res: transcript.Transcript = await invoke('transcribe', {
 				options,
 				modelPath,
@@ -408,6 +413,7 @@ export function viewModel() {
 			setLoading(false)
 			setIsAborting(false)
 			setProgress(null)
+			setProgressPhase(null)
 			if (!abortRef.current) {
 				// Focus back the window and play sound
 				if (preferenceRef.current.soundOnFinish) {
@@ -495,6 +501,7 @@ export function viewModel() {
 		setSettingsVisible,
 		loading,
 		progress,
+		progressPhase,
 		audio,
 		setAudio,
 		files,
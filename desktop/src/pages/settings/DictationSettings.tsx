@@ -4,6 +4,8 @@ import { useTranslation } from 'react-i18next'
 import { InfoTooltip } from '~/components/InfoTooltip'
 import { AudioDevice } from '~/lib/audio'
 import { useDictation } from '~/providers/Dictation'
+import { Glossary } from '~/providers/Preference'
+import * as dialog from '@tauri-apps/plugin-dialog'
 import toast from 'react-hot-toast'
 
 type Platform = 'linux' | 'macos' | 'windows'
@@ -114,6 +116,23 @@ export default function DictationSettings({}: DictationSettingsProps) {
 		toast.success(t('common.saved'))
 	}
 
+	async function selectGlossary() {
+		const selected = await dialog.open({
+			multiple: false,
+			filters: [{ name: 'Glossary', extensions: ['txt', 'json'] }],
+		})
+		if (!selected) {
+			return
+		}
+		try {
+			const glossary = await invoke<Glossary>('load_glossary', { path: selected })
+			dictation.setGlossary(glossary)
+			toast.success(t('common.saved'))
+		} catch (error) {
+			await dialog.message(String(error), { kind: 'error' })
+		}
+	}
+
 	function handleToggleWidget(enabled: boolean) {
 		dictation.setShowFloatingWidget(enabled)
 		toast.success(enabled ? t('common.enabled') : t('common.disabled'))
@@ -175,6 +194,27 @@ export default function DictationSettings({}: DictationSettingsProps) {
 				</select>
 			</label>
 
+			{/* Glossary */}
+			<div className="form-control w-full">
+				<div className="label">
+					<span className="label-text flex items-center gap-1">
+						<InfoTooltip text={t('common.info-glossary')} />
+						{t('common.glossary')}
+						{dictation.glossary ? ` (${dictation.glossary.terms.length} ${t('common.terms')})` : ''}
+					</span>
+				</div>
+				<div className="flex gap-2">
+					<button onClick={selectGlossary} className="btn btn-sm btn-outline flex-1">
+						{t('common.select-glossary')}
+					</button>
+					{dictation.glossary && (
+						<button onClick={() => dictation.setGlossary(null)} className="btn btn-sm btn-ghost">
+							{t('common.clear')}
+						</button>
+					)}
+				</div>
+			</div>
+
 			{/* Keyboard Shortcut Customization */}
 			<div className="form-control w-full">
 				<div className="label">
@@ -2,6 +2,7 @@ import { ReactNode, createContext, useContext, useState, useEffect } from 'react
 import { useLocalStorage } from 'usehooks-ts'
 import { isTauri } from '@tauri-apps/api/core'
 import { ModifyState } from '~/lib/utils'
+import { Glossary } from './Preference'
 
 export interface DictationSettings {
 	enabled: boolean
@@ -16,6 +17,9 @@ export interface DictationSettings {
 	setMicrophoneDeviceId: ModifyState<string | null>
 	modelName: string
 	setModelName: ModifyState<string>
+	// Loaded with the load_glossary command and passed to transcribe_dictation
+	glossary: Glossary | null
+	setGlossary: ModifyState<Glossary | null>
 }
 
 // Create the context
@@ -54,6 +58,7 @@ export function DictationProvider({ children }: { children: ReactNode }) {
 	const [keyboardShortcut, setKeyboardShortcut] = useLocalStorage('dictation_keyboard_shortcut', defaultShortcut)
 	const [microphoneDeviceId, setMicrophoneDeviceId] = useLocalStorage<string | null>('dictation_microphone_device', null)
 	const [modelName, setModelName] = useLocalStorage('dictation_model_name', 'small')
+	const [glossary, setGlossary] = useLocalStorage<Glossary | null>('dictation_glossary', null)
 
 	const settings: DictationSettings = {
 		enabled,
@@ -68,6 +73,8 @@ export function DictationProvider({ children }: { children: ReactNode }) {
 		setMicrophoneDeviceId,
 		modelName,
 		setModelName,
+		glossary,
+		setGlossary,
 	}
 
 	return <DictationContext.Provider value={settings}>{children}</DictationContext.Provider>
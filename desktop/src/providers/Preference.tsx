@@ -8,8 +8,11 @@ import WhisperLanguages from '~/assets/whisper-languages.json'
 import { useTranslation } from 'react-i18next'
 import { defaultOllamaConfig, LlmConfig } from '~/lib/llm'
 import { message } from '@tauri-apps/plugin-dialog'
+import { defaultSubtitleLayout, SubtitleLayout } from '~/lib/transcript'
 
 type Direction = 'ltr' | 'rtl'
+// Transcribe every speaker turn on its own, or the whole audio once and split it by the speaker of every word
+export type DiarizeMode = 'segments' | 'words'
 
 export interface AdvancedTranscribeOptions {
 	includeSubFolders: boolean
@@ -51,6 +54,13 @@ export interface Preference {
 	setRecognizeSpeakers: ModifyState<boolean>
 	maxSpeakers: number
 	setMaxSpeakers: ModifyState<number>
+	// Exact number of speakers, 0 finds it automatically
+	numberOfSpeakers: number
+	setNumberOfSpeakers: ModifyState<number>
+	diarizeMode: DiarizeMode
+	setDiarizeMode: ModifyState<DiarizeMode>
+	detectOverlap: boolean
+	setDetectOverlap: ModifyState<boolean>
 	diarizeThreshold: number
 	setDiarizeThreshold: ModifyState<number>
 	setLanguageDirections: () => void
@@ -70,6 +80,9 @@ export interface Preference {
 
 	advancedTranscribeOptions: AdvancedTranscribeOptions
 	setAdvancedTranscribeOptions: ModifyState<AdvancedTranscribeOptions>
+
+	subtitleLayout: SubtitleLayout | null
+	setSubtitleLayout: ModifyState<SubtitleLayout | null>
 }
 
 // Create the context
@@ -85,16 +98,26 @@ export interface FfmpegOptions {
 	custom_command: string | null
 }
 
+export interface Glossary {
+	terms: { term: string; aliases: string[] }[]
+	replacements: { from: string; to: string }[]
+	threshold?: number | null
+}
+
 export interface ModelOptions {
 	lang: string
 	verbose: boolean
 	n_threads?: number
 	init_prompt?: string
+	// Loaded with the load_glossary command
+	glossary?: Glossary | null
 	temperature?: number
 	translate?: boolean
 	max_text_ctx?: number
 	word_timestamps?: boolean
 	max_sentence_len?: number
+	// Detect the language of every chunk or diarized segment
+	multilingual?: boolean
 	sampling_strategy: 'greedy' | 'beam search'
 	sampling_bestof_or_beam_size?: number
 }
@@ -124,11 +147,15 @@ const defaultOptions = {
 	},
 	recognizeSpeakers: false,
 	maxSpeakers: 5,
+	numberOfSpeakers: 0,
+	diarizeMode: 'segments',
+	detectOverlap: false,
 	diarizeThreshold: 0.5,
 	storeRecordInDocuments: true,
 	llmConfig: defaultOllamaConfig(),
 	ytDlpVersion: null,
 	shouldCheckYtDlpVersion: true,
+	subtitleLayout: null,
 }
 
 // Preference provider component
@@ -156,6 +183,9 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 	const [ffmpegOptions, setFfmpegOptions] = useLocalStorage<FfmpegOptions>('prefs_ffmpeg_options', defaultOptions.ffmpegOptions)
 	const [recognizeSpeakers, setRecognizeSpeakers] = useLocalStorage<boolean>('prefs_recognize_speakers', defaultOptions.recognizeSpeakers)
 	const [maxSpeakers, setMaxSpeakers] = useLocalStorage<number>('prefs_max_speakers', defaultOptions.maxSpeakers)
+	const [numberOfSpeakers, setNumberOfSpeakers] = useLocalStorage<number>('prefs_number_of_speakers', defaultOptions.numberOfSpeakers)
+	const [diarizeMode, setDiarizeMode] = useLocalStorage<DiarizeMode>('prefs_diarize_mode', defaultOptions.diarizeMode)
+	const [detectOverlap, setDetectOverlap] = useLocalStorage<boolean>('prefs_detect_overlap', defaultOptions.detectOverlap)
 	const [diarizeThreshold, setDiarizeThreshold] = useLocalStorage<number>('prefs_diarize_threshold', defaultOptions.diarizeThreshold)
 	const [storeRecordInDocuments, setStoreRecordInDocuments] = useLocalStorage('prefs_store_record_in_documents', defaultOptions.storeRecordInDocuments)
 	const [llmConfig, setLlmConfig] = useLocalStorage<LlmConfig>('prefs_llm_config', defaultOptions.llmConfig)
@@ -166,6 +196,7 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		saveNextToAudioFile: true,
 		skipIfExists: true,
 	})
+	const [subtitleLayout, setSubtitleLayout] = useLocalStorage<SubtitleLayout | null>('prefs_subtitle_layout', defaultOptions.subtitleLayout)
 
 	useEffect(() => {
 		setIsFirstRun(false)
@@ -220,15 +251,20 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		setFfmpegOptions(defaultOptions.ffmpegOptions)
 		setRecognizeSpeakers(defaultOptions.recognizeSpeakers)
 		setMaxSpeakers(defaultOptions.maxSpeakers)
+		setNumberOfSpeakers(defaultOptions.numberOfSpeakers)
+		setDiarizeMode(defaultOptions.diarizeMode)
+		setDetectOverlap(defaultOptions.detectOverlap)
 		setDiarizeThreshold(defaultOptions.diarizeThreshold)
 		setStoreRecordInDocuments(defaultOptions.storeRecordInDocuments)
 		setLlmConfig(defaultOptions.llmConfig)
+		setSubtitleLayout(defaultOptions.subtitleLayout)
 		message(i18n.t('common.success-action'))
 	}
 
 	function enableSubtitlesPreset() {
 		setModelOptions({ ...preference.modelOptions, word_timestamps: true, max_sentence_len: 32 })
 		setTextFormat('srt')
+		setSubtitleLayout(defaultSubtitleLayout)
 		message(i18n.t('common.success-action'))
 	}
 
@@ -244,6 +280,12 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		setDiarizeThreshold,
 		maxSpeakers,
 		setMaxSpeakers,
+		numberOfSpeakers,
+		setNumberOfSpeakers,
+		diarizeMode,
+		setDiarizeMode,
+		detectOverlap,
+		setDetectOverlap,
 		highGraphicsPreference,
 		setHighGraphicsPreference,
 		recognizeSpeakers,
@@ -280,6 +322,8 @@ export function PreferenceProvider({ children }: { children: ReactNode }) {
 		setShouldCheckYtDlpVersion,
 		advancedTranscribeOptions,
 		setAdvancedTranscribeOptions,
+		subtitleLayout,
+		setSubtitleLayout,
 	}
 
 	return <PreferenceContext.Provider value={preference}>{children}</PreferenceContext.Provider>